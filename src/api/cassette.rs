@@ -0,0 +1,463 @@
+//! Record and replay of Gewe traffic.
+//!
+//! A [`Cassette`] attached with [`ApiClientBuilder::with_cassette`](super::ApiClientBuilder::with_cassette)
+//! either records every call (route, request body and response) to a JSON file,
+//! or replays the recorded responses without any network access. Recording
+//! against a staging container gives regression fixtures that run offline.
+//! The cassette wraps the client's [`Transport`] in a [`CassetteTransport`].
+//!
+//! Tokens and wxids are redacted before anything is written: the token becomes
+//! `<token>` and every wxid is replaced by a stable pseudonym (`wxid_redacted_1`,
+//! ..., `redacted_1@chatroom` for chatrooms), so the recorded conversations stay
+//! consistent. `wxid_...` and `...@chatroom` ids are recognized anywhere, custom
+//! ids when they appear in a field holding wxids (`toWxid`, `userName`, ...).
+//! Transport errors are recorded as well.
+//!
+//! Replaying redacts the live requests the same way: each call is answered by
+//! the first unused interaction on its route whose request matches, so the
+//! calls may come in another order than recorded. A call matching none fails.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::api::cassette::Cassette;
+//! use rgewe_api::api::ApiClientBuilder;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     // Record against a real container...
+//!     let client = ApiClientBuilder::new()
+//!         .with_token("your_token")
+//!         .with_cassette(Cassette::record("tests/cassettes/profile.json"))
+//!         .build();
+//!     client.get_profile("your_app_id").await.unwrap();
+//!
+//!     // ...and replay offline.
+//!     let client = ApiClientBuilder::new()
+//!         .with_cassette(Cassette::replay("tests/cassettes/profile.json").unwrap())
+//!         .build();
+//!     let profile = client.get_profile("your_app_id").await.unwrap();
+//!     assert_eq!(profile["ret"], 200);
+//! }
+//! ```
+use async_trait::async_trait;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use super::transport::Transport;
+
+const REDACTED_TOKEN: &str = "<token>";
+
+/// The fields holding wxids, redacted even when they are custom ids.
+const WXID_FIELDS: [&str; 14] = [
+    "wxid",
+    "wxids",
+    "wxIds",
+    "toWxid",
+    "memberWxids",
+    "ats",
+    "userName",
+    "fromUserName",
+    "toUserName",
+    "inviterUserName",
+    "adminWxid",
+    "chatroomId",
+    "chatroomOwner",
+    "chatRoomOwner",
+];
+
+/// A word of a string that may be a wxid.
+static WORD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[0-9A-Za-z_-]+(?:@chatroom)?").unwrap());
+
+/// Whether a [`Cassette`] records real traffic or replays it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// A recorded call.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Interaction {
+    pub route: String,
+    pub request: Value,
+    /// `Value::Null` if the transport failed.
+    pub response: Value,
+    /// The error of the transport, replayed instead of the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Default)]
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    /// Replay: whether each interaction was already served.
+    used: Vec<bool>,
+    /// The pseudonyms of the wxids seen so far: assigned when recording,
+    /// learned from the matched requests when replaying.
+    wxids: HashMap<String, String>,
+}
+
+/// Recorded Gewe traffic, see the [module docs](self).
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    state: Mutex<CassetteState>,
+}
+
+impl Cassette {
+    /// Records to `path`, overwriting it on the first call.
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: CassetteMode::Record,
+            state: Mutex::new(CassetteState::default()),
+        }
+    }
+
+    /// Replays the interactions recorded in `path`.
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let file: CassetteFile = serde_json::from_slice(&std::fs::read(path.as_ref())?)?;
+        let used = vec![false; file.interactions.len()];
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            mode: CassetteMode::Replay,
+            state: Mutex::new(CassetteState {
+                interactions: file.interactions,
+                used,
+                wxids: HashMap::new(),
+            }),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The interactions recorded or loaded so far.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.lock().unwrap().interactions.clone()
+    }
+
+    /// Redacts and appends a call, then rewrites the cassette file.
+    pub fn record_interaction(
+        &self,
+        token: &str,
+        route: &str,
+        request: &Value,
+        response: &Value,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut tokens = vec![token.to_string()];
+        if route == "/tools/getTokenId" {
+            if let Some(issued) = response.get("data").and_then(Value::as_str) {
+                tokens.push(issued.to_string());
+            }
+        }
+        self.push(route, request, response, None, tokens)
+    }
+
+    /// Redacts and appends a call that failed in the transport, then rewrites the cassette file.
+    pub fn record_error(
+        &self,
+        token: &str,
+        route: &str,
+        request: &Value,
+        error: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.push(
+            route,
+            request,
+            &Value::Null,
+            Some(error),
+            vec![token.to_string()],
+        )
+    }
+
+    fn push(
+        &self,
+        route: &str,
+        request: &Value,
+        response: &Value,
+        error: Option<&str>,
+        mut tokens: Vec<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut state = self.state.lock().unwrap();
+        tokens.retain(|t| !t.is_empty());
+        let error = error
+            .map(|e| redact(&Value::String(e.to_string()), &tokens, &mut state.wxids))
+            .and_then(|e| e.as_str().map(str::to_string));
+        let interaction = Interaction {
+            route: route.to_string(),
+            request: redact(request, &tokens, &mut state.wxids),
+            response: redact(response, &tokens, &mut state.wxids),
+            error,
+        };
+        state.interactions.push(interaction);
+        let file = CassetteFile {
+            interactions: state.interactions.clone(),
+        };
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&file)?)?;
+        Ok(())
+    }
+
+    /// Returns the recorded response of a call, or its recorded transport error.
+    ///
+    /// The call is answered by the first unused interaction on `route` whose
+    /// request is the redacted `request`, fails if there is none.
+    pub fn replay_interaction(
+        &self,
+        token: &str,
+        route: &str,
+        request: &Value,
+    ) -> Result<Value, Box<dyn Error>> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let tokens: Vec<String> = Some(token.to_string())
+            .filter(|t| !t.is_empty())
+            .into_iter()
+            .collect();
+        let live = rewrite(request, &mut |s| hide_tokens(s, &tokens));
+        let mut unused = 0;
+        let mut found = None;
+        for (i, interaction) in state.interactions.iter().enumerate() {
+            if state.used[i] || interaction.route != route {
+                continue;
+            }
+            unused += 1;
+            let mut wxids = state.wxids.clone();
+            if matches(&interaction.request, &live, &mut wxids) {
+                state.wxids = wxids;
+                found = Some(i);
+                break;
+            }
+        }
+        let Some(index) = found else {
+            return Err(if unused == 0 {
+                format!(
+                    "No recorded interaction left for route {} in {}",
+                    route,
+                    self.path.display()
+                )
+            } else {
+                format!(
+                    "No recorded interaction for route {} matches the request {} in {}",
+                    route,
+                    live,
+                    self.path.display()
+                )
+            }
+            .into());
+        };
+        state.used[index] = true;
+        let interaction = &state.interactions[index];
+        match &interaction.error {
+            Some(error) => Err(error.clone().into()),
+            None => Ok(interaction.response.clone()),
+        }
+    }
+}
+
+/// Redacts the tokens and wxids of `value`, assigning pseudonyms to the new wxids.
+fn redact(value: &Value, tokens: &[String], wxids: &mut HashMap<String, String>) -> Value {
+    let mut ids = Vec::new();
+    collect_wxids(value, &mut ids);
+    for id in ids {
+        pseudonym(&id, wxids);
+    }
+    rewrite(value, &mut |s| {
+        let s = hide_tokens(s, tokens);
+        WORD.replace_all(&s, |caps: &Captures| {
+            let word = &caps[0];
+            let is_wxid =
+                (word.starts_with("wxid_") || word.ends_with("@chatroom")) && !is_pseudonym(word);
+            match wxids.get(word) {
+                Some(pseudonym) => pseudonym.clone(),
+                None if is_wxid => pseudonym(word, wxids),
+                None => word.to_string(),
+            }
+        })
+        .into_owned()
+    })
+}
+
+/// Rewrites every string of `value` with `f`, and the `token` fields to [`REDACTED_TOKEN`].
+fn rewrite(value: &Value, f: &mut impl FnMut(&str) -> String) -> Value {
+    match value {
+        Value::String(s) => Value::String(f(s)),
+        Value::Array(items) => Value::Array(items.iter().map(|v| rewrite(v, f)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let v = if k == "token" && v.is_string() {
+                        Value::String(REDACTED_TOKEN.to_string())
+                    } else {
+                        rewrite(v, f)
+                    };
+                    (k.clone(), v)
+                })
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn hide_tokens(s: &str, tokens: &[String]) -> String {
+    tokens.iter().fold(s.to_string(), |s, token| {
+        s.replace(token.as_str(), REDACTED_TOKEN)
+    })
+}
+
+/// Collects the ids in the [`WXID_FIELDS`] of `value`, in order of appearance.
+fn collect_wxids(value: &Value, ids: &mut Vec<String>) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|v| collect_wxids(v, ids)),
+        Value::Object(map) => {
+            for (k, v) in map {
+                if WXID_FIELDS.contains(&k.as_str()) {
+                    let values = match v {
+                        Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+                        _ => v.as_str().into_iter().collect::<Vec<_>>(),
+                    };
+                    ids.extend(
+                        values
+                            .into_iter()
+                            .flat_map(|s| s.split(','))
+                            .map(str::trim)
+                            .filter(|id| WORD.find(id).is_some_and(|m| m.len() == id.len()))
+                            .filter(|id| !is_pseudonym(id))
+                            .map(str::to_string),
+                    );
+                }
+                collect_wxids(v, ids);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn pseudonym(wxid: &str, wxids: &mut HashMap<String, String>) -> String {
+    let next = wxids.len() + 1;
+    wxids
+        .entry(wxid.to_string())
+        .or_insert_with(|| {
+            if wxid.ends_with("@chatroom") {
+                format!("redacted_{}@chatroom", next)
+            } else {
+                format!("wxid_redacted_{}", next)
+            }
+        })
+        .clone()
+}
+
+fn is_pseudonym(word: &str) -> bool {
+    word.starts_with("wxid_redacted_")
+        || (word.starts_with("redacted_") && word.ends_with("@chatroom"))
+}
+
+/// Whether the recorded request `recorded` is the live request `live` with
+/// its wxids redacted, learning the pseudonyms of its new wxids.
+fn matches(recorded: &Value, live: &Value, wxids: &mut HashMap<String, String>) -> bool {
+    match (recorded, live) {
+        (Value::String(recorded), Value::String(live)) => matches_words(recorded, live, wxids),
+        (Value::Array(recorded), Value::Array(live)) => {
+            recorded.len() == live.len()
+                && recorded.iter().zip(live).all(|(r, l)| matches(r, l, wxids))
+        }
+        (Value::Object(recorded), Value::Object(live)) => {
+            recorded.len() == live.len()
+                && live
+                    .iter()
+                    .all(|(k, l)| recorded.get(k).is_some_and(|r| matches(r, l, wxids)))
+        }
+        (recorded, live) => recorded == live,
+    }
+}
+
+/// The words of `s` that may be wxids, and the text between them.
+fn split(s: &str) -> (Vec<&str>, Vec<&str>) {
+    let words = WORD.find_iter(s).map(|m| m.as_str()).collect();
+    (words, WORD.split(s).collect())
+}
+
+fn matches_words(recorded: &str, live: &str, wxids: &mut HashMap<String, String>) -> bool {
+    let (recorded_words, recorded_separators) = split(recorded);
+    let (live_words, live_separators) = split(live);
+    if recorded_words.len() != live_words.len() || recorded_separators != live_separators {
+        return false;
+    }
+    recorded_words.iter().zip(&live_words).all(|(&r, &l)| {
+        if r == l {
+            return true;
+        }
+        match wxids.get(l) {
+            Some(pseudonym) => pseudonym == r,
+            None if is_pseudonym(r) && !wxids.values().any(|p| p == r) => {
+                wxids.insert(l.to_string(), r.to_string());
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// A [`Transport`] recording the calls sent through `inner`, or replaying them from the cassette.
+pub struct CassetteTransport {
+    cassette: Cassette,
+    inner: Arc<dyn Transport>,
+}
+
+impl CassetteTransport {
+    pub fn new(cassette: Cassette, inner: Arc<dyn Transport>) -> Self {
+        Self { cassette, inner }
+    }
+
+    pub fn cassette(&self) -> &Cassette {
+        &self.cassette
+    }
+}
+
+#[async_trait]
+impl Transport for CassetteTransport {
+    async fn post(
+        &self,
+        route: &str,
+        token: &str,
+        body: Option<Value>,
+    ) -> Result<Value, Box<dyn Error>> {
+        let request = body.clone().unwrap_or(Value::Null);
+        if self.cassette.mode() == CassetteMode::Replay {
+            return self.cassette.replay_interaction(token, route, &request);
+        }
+        match self.inner.post(route, token, body).await {
+            Ok(resp) => {
+                self.cassette
+                    .record_interaction(token, route, &request, &resp)?;
+                Ok(resp)
+            }
+            Err(e) => {
+                self.cassette
+                    .record_error(token, route, &request, &e.to_string())?;
+                Err(e)
+            }
+        }
+    }
+}
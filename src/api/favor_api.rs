@@ -1,12 +1,41 @@
 use serde_json::{json, Value};
 use std::error::Error;
 
-use super::ApiClient;
-
-impl ApiClient {
+impl_params_apis! {
     impl_params_api!(
     /// Sync favor API
-    /// TODO: need add doc
+    ///
+    /// Wrapper of calling `/favor/sync` API of the service.
+    /// Lists the favorites changed since `sync_key`, together with the sync key of the next page.
+    ///
+    /// Notice:
+    /// 1. Use an empty `sync_key` for the first call.
+    /// 2. Keep calling with the returned `syncKey` until the returned list is empty,
+    ///     see [`FavorSync`](crate::favor::sync::FavorSync) which does the paging.
+    /// 3. Entries with `flag` 1 are deleted favorites.
+    ///
+    /// # Route
+    ///
+    /// `/favor/sync`
+    ///
+    /// # Parameters
+    ///
+    /// - `app_id` - The application identifier associated with the user.
+    /// - `sync_key` - The sync key returned by the last call, empty for the first call.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use rgewe_api::api::ApiClientBuilder;
+    ///     let client = ApiClientBuilder::new().with_token("your_token").build();
+    ///     let value = client.sync_favor("your_app_id", "").await.unwrap();
+    ///     assert!(value.get("ret").unwrap().as_u64().unwrap() == 200);
+    ///     let sync_key = value["data"]["syncKey"].as_str().unwrap();
+    ///     println!("next sync key: {}", sync_key);
+    /// }
+    /// ```
     sync_favor,
     "/favor/sync",
     ("appId", app_id, &str),
@@ -22,7 +51,31 @@ impl ApiClient {
 
     impl_params_api!(
     /// Get favor content API
-    /// TODO: need add doc
+    ///
+    /// Wrapper of calling `/favor/getContent` API of the service.
+    /// Gets the content of a favorite, the `content` field of `data` holds the `<favitem>` XML,
+    /// see [`Favorite::from_data`](crate::favor::item::Favorite::from_data).
+    ///
+    /// # Route
+    ///
+    /// `/favor/getContent`
+    ///
+    /// # Parameters
+    ///
+    /// - `app_id` - The application identifier associated with the user.
+    /// - `fav_id` - The favorite id returned by [`ApiClient::sync_favor`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use rgewe_api::api::ApiClientBuilder;
+    ///     let client = ApiClientBuilder::new().with_token("your_token").build();
+    ///     let value = client.get_favor_content("your_app_id", 1).await.unwrap();
+    ///     assert!(value.get("ret").unwrap().as_u64().unwrap() == 200);
+    /// }
+    /// ```
     get_favor_content,
     "/favor/getContent",
     ("appId", app_id, &str),
@@ -38,7 +91,30 @@ impl ApiClient {
 
     impl_params_api!(
     /// Delete favor with favor id API
-    /// TODO: need add doc
+    ///
+    /// Wrapper of calling `/favor/delete` API of the service.
+    /// Deletes a favorite.
+    ///
+    /// # Route
+    ///
+    /// `/favor/delete`
+    ///
+    /// # Parameters
+    ///
+    /// - `app_id` - The application identifier associated with the user.
+    /// - `fav_id` - The favorite id returned by [`ApiClient::sync_favor`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use rgewe_api::api::ApiClientBuilder;
+    ///     let client = ApiClientBuilder::new().with_token("your_token").build();
+    ///     let value = client.delete_favor("your_app_id", 1).await.unwrap();
+    ///     assert!(value.get("ret").unwrap().as_u64().unwrap() == 200);
+    /// }
+    /// ```
     delete_favor,
     "/favor/delete",
     ("appId", app_id, &str),
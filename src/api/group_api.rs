@@ -1,9 +1,9 @@
 use serde_json::{json, Value};
 use std::error::Error;
 
-use super::{ApiClient, Wxid};
+use super::Wxid;
 
-impl ApiClient {
+impl_params_apis! {
     impl_params_api!(
     /// Ctreate chatroom/group with at least two users
     /// TODO: need add doc
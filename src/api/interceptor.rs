@@ -0,0 +1,75 @@
+//! Hooks run around every call of an [`ApiClient`](super::ApiClient).
+//!
+//! Interceptors added with [`ApiClientBuilder::with_interceptor`](super::ApiClientBuilder::with_interceptor)
+//! see every call made through [`ApiClient::gewe_post_json`](super::ApiClient::gewe_post_json),
+//! i.e. every method generated by `impl_params_api!`, with the route and json body:
+//!
+//! - [`Interceptor::before_request`] may modify the body or veto the call by returning an error,
+//! - [`Interceptor::after_response`] receives the response of a completed call,
+//! - [`Interceptor::on_error`] receives the error of a vetoed or failed call.
+//!
+//! Interceptors run in the order they were added. Note that Gewe reports most
+//! failures with a non-200 `ret` in a successful response, see
+//! [`response_data`](super::response_data).
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::api::interceptor::Interceptor;
+//! use rgewe_api::api::ApiClientBuilder;
+//! use serde_json::Value;
+//! use std::error::Error;
+//!
+//! /// Refuses to send texts containing banned words.
+//! struct ContentFilter;
+//!
+//! impl Interceptor for ContentFilter {
+//!     fn before_request(&self, route: &str, body: &mut Value) -> Result<(), Box<dyn Error>> {
+//!         let content = body.get("content").and_then(Value::as_str).unwrap_or_default();
+//!         if route == "/message/postText" && content.contains("banned") {
+//!             return Err("Text contains a banned word".into());
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let client = ApiClientBuilder::new()
+//!     .with_token("your_token")
+//!     .with_interceptor(ContentFilter)
+//!     .build();
+//! ```
+use serde_json::Value;
+use std::error::Error;
+use std::sync::Arc;
+
+/// Hooks run around every API call, see the [module docs](self).
+///
+/// All hooks default to doing nothing.
+pub trait Interceptor: Send + Sync {
+    /// Called before the call is sent, `body` is `Value::Null` for calls without body.
+    ///
+    /// Returning an error vetoes the call, which then fails with that error.
+    fn before_request(&self, _route: &str, _body: &mut Value) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Called with the response of a completed call.
+    fn after_response(&self, _route: &str, _body: &Value, _response: &Value) {}
+
+    /// Called when a call was vetoed or failed.
+    fn on_error(&self, _route: &str, _body: &Value, _error: &dyn Error) {}
+}
+
+impl<T: Interceptor + ?Sized> Interceptor for Arc<T> {
+    fn before_request(&self, route: &str, body: &mut Value) -> Result<(), Box<dyn Error>> {
+        (**self).before_request(route, body)
+    }
+
+    fn after_response(&self, route: &str, body: &Value, response: &Value) {
+        (**self).after_response(route, body, response)
+    }
+
+    fn on_error(&self, route: &str, body: &Value, error: &dyn Error) {
+        (**self).on_error(route, body, error)
+    }
+}
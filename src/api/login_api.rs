@@ -1,9 +1,7 @@
 use serde_json::{json, Value};
 use std::error::Error;
 
-use super::ApiClient;
-
-impl ApiClient {
+impl_params_apis! {
     impl_params_api!(
     /// Get token API
     ///
@@ -2,9 +2,11 @@
 use serde_json::{json, Value};
 use std::error::Error;
 
-use super::{ApiClient, Wxid};
+use super::{response_data, ApiClient, Wxid};
+use crate::callback::{Message, MessageType, Quotable};
+use crate::xml;
 
-impl ApiClient {
+impl_params_apis! {
     impl_params_api!(
     /// Send a text message
     ///
@@ -437,4 +439,205 @@ impl ApiClient {
     //     });
     //     util::gewe_post_json("/message/revokeMsg", Some(params)).await
     // }
+
+    impl_params_api!(
+    /// Download CDN file API
+    ///
+    /// Wrapper of calling `/message/downloadCdn` API of the service.
+    /// Downloads a file stored on the WeChat CDN (e.g. favorite attachments) to the Gewe service.
+    ///
+    /// Notice:
+    /// 1. `data.fileUrl` of the response is relative to the download service of the
+    ///     Gewe container (port 2532, e.g. `http://localhost:2532/download/`).
+    ///
+    /// # Route
+    ///
+    /// `/message/downloadCdn`
+    ///
+    /// # Parameters
+    ///
+    /// - `app_id` - The application identifier associated with the user.
+    /// - `aes_key` - The CDN AES key of the file (e.g. `cdn_datakey` of a favorite).
+    /// - `file_id` - The CDN file id (e.g. `cdn_dataurl` of a favorite).
+    /// - `file_type` - `1` HD image, `2` image, `3` thumbnail, `4` video, `5` file.
+    /// - `total_size` - The file size in bytes.
+    /// - `suffix` - The file extension, e.g. `pdf`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use rgewe_api::api::ApiClientBuilder;
+    ///     let client = ApiClientBuilder::new().with_token("your_token").build();
+    ///     let value = client
+    ///         .download_cdn("your_app_id", "aes_key", "file_id", "5", "2048", "pdf")
+    ///         .await
+    ///         .unwrap();
+    ///     assert!(value.get("ret").unwrap().as_u64().unwrap() == 200);
+    /// }
+    /// ```
+    download_cdn,
+    "/message/downloadCdn",
+    ("appId", app_id, &str),
+    ("aesKey", aes_key, &str),
+    ("fileId", file_id, &str),
+    ("type", file_type, &str),
+    ("totalSize", total_size, &str),
+    ("suffix", suffix, &str));
+}
+
+impl ApiClient {
+    /// Sends `text` to the conversation of `original`, quoting it.
+    ///
+    /// Builds the `<refermsg>` of a quote (appmsg type 57) for
+    /// [`ApiClient::post_app_msg`], from a received [`Message`](crate::callback::Message)
+    /// or a [`StoredMessage`](crate::store::StoredMessage).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use rgewe_api::api::ApiClient;
+    /// use rgewe_api::callback::Message;
+    ///
+    /// async fn answer(client: &ApiClient, question: &Message) {
+    ///     client.reply_to(question, "noon").await.unwrap();
+    /// }
+    /// ```
+    pub async fn reply_to(
+        &self,
+        original: &impl Quotable,
+        text: &str,
+    ) -> Result<Value, Box<dyn Error>> {
+        let to = Wxid::try_from(original.chat_id())?;
+        let appmsg = original.refer_msg().to_appmsg(text);
+        self.post_app_msg(original.app_id(), &to, &appmsg).await
+    }
+}
+
+/// The outcome of forwarding a message to one recipient, see [`ApiClient::forward`].
+#[derive(Debug)]
+pub struct Forwarded {
+    pub to: Wxid,
+    /// The `data` of the send response, or why the send failed.
+    pub result: Result<Value, Box<dyn Error>>,
+}
+
+impl ApiClient {
+    /// Forwards a received message to every recipient of `to`, one after the other.
+    ///
+    /// The route depends on the message type:
+    ///
+    /// - text, and the reply text of quotes: [`ApiClient::post_text`],
+    /// - images: [`ApiClient::forward_image`],
+    /// - videos: [`ApiClient::forward_video`],
+    /// - files (appmsg type 6): [`ApiClient::forward_file`],
+    /// - links (appmsg type 5): [`ApiClient::forward_url`],
+    /// - mini-apps (appmsg type 33/36): [`ApiClient::forward_mini_app`], with
+    ///   the `<thumburl>` of the message as cover.
+    ///
+    /// Fails without sending anything for other messages. Otherwise returns
+    /// the outcome for each recipient, in the order of `to`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use rgewe_api::api::{ApiClient, Wxid};
+    /// use rgewe_api::callback::Message;
+    ///
+    /// async fn share(client: &ApiClient, msg: &Message) {
+    ///     let to = [
+    ///         Wxid::try_from("wxid_alice").unwrap(),
+    ///         Wxid::try_from("34757816141@chatroom").unwrap(),
+    ///     ];
+    ///     for forwarded in client.forward(msg, &to).await.unwrap() {
+    ///         if let Err(e) = forwarded.result {
+    ///             println!("failed to forward to {}: {}", forwarded.to, e);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub async fn forward(
+        &self,
+        message: &Message,
+        to: &[Wxid],
+    ) -> Result<Vec<Forwarded>, Box<dyn Error>> {
+        let route = ForwardRoute::of(message)?;
+        let app_id = &message.app_id;
+        let xml = message.text();
+        let mut forwarded = Vec::with_capacity(to.len());
+        for to_wxid in to {
+            let ret = match &route {
+                ForwardRoute::Text(content) => self.post_text(app_id, to_wxid, content, "").await,
+                ForwardRoute::Image => self.forward_image(app_id, to_wxid, xml).await,
+                ForwardRoute::Video => self.forward_video(app_id, to_wxid, xml).await,
+                ForwardRoute::File => self.forward_file(app_id, to_wxid, xml).await,
+                ForwardRoute::Url => self.forward_url(app_id, to_wxid, xml).await,
+                ForwardRoute::MiniApp(cover) => {
+                    self.forward_mini_app(app_id, to_wxid, xml, cover).await
+                }
+            };
+            forwarded.push(Forwarded {
+                to: to_wxid.clone(),
+                result: ret.and_then(response_data),
+            });
+        }
+        Ok(forwarded)
+    }
+}
+
+/// How a message is forwarded, see [`ApiClient::forward`].
+enum ForwardRoute {
+    Text(String),
+    Image,
+    Video,
+    File,
+    Url,
+    /// With the cover image URL.
+    MiniApp(String),
+}
+
+impl ForwardRoute {
+    fn of(message: &Message) -> Result<Self, Box<dyn Error>> {
+        let route = match message.message_type() {
+            MessageType::Text => ForwardRoute::Text(message.text().to_string()),
+            MessageType::Image => ForwardRoute::Image,
+            MessageType::Video => ForwardRoute::Video,
+            MessageType::AppMsg => match message.app_msg() {
+                Some(app_msg) if app_msg.kind == 5 => ForwardRoute::Url,
+                Some(app_msg) if app_msg.kind == 6 => ForwardRoute::File,
+                Some(app_msg) if matches!(app_msg.kind, 33 | 36) => {
+                    let cover = xml::parse(message.text())
+                        .and_then(|doc| xml::text(doc.root(), "thumburl"))
+                        .unwrap_or_default();
+                    ForwardRoute::MiniApp(cover)
+                }
+                Some(app_msg) if app_msg.kind == 57 => ForwardRoute::Text(app_msg.title),
+                Some(app_msg) => {
+                    return Err(
+                        format!("Cannot forward app messages of type {}", app_msg.kind).into(),
+                    )
+                }
+                None => return Err("Cannot forward an app message without <appmsg>".into()),
+            },
+            other => return Err(format!("Cannot forward {:?} messages", other).into()),
+        };
+        Ok(route)
+    }
+}
+
+impl crate::blocking::ApiClient {
+    /// Blocking version of [`ApiClient::forward`].
+    pub fn forward(
+        &self,
+        message: &Message,
+        to: &[Wxid],
+    ) -> Result<Vec<Forwarded>, Box<dyn Error>> {
+        self.block_on(self.inner().forward(message, to))
+    }
+
+    /// Blocking version of [`ApiClient::reply_to`].
+    pub fn reply_to(&self, original: &impl Quotable, text: &str) -> Result<Value, Box<dyn Error>> {
+        self.block_on(self.inner().reply_to(original, text))
+    }
 }
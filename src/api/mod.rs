@@ -1,8 +1,13 @@
 #![allow(clippy::too_many_arguments)]
-use reqwest::{header::HeaderMap, Client};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::error::Error;
+use std::sync::Arc;
+
+use cassette::{Cassette, CassetteTransport};
+use interceptor::Interceptor;
+use sent::SentMessages;
+use transport::{HttpTransport, Transport};
 
 const BASE_URL: &str = "http://localhost:2531/v2/api";
 const HEADER_GEWE: &str = "X-GEWE-TOKEN";
@@ -20,40 +25,43 @@ pub async fn test_get_ip() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-async fn post_json(
-    url: &str,
-    with_proxy: bool,
-    headers: HeaderMap,
-    body: &Option<Value>,
-) -> Result<Value, Box<dyn Error>> {
-    let client = if with_proxy {
-        // reqwest client use system proxy as default
-        Client::new()
-    } else {
-        Client::builder().no_proxy().build().unwrap()
-    };
-
-    let rest = client
-        .post(url)
-        // TODO: change headers to ref?
-        .headers(headers)
-        .json(body)
-        .send()
-        .await?
-        .json()
-        .await?;
-    Ok(rest)
-}
-
-#[derive(Debug, Default)]
 pub struct ApiClient {
     pub token: String,
+    /// The base url the client was built with. The [`HttpTransport`] captures
+    /// it at build time, so assigning it later does not redirect the calls.
     pub base_url: String,
+    transport: Arc<dyn Transport>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    sent: SentMessages,
+    #[cfg(feature = "tracing")]
+    trace_content: bool,
+}
+
+impl std::fmt::Debug for ApiClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiClient")
+            .field("token", &self.token)
+            .field("base_url", &self.base_url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ApiClient {
+    fn default() -> Self {
+        ApiClientBuilder::new().build()
+    }
 }
 
 pub struct ApiClientBuilder {
     token: Option<String>,
     base_url: Option<String>,
+    transport: Option<Arc<dyn Transport>>,
+    cassette: Option<Cassette>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<crate::metrics::Metrics>>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    #[cfg(feature = "tracing")]
+    trace_content: bool,
 }
 impl Default for ApiClientBuilder {
     fn default() -> Self {
@@ -66,6 +74,13 @@ impl ApiClientBuilder {
         Self {
             token: None,
             base_url: None,
+            transport: None,
+            cassette: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            interceptors: Vec::new(),
+            #[cfg(feature = "tracing")]
+            trace_content: false,
         }
     }
     pub fn with_token(mut self, token: &str) -> Self {
@@ -76,10 +91,57 @@ impl ApiClientBuilder {
         self.base_url = Some(base_url.to_string());
         self
     }
+    /// Sends the calls through `transport` instead of an [`HttpTransport`] to the base url.
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+    /// Records the traffic to, or replays it from, a [`Cassette`].
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+    /// Records the calls in a [`Metrics`](crate::metrics::Metrics) registry.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<crate::metrics::Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    /// Adds an [`Interceptor`] run around every call, after the ones added before.
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+    /// Logs message content and other sensitive request fields unredacted in the traces.
+    #[cfg(feature = "tracing")]
+    pub fn with_trace_content(mut self, enabled: bool) -> Self {
+        self.trace_content = enabled;
+        self
+    }
+    /// Builds a [`blocking::ApiClient`](crate::blocking::ApiClient) for synchronous code.
+    pub fn build_blocking(self) -> Result<crate::blocking::ApiClient, Box<dyn Error>> {
+        crate::blocking::ApiClient::new(self.build())
+    }
     pub fn build(self) -> ApiClient {
+        let base_url = self.base_url.unwrap_or_else(|| BASE_URL.to_string());
+        let mut transport = self
+            .transport
+            .unwrap_or_else(|| Arc::new(HttpTransport::new(&base_url)));
+        if let Some(cassette) = self.cassette {
+            transport = Arc::new(CassetteTransport::new(cassette, transport));
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.metrics {
+            transport = Arc::new(crate::metrics::MetricsTransport::new(metrics, transport));
+        }
         ApiClient {
             token: self.token.unwrap_or_default(),
-            base_url: self.base_url.unwrap_or_else(|| BASE_URL.to_string()),
+            base_url,
+            transport,
+            interceptors: self.interceptors,
+            sent: SentMessages::default(),
+            #[cfg(feature = "tracing")]
+            trace_content: self.trace_content,
         }
     }
 }
@@ -93,9 +155,6 @@ impl ApiClient {
         route: &str,
         body: Option<Value>,
     ) -> Result<Value, Box<dyn Error>> {
-        let mut headers = HeaderMap::new();
-        headers.insert(HEADER_GEWE, self.token.parse().unwrap());
-
         // Check json body
         if body.is_none() {
             // Empty json body
@@ -104,14 +163,75 @@ impl ApiClient {
                 return Err(format!("Empty json body for route: {}", route).into());
             }
         }
-        let url = format!("{}{}", BASE_URL, route);
-        let with_proxy = false;
-        // TODO: add proxy configuration
-        post_json(&url, with_proxy, headers, &body).await
+        let target = SentMessages::target(route, body.as_ref());
+        #[cfg(feature = "tracing")]
+        let ret = {
+            use tracing::Instrument;
+            let span = crate::trace::api_span(route, body.as_ref(), self.trace_content);
+            let start = std::time::Instant::now();
+            let ret = self.send(route, body).instrument(span.clone()).await;
+            crate::trace::record_api_result(&span, &ret, start.elapsed());
+            ret
+        };
+        #[cfg(not(feature = "tracing"))]
+        let ret = self.send(route, body).await;
+        if let (Some(target), Ok(resp)) = (target, &ret) {
+            self.sent.track(route, target, resp);
+        }
+        ret
+    }
+
+    async fn send(&self, route: &str, body: Option<Value>) -> Result<Value, Box<dyn Error>> {
+        if self.interceptors.is_empty() {
+            return self.transport.post(route, &self.token, body).await;
+        }
+
+        let mut body = body.unwrap_or(Value::Null);
+        // No error may be held across the await below, or the future would not be `Send`.
+        if let Err(e) = self
+            .interceptors
+            .iter()
+            .try_for_each(|i| i.before_request(route, &mut body))
+        {
+            let ret = Err(e);
+            self.notify_interceptors(route, &body, &ret);
+            return ret;
+        }
+        let ret = self
+            .transport
+            .post(route, &self.token, Some(body.clone()))
+            .await;
+        self.notify_interceptors(route, &body, &ret);
+        ret
+    }
+
+    fn notify_interceptors(&self, route: &str, body: &Value, ret: &Result<Value, Box<dyn Error>>) {
+        match ret {
+            Ok(resp) => self
+                .interceptors
+                .iter()
+                .for_each(|i| i.after_response(route, body, resp)),
+            Err(e) => self
+                .interceptors
+                .iter()
+                .for_each(|i| i.on_error(route, body, e.as_ref())),
+        }
     }
 }
 
-/// Represents a WeChat ID (Wxid) that must start with "wxid_" and be within a valid length range.
+/// Checks the `ret` code of a Gewe response and returns its `data`.
+///
+/// Gewe answers HTTP 200 even for failed requests, the outcome is reported by
+/// `ret` (200 on success) and `msg`.
+pub fn response_data(mut ret: Value) -> Result<Value, Box<dyn Error>> {
+    match ret.get("ret").and_then(Value::as_u64) {
+        Some(200) => Ok(ret.get_mut("data").map(Value::take).unwrap_or(Value::Null)),
+        _ => Err(format!("Gewe request failed: {}", ret).into()),
+    }
+}
+
+/// Represents a WeChat ID (Wxid): an original `wxid_` id, a custom id chosen by
+/// the user, a chatroom id ending in `@chatroom`, or an id like `filehelper`.
 /// - Unnamed single-field struct → Serialized directly as the field’s value (used here)
 /// - Unnamed multi-field struct → Serialized as a JSON array
 /// - Named multi-field struct → Serialized as a JSON object
@@ -130,10 +250,11 @@ impl TryFrom<&str> for Wxid {
 
     /// Attempts to create a Wxid from a string slice.
     ///
-    /// Fails if the string does not start with "wxid_" or is not within the valid length range.
+    /// Fails if the string is empty, longer than 64 characters, or contains
+    /// anything but ASCII letters, digits, `_`, `-`, `@` and `.`.
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        if value.starts_with("wxid_") || value.ends_with("@chatroom") {
-            // TODO value.len() > 5 && value.len() < 50
+        let valid_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '@' | '.');
+        if !value.is_empty() && value.len() <= 64 && value.chars().all(valid_char) {
             Ok(Wxid(value.to_string()))
         } else {
             Err(format!("Invalid Wxid format: {}", value))
@@ -147,14 +268,35 @@ impl std::fmt::Display for Wxid {
     }
 }
 
+/// Expands the [`impl_params_api!`] invocations of an API module.
+macro_rules! impl_params_apis {
+  ($(impl_params_api!($($def:tt)*);)*) => {
+      $(impl_params_api!($($def)*);)*
+  };
+}
+
+/// Generates an API method of [`ApiClient`] posting its parameters as json to `$route`,
+/// and the same method on [`blocking::ApiClient`](crate::blocking::ApiClient).
 macro_rules! impl_params_api {
   ($(#[$meta:meta])* $f_name:ident, $route:expr, $(($p_k:expr, $p_v:ident, $p_t:ty)),* $(,)? ) => {
-      $(#[$meta])*
-      pub async fn $f_name(&self, $($p_v: $p_t),*) -> Result<Value, Box<dyn Error>> {
-          let p = json!({
-            $($p_k: $p_v),*
-          });
-          self.gewe_post_json($route, Some(p)).await
+      impl $crate::api::ApiClient {
+          $(#[$meta])*
+          pub async fn $f_name(&self, $($p_v: $p_t),*) -> Result<Value, Box<dyn Error>> {
+              let p = json!({
+                $($p_k: $p_v),*
+              });
+              self.gewe_post_json($route, Some(p)).await
+          }
+      }
+
+      impl $crate::blocking::ApiClient {
+          #[doc = concat!(
+              "Blocking version of [`ApiClient::", stringify!($f_name),
+              "`](crate::api::ApiClient::", stringify!($f_name), "), calling `", $route, "`."
+          )]
+          pub fn $f_name(&self, $($p_v: $p_t),*) -> Result<Value, Box<dyn Error>> {
+              self.block_on(self.inner().$f_name($($p_v),*))
+          }
       }
   };
 }
@@ -192,10 +334,26 @@ pub enum PrivacyOperationType {
     AddViaBusinessCard = 40,
 }
 
+/// Operation types of [`ApiClient::admin_operate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum AdminOperationType {
+    /// Add chatroom/group admins (multiple wxids allowed).
+    AddAdmin = 1,
+    /// Remove chatroom/group admins (multiple wxids allowed).
+    RemoveAdmin = 2,
+    /// Transfer the chatroom/group ownership (a single wxid).
+    TransferOwner = 3,
+}
+
+pub mod cassette;
 pub mod contacts_api;
 pub mod favor_api;
 pub mod group_api;
+pub mod interceptor;
 pub mod label_api;
 pub mod login_api;
 pub mod message_api;
 pub mod personal_api;
+pub mod sent;
+pub mod transport;
@@ -1,9 +1,9 @@
 use serde_json::{json, Value};
 use std::error::Error;
 
-use super::{ApiClient, PrivacyOperationType, Sex};
+use super::{PrivacyOperationType, Sex};
 
-impl ApiClient {
+impl_params_apis! {
     impl_params_api!(
     /// Get user profile API
     ///
@@ -0,0 +1,274 @@
+//! Tracking of the sent messages, to revoke them later.
+//!
+//! [`ApiClient::revoke_msg`](super::ApiClient::revoke_msg) needs the `toWxid`,
+//! `msgId`, `newMsgId` and `createTime` returned when the message was sent,
+//! and WeChat only revokes messages within [`REVOKE_WINDOW`]. Every client
+//! therefore remembers the successful `post*`/`forward*` calls of the last
+//! [`TRACKING_PERIOD`], identified by a local [`SentHandle`]:
+//!
+//! - [`ApiClient::sent_messages`](super::ApiClient::sent_messages) and
+//!   [`ApiClient::last_sent`](super::ApiClient::last_sent) list them,
+//! - [`ApiClient::revoke`](super::ApiClient::revoke) revokes one by handle,
+//! - [`ApiClient::revoke_last`](super::ApiClient::revoke_last) revokes the
+//!   last messages sent to a chat.
+//!
+//! Revoking a message sent longer than [`REVOKE_WINDOW`] ago fails without
+//! calling the API.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::api::{ApiClientBuilder, Wxid};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = ApiClientBuilder::new().with_token("your_token").build();
+//!     let to = Wxid::try_from("wxid_xxx").unwrap();
+//!     client.post_text("your_app_id", &to, "oops", "").await.unwrap();
+//!     let sent = client.last_sent(&to).unwrap();
+//!     client.revoke(sent.handle).await.unwrap();
+//! }
+//! ```
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{ApiClient, Wxid};
+use crate::callback::int_field;
+
+/// How long after sending WeChat allows revoking a message.
+pub const REVOKE_WINDOW: Duration = Duration::from_secs(120);
+
+/// How long sent messages are remembered.
+pub const TRACKING_PERIOD: Duration = Duration::from_secs(600);
+
+/// A local identifier of a sent message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SentHandle(u64);
+
+impl fmt::Display for SentHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// The result of a send call, as needed to revoke the message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentMessage {
+    pub handle: SentHandle,
+    pub route: String,
+    pub app_id: String,
+    pub to_wxid: String,
+    pub msg_id: i64,
+    pub new_msg_id: i64,
+    pub create_time: i64,
+    /// When the response was received.
+    pub sent_at: Instant,
+}
+
+impl SentMessage {
+    /// Whether the message can still be revoked.
+    pub fn is_revocable(&self) -> bool {
+        self.sent_at.elapsed() < REVOKE_WINDOW
+    }
+
+    fn check_revocable(&self) -> Result<(), Box<dyn Error>> {
+        if self.is_revocable() {
+            return Ok(());
+        }
+        Err(format!(
+            "Message {} to {} was sent {}s ago, WeChat only revokes messages within {}s",
+            self.handle,
+            self.to_wxid,
+            self.sent_at.elapsed().as_secs(),
+            REVOKE_WINDOW.as_secs()
+        )
+        .into())
+    }
+}
+
+/// The outcome of revoking one message, see [`ApiClient::revoke_last`].
+#[derive(Debug)]
+pub struct Revoked {
+    pub handle: SentHandle,
+    /// The response of the revocation, or why it failed.
+    pub result: Result<Value, Box<dyn Error>>,
+}
+
+/// The messages sent by a client, see the [module docs](self).
+#[derive(Debug, Default)]
+pub(crate) struct SentMessages {
+    inner: Mutex<Tracked>,
+}
+
+#[derive(Debug, Default)]
+struct Tracked {
+    next_handle: u64,
+    messages: VecDeque<SentMessage>,
+}
+
+impl SentMessages {
+    /// The `(appId, toWxid)` of a call to a route sending a message.
+    pub(crate) fn target(route: &str, body: Option<&Value>) -> Option<(String, String)> {
+        if !route.starts_with("/message/post") && !route.starts_with("/message/forward") {
+            return None;
+        }
+        let field = |key: &str| body?.get(key)?.as_str().map(str::to_string);
+        Some((field("appId")?, field("toWxid")?))
+    }
+
+    /// Remembers a send call if it succeeded.
+    pub(crate) fn track(&self, route: &str, (app_id, to_wxid): (String, String), resp: &Value) {
+        if resp.get("ret").and_then(Value::as_i64) != Some(200) {
+            return;
+        }
+        let Some(data) = resp.get("data") else {
+            return;
+        };
+        let mut tracked = self.inner.lock().unwrap();
+        while tracked
+            .messages
+            .front()
+            .is_some_and(|m| m.sent_at.elapsed() > TRACKING_PERIOD)
+        {
+            tracked.messages.pop_front();
+        }
+        tracked.next_handle += 1;
+        let handle = SentHandle(tracked.next_handle);
+        tracked.messages.push_back(SentMessage {
+            handle,
+            route: route.to_string(),
+            app_id,
+            to_wxid,
+            msg_id: int_field(data, "msgId"),
+            new_msg_id: int_field(data, "newMsgId"),
+            create_time: int_field(data, "createTime"),
+            sent_at: Instant::now(),
+        });
+    }
+
+    pub(crate) fn all(&self) -> Vec<SentMessage> {
+        self.inner
+            .lock()
+            .unwrap()
+            .messages
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    fn get(&self, handle: SentHandle) -> Option<SentMessage> {
+        let tracked = self.inner.lock().unwrap();
+        tracked
+            .messages
+            .iter()
+            .find(|m| m.handle == handle)
+            .cloned()
+    }
+
+    /// The last `n` messages sent to `to_wxid`, the most recent first.
+    fn last(&self, to_wxid: &str, n: usize) -> Vec<SentMessage> {
+        let tracked = self.inner.lock().unwrap();
+        tracked
+            .messages
+            .iter()
+            .rev()
+            .filter(|m| m.to_wxid == to_wxid)
+            .take(n)
+            .cloned()
+            .collect()
+    }
+
+    fn remove(&self, handle: SentHandle) {
+        let mut tracked = self.inner.lock().unwrap();
+        tracked.messages.retain(|m| m.handle != handle);
+    }
+}
+
+impl ApiClient {
+    /// The messages sent in the last [`TRACKING_PERIOD`], the oldest first.
+    pub fn sent_messages(&self) -> Vec<SentMessage> {
+        self.sent.all()
+    }
+
+    /// The last message sent to `to_wxid`.
+    pub fn last_sent(&self, to_wxid: &Wxid) -> Option<SentMessage> {
+        self.sent.last(to_wxid.as_str(), 1).pop()
+    }
+
+    /// Revokes a sent message, fails if it was sent longer than [`REVOKE_WINDOW`] ago.
+    pub async fn revoke(&self, handle: SentHandle) -> Result<Value, Box<dyn Error>> {
+        let sent = self
+            .sent
+            .get(handle)
+            .ok_or_else(|| format!("Unknown or expired sent message {}", handle))?;
+        sent.check_revocable()?;
+        self.revoke_sent(&sent).await
+    }
+
+    /// Revokes the last `n` messages sent to `to_wxid`, the most recent first.
+    ///
+    /// Fails without revoking anything if fewer than `n` messages are tracked
+    /// or any of them is past the [`REVOKE_WINDOW`]. Otherwise every message
+    /// is revoked, a failed revocation does not stop the others and the
+    /// outcome of each is returned.
+    pub async fn revoke_last(
+        &self,
+        to_wxid: &Wxid,
+        n: usize,
+    ) -> Result<Vec<Revoked>, Box<dyn Error>> {
+        let messages = self.sent.last(to_wxid.as_str(), n);
+        if messages.len() < n {
+            return Err(format!(
+                "Only {} tracked messages to {}, cannot revoke {}",
+                messages.len(),
+                to_wxid,
+                n
+            )
+            .into());
+        }
+        for sent in &messages {
+            sent.check_revocable()?;
+        }
+        let mut revoked = Vec::with_capacity(n);
+        for sent in &messages {
+            revoked.push(Revoked {
+                handle: sent.handle,
+                result: self.revoke_sent(sent).await,
+            });
+        }
+        Ok(revoked)
+    }
+
+    async fn revoke_sent(&self, sent: &SentMessage) -> Result<Value, Box<dyn Error>> {
+        let ret = self
+            .revoke_msg(
+                &sent.app_id,
+                &Wxid::try_from(sent.to_wxid.as_str())?,
+                &sent.msg_id.to_string(),
+                &sent.new_msg_id.to_string(),
+                &sent.create_time.to_string(),
+            )
+            .await?;
+        if ret.get("ret").and_then(Value::as_i64) != Some(200) {
+            return Err(format!("Gewe request failed: {}", ret).into());
+        }
+        self.sent.remove(sent.handle);
+        Ok(ret)
+    }
+}
+
+impl crate::blocking::ApiClient {
+    /// Blocking version of [`ApiClient::revoke`].
+    pub fn revoke(&self, handle: SentHandle) -> Result<Value, Box<dyn Error>> {
+        self.block_on(self.inner().revoke(handle))
+    }
+
+    /// Blocking version of [`ApiClient::revoke_last`].
+    pub fn revoke_last(&self, to_wxid: &Wxid, n: usize) -> Result<Vec<Revoked>, Box<dyn Error>> {
+        self.block_on(self.inner().revoke_last(to_wxid, n))
+    }
+}
@@ -0,0 +1,116 @@
+//! The backend [`ApiClient`](super::ApiClient) sends its calls through.
+//!
+//! Every API method ends up in [`ApiClient::gewe_post_json`](super::ApiClient::gewe_post_json),
+//! which hands the route, token and json body to a [`Transport`]. The default
+//! is [`HttpTransport`] (reqwest), others are plugged in with
+//! [`ApiClientBuilder::with_transport`](super::ApiClientBuilder::with_transport):
+//! mocks, recording layers ([`CassetteTransport`](super::cassette::CassetteTransport)),
+//! middleware chains or other HTTP stacks.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use async_trait::async_trait;
+//! use rgewe_api::api::transport::Transport;
+//! use rgewe_api::api::ApiClientBuilder;
+//! use serde_json::{json, Value};
+//! use std::error::Error;
+//!
+//! struct Online;
+//!
+//! #[async_trait]
+//! impl Transport for Online {
+//!     async fn post(&self, route: &str, _token: &str, _body: Option<Value>) -> Result<Value, Box<dyn Error>> {
+//!         Ok(json!({ "ret": 200, "msg": route, "data": true }))
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = ApiClientBuilder::new().with_transport(Online).build();
+//!     let value = client.check_online("your_app_id").await.unwrap();
+//!     assert_eq!(value["data"], true);
+//! }
+//! ```
+use async_trait::async_trait;
+use reqwest::{header::HeaderMap, Client};
+use serde_json::Value;
+use std::error::Error;
+use std::sync::Arc;
+
+use super::HEADER_GEWE;
+
+/// Sends a call to the Gewe service and returns its json response.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Posts `body` to `route` (e.g. `/message/postText`) authenticated with `token`.
+    async fn post(
+        &self,
+        route: &str,
+        token: &str,
+        body: Option<Value>,
+    ) -> Result<Value, Box<dyn Error>>;
+}
+
+#[async_trait]
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    async fn post(
+        &self,
+        route: &str,
+        token: &str,
+        body: Option<Value>,
+    ) -> Result<Value, Box<dyn Error>> {
+        (**self).post(route, token, body).await
+    }
+}
+
+/// The default [`Transport`], posting json over HTTP with reqwest.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    base_url: String,
+    client: Client,
+}
+
+impl HttpTransport {
+    /// A transport posting to `base_url`, e.g. `http://localhost:2531/v2/api`, without proxy.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            client: Client::builder().no_proxy().build().unwrap(),
+        }
+    }
+
+    /// Uses the system proxy, reqwest's default.
+    pub fn with_proxy(mut self) -> Self {
+        self.client = Client::new();
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn post(
+        &self,
+        route: &str,
+        token: &str,
+        body: Option<Value>,
+    ) -> Result<Value, Box<dyn Error>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(HEADER_GEWE, token.parse()?);
+        let url = format!("{}{}", self.base_url, route);
+        let rest = self
+            .client
+            .post(url)
+            .headers(headers)
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(rest)
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Settings read from the config file, overridden by the command line options.
+///
+/// ```toml
+/// base_url = "http://localhost:2531/v2/api"
+/// token = "your_token"
+/// app_id = "your_app_id"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Config {
+    pub base_url: Option<String>,
+    pub token: Option<String>,
+    pub app_id: Option<String>,
+}
+
+impl Config {
+    /// `$RGEWE_CONFIG`, or `~/.config/rgewe/config.toml`.
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os("RGEWE_CONFIG") {
+            return PathBuf::from(path);
+        }
+        let home = std::env::var_os("HOME").unwrap_or_default();
+        Path::new(&home).join(".config/rgewe/config.toml")
+    }
+
+    /// Loads the config at `path`, the defaults if it does not exist.
+    pub fn load(path: &Path) -> Result<Config, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let config = toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    /// Writes the config readable by its owner only, as it holds the token.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        // `mode` only applies to new files.
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        file.write_all(toml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,424 @@
+//! `rgewe`: operate Gewe accounts from the command line.
+//!
+//! Built with the `cli` feature. The base URL, token and appId are read from
+//! the config file (see [`config::Config`]) and can be overridden with
+//! `--base-url`, `--token` and `--app-id`. `rgewe login` fetches a token if
+//! none is configured and stores the token and appId for the next calls.
+//! Every command prints the response data as JSON with `--json`.
+//! With the `tui` feature, `rgewe chat` opens an interactive chat client.
+mod config;
+#[cfg(feature = "tui")]
+mod tui;
+
+use clap::{Parser, Subcommand};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use rgewe_api::api::{response_data, ApiClient, ApiClientBuilder, Wxid};
+use rgewe_api::contacts::directory::ContactDirectory;
+use rgewe_api::group::member_cache::ChatroomMembers;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use config::Config;
+
+/// Interval between two `/login/checkLogin` polls.
+const LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Gives up waiting for the QR code to be scanned after this long.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Parser)]
+#[command(
+    name = "rgewe",
+    version,
+    about = "Operate Gewe accounts from the command line"
+)]
+struct Cli {
+    /// Config file, defaults to `$RGEWE_CONFIG` or `~/.config/rgewe/config.toml`.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Gewe API base URL, e.g. `http://localhost:2531/v2/api`.
+    #[arg(long, global = true)]
+    base_url: Option<String>,
+    #[arg(long, global = true)]
+    token: Option<String>,
+    #[arg(long, global = true)]
+    app_id: Option<String>,
+    /// Print the response data as JSON.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Log in by scanning the QR code rendered in the terminal.
+    Login,
+    /// Show whether the account is online.
+    Status,
+    /// Send a message.
+    #[command(subcommand)]
+    Send(SendCommand),
+    /// List or search the contacts.
+    #[command(subcommand)]
+    Contacts(ContactsCommand),
+    /// Inspect and manage a chatroom.
+    #[command(subcommand)]
+    Group(GroupCommand),
+    /// List the contact labels.
+    Labels,
+    /// Log the account out.
+    Logout,
+    /// Chat interactively in the terminal.
+    #[cfg(feature = "tui")]
+    Chat(tui::ChatArgs),
+}
+
+#[derive(Subcommand)]
+enum SendCommand {
+    Text {
+        to: String,
+        text: String,
+        /// Wxids to mention, `notify@all` for everyone.
+        #[arg(long)]
+        at: Vec<String>,
+    },
+    Image {
+        to: String,
+        /// URL of the image, downloaded by the Gewe service.
+        url: String,
+    },
+    File {
+        to: String,
+        /// URL of the file, downloaded by the Gewe service.
+        url: String,
+        /// File name shown to the receiver, defaults to the last segment of the URL.
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ContactsCommand {
+    List {
+        /// Use the contact list cached by the service.
+        #[arg(long)]
+        cache: bool,
+    },
+    /// Search the contacts by remark, nickname, alias or pinyin.
+    Search { query: String },
+}
+
+#[derive(Subcommand)]
+enum GroupCommand {
+    Info {
+        chatroom: String,
+    },
+    Members {
+        chatroom: String,
+    },
+    Invite {
+        chatroom: String,
+        wxids: Vec<String>,
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+    Kick {
+        chatroom: String,
+        wxids: Vec<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let config_path = cli.config.clone().unwrap_or_else(Config::default_path);
+    let mut config = Config::load(&config_path)?;
+    if let Some(base_url) = &cli.base_url {
+        config.base_url = Some(base_url.clone());
+    }
+    if let Some(token) = &cli.token {
+        config.token = Some(token.clone());
+    }
+    if let Some(app_id) = &cli.app_id {
+        config.app_id = Some(app_id.clone());
+    }
+
+    if let Command::Login = cli.command {
+        return login(&mut config, &config_path, cli.json).await;
+    }
+    if config.token.as_deref().unwrap_or_default().is_empty() {
+        return Err("No token configured, run `rgewe login` first".into());
+    }
+    let client = build_client(&config);
+    let app_id = config
+        .app_id
+        .clone()
+        .filter(|a| !a.is_empty())
+        .ok_or("No appId configured, run `rgewe login` first")?;
+
+    match cli.command {
+        Command::Login => unreachable!(),
+        Command::Status => {
+            let online = response_data(client.check_online(&app_id).await?)?;
+            let is_online = online.as_bool().unwrap_or(false);
+            let profile = if is_online {
+                response_data(client.get_profile(&app_id).await?)?
+            } else {
+                Value::Null
+            };
+            let status = json!({ "appId": app_id, "online": is_online, "profile": profile });
+            output(cli.json, &status, || {
+                if is_online {
+                    format!(
+                        "{} online as {} ({})",
+                        app_id,
+                        str_of(&profile, "nickName"),
+                        str_of(&profile, "wxid")
+                    )
+                } else {
+                    format!("{} offline", app_id)
+                }
+            });
+        }
+        Command::Send(send) => {
+            let sent = match send {
+                SendCommand::Text { to, text, at } => {
+                    client
+                        .post_text(&app_id, &wxid(&to)?, &text, &at.join(","))
+                        .await?
+                }
+                SendCommand::Image { to, url } => {
+                    client.post_image(&app_id, &wxid(&to)?, &url).await?
+                }
+                SendCommand::File { to, url, name } => {
+                    let name = name.unwrap_or_else(|| {
+                        url.rsplit('/')
+                            .next()
+                            .and_then(|n| n.split('?').next())
+                            .unwrap_or("file")
+                            .to_string()
+                    });
+                    client.post_file(&app_id, &wxid(&to)?, &url, &name).await?
+                }
+            };
+            let sent = response_data(sent)?;
+            output(cli.json, &sent, || {
+                format!(
+                    "sent to {} (newMsgId {})",
+                    str_of(&sent, "toWxid"),
+                    sent.get("newMsgId").unwrap_or(&Value::Null)
+                )
+            });
+        }
+        Command::Contacts(contacts) => {
+            let directory = ContactDirectory::new(&app_id);
+            let found = match contacts {
+                ContactsCommand::List { cache } => {
+                    directory.sync(&client, cache).await?;
+                    directory.all()
+                }
+                ContactsCommand::Search { query } => {
+                    directory.sync(&client, true).await?;
+                    directory.search(&query)
+                }
+            };
+            output(cli.json, &serde_json::to_value(&found)?, || {
+                found
+                    .iter()
+                    .map(|c| format!("{}\t{}\t{}", c.wxid, c.name(), c.nick_name))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+        }
+        Command::Group(group) => group_command(&client, &app_id, group, cli.json).await?,
+        Command::Labels => {
+            let data = response_data(client.list_labels(&app_id).await?)?;
+            output(cli.json, &data, || {
+                data.get("labelList")
+                    .and_then(Value::as_array)
+                    .map(|labels| {
+                        labels
+                            .iter()
+                            .map(|l| {
+                                format!(
+                                    "{}\t{}",
+                                    l.get("labelId").unwrap_or(&Value::Null),
+                                    str_of(l, "labelName")
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    })
+                    .unwrap_or_default()
+            });
+        }
+        Command::Logout => {
+            let data = response_data(client.log_out(&app_id).await?)?;
+            output(cli.json, &data, || format!("{} logged out", app_id));
+        }
+        #[cfg(feature = "tui")]
+        Command::Chat(args) => {
+            let token = config.token.clone().unwrap_or_default();
+            tui::run(client, &app_id, &token, args).await?
+        }
+    }
+    Ok(())
+}
+
+async fn group_command(
+    client: &ApiClient,
+    app_id: &str,
+    group: GroupCommand,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    match group {
+        GroupCommand::Info { chatroom } => {
+            let info = response_data(client.get_chatroom_info(app_id, &chatroom).await?)?;
+            output(json, &info, || {
+                let members = info
+                    .get("memberList")
+                    .and_then(Value::as_array)
+                    .map(Vec::len)
+                    .unwrap_or_default();
+                format!(
+                    "{}\t{}\towner {}\t{} members",
+                    str_of(&info, "chatroomId"),
+                    str_of(&info, "nickName"),
+                    str_of(&info, "chatRoomOwner"),
+                    members
+                )
+            });
+        }
+        GroupCommand::Members { chatroom } => {
+            let data = response_data(client.get_chatroom_member_list(app_id, &chatroom).await?)?;
+            let members = ChatroomMembers::from_data(&chatroom, &data)?;
+            output(json, &serde_json::to_value(&members)?, || {
+                members
+                    .members
+                    .iter()
+                    .map(|m| format!("{}\t{}", m.wxid, m.name()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+        }
+        GroupCommand::Invite {
+            chatroom,
+            wxids,
+            reason,
+        } => {
+            let wxids = wxids
+                .iter()
+                .map(|w| wxid(w))
+                .collect::<Result<Vec<_>, _>>()?;
+            let data = response_data(
+                client
+                    .invite_member(app_id, wxids, &chatroom, &reason)
+                    .await?,
+            )?;
+            output(json, &data, || format!("invited to {}", chatroom));
+        }
+        GroupCommand::Kick { chatroom, wxids } => {
+            let data = response_data(
+                client
+                    .remove_member(app_id, &wxids.join(","), &chatroom)
+                    .await?,
+            )?;
+            output(json, &data, || format!("removed from {}", chatroom));
+        }
+    }
+    Ok(())
+}
+
+/// Logs in by QR code, fetching a token first if none is configured.
+///
+/// Saves the token and appId the login produced to the config file, not the
+/// command line overrides.
+async fn login(
+    config: &mut Config,
+    config_path: &std::path::Path,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut saved = Config::load(config_path)?;
+    if config.token.as_deref().unwrap_or_default().is_empty() {
+        let client = build_client(config);
+        let token = response_data(client.get_token().await?)?;
+        config.token = token.as_str().map(str::to_string);
+        saved.token = config.token.clone();
+        saved.save(config_path)?;
+    }
+    let client = build_client(config);
+    let app_id = config.app_id.clone().unwrap_or_default();
+    let qr = response_data(client.get_login_qr(&app_id).await?)?;
+    let app_id = str_of(&qr, "appId").to_string();
+    let uuid = str_of(&qr, "uuid").to_string();
+    config.app_id = Some(app_id.clone());
+    saved.app_id = Some(app_id.clone());
+    saved.save(config_path)?;
+
+    let code = QrCode::new(str_of(&qr, "qrData"))?;
+    eprintln!("{}", code.render::<Dense1x2>().quiet_zone(true).build());
+    eprintln!("Scan the QR code with WeChat to log in {}", app_id);
+
+    let deadline = tokio::time::Instant::now() + LOGIN_TIMEOUT;
+    loop {
+        tokio::time::sleep(LOGIN_POLL_INTERVAL).await;
+        let status = response_data(client.check_login_qr(&app_id, &uuid, "").await?)?;
+        let wxid = status
+            .get("loginInfo")
+            .map(|info| str_of(info, "wxid"))
+            .unwrap_or_default();
+        if !wxid.is_empty() {
+            output(json, &status, || {
+                format!(
+                    "logged in as {} ({}), appId {}",
+                    str_of(&status, "nickName"),
+                    wxid,
+                    app_id
+                )
+            });
+            return Ok(());
+        }
+        let expired = status
+            .get("expiredTime")
+            .and_then(Value::as_i64)
+            .is_none_or(|left| left <= 0);
+        if expired || tokio::time::Instant::now() >= deadline {
+            return Err("The login QR code expired, run `rgewe login` again".into());
+        }
+    }
+}
+
+fn build_client(config: &Config) -> ApiClient {
+    let mut builder =
+        ApiClientBuilder::new().with_token(config.token.as_deref().unwrap_or_default());
+    if let Some(base_url) = &config.base_url {
+        builder = builder.with_base_url(base_url);
+    }
+    builder.build()
+}
+
+fn wxid(id: &str) -> Result<Wxid, Box<dyn Error>> {
+    Ok(Wxid::try_from(id)?)
+}
+
+fn str_of<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+fn output(json: bool, data: &Value, human: impl FnOnce() -> String) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(data).unwrap_or_default());
+    } else {
+        println!("{}", human());
+    }
+}
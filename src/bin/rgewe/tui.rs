@@ -0,0 +1,571 @@
+//! `rgewe chat`: an interactive terminal chat client, built with the `tui` feature.
+//!
+//! Conversations are listed from the contact directory, incoming messages are
+//! received live by a [`CallbackServer`] and replies are sent with `post_text`.
+//! The input line accepts:
+//!
+//! - plain text, where `@name` or `@wxid` mentions a chatroom member and `@all` everyone,
+//! - `/image <path>` and `/file <path>`, sending a local file: Gewe downloads it
+//!   from the file server started on `--files-listen`, reachable at `--public-url`,
+//!   under a random key valid until the send call returns,
+//! - `/revoke`, revoking the last message sent to the conversation,
+//! - `/open <query>`, selecting the first conversation matching the query,
+//! - `/quit`.
+//!
+//! Up/Down switch the conversation, Esc or Ctrl-C quits.
+use axum::extract::{Path as UrlPath, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use rgewe_api::api::{response_data, ApiClient, Wxid};
+use rgewe_api::callback::server::CallbackServer;
+use rgewe_api::callback::{CallbackEvent, Message, MessageType};
+use rgewe_api::contacts::directory::ContactDirectory;
+use rgewe_api::group::member_cache::ChatroomMembers;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Options of `rgewe chat`.
+#[derive(clap::Args)]
+pub struct ChatArgs {
+    /// Address the callback receiver listens on.
+    #[arg(long, default_value = "0.0.0.0:18080")]
+    listen: String,
+    /// Callback URL to register with Gewe, e.g. `http://your_host:18080/callback`.
+    #[arg(long)]
+    callback_url: Option<String>,
+    /// Address of the file server for `/image` and `/file`, on the interface
+    /// Gewe reaches it by, e.g. the docker bridge `172.17.0.1:18081`.
+    #[arg(long, default_value = "127.0.0.1:18081")]
+    files_listen: String,
+    /// URL under which Gewe reaches the file server, e.g. `http://your_host:18081`.
+    /// Required for `/image` and `/file`.
+    #[arg(long)]
+    public_url: Option<String>,
+}
+
+/// Local files shared with Gewe, by the key in their URL.
+type SharedFiles = Arc<Mutex<HashMap<String, PathBuf>>>;
+
+/// Runs the chat client until the user quits.
+pub async fn run(
+    client: ApiClient,
+    app_id: &str,
+    token: &str,
+    args: ChatArgs,
+) -> Result<(), Box<dyn Error>> {
+    let mut server = CallbackServer::bind(&args.listen).await?;
+    if let Some(url) = &args.callback_url {
+        response_data(client.set_call_back(token, url).await?)?;
+    }
+    let files = SharedFiles::default();
+    if args.public_url.is_some() {
+        let listener = tokio::net::TcpListener::bind(&args.files_listen).await?;
+        let app = Router::new()
+            .route("/files/{key}", get(serve_file))
+            .with_state(files.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+    }
+
+    let directory = ContactDirectory::new(app_id);
+    directory.sync(&client, true).await?;
+    let mut chat = Chat::new(client, directory, files, args.public_url);
+    chat.status = format!(
+        "{} conversations, receiving callbacks on {}",
+        chat.conversations.len(),
+        server.local_addr()
+    );
+
+    let (keys_tx, mut keys) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if keys_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut terminal = ratatui::init();
+    let ret = chat.event_loop(&mut terminal, &mut server, &mut keys).await;
+    ratatui::restore();
+    ret
+}
+
+async fn serve_file(
+    State(files): State<SharedFiles>,
+    UrlPath(key): UrlPath<String>,
+) -> Result<Vec<u8>, StatusCode> {
+    let path = files.lock().unwrap().get(&key).cloned();
+    let path = path.ok_or(StatusCode::NOT_FOUND)?;
+    tokio::fs::read(path)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
+
+/// An unguessable key for a shared file: the file server has no other access control.
+fn random_key() -> Result<String, Box<dyn Error>> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// A line of a conversation history.
+struct ChatLine {
+    from: String,
+    text: String,
+    revoked: bool,
+}
+
+struct Conversation {
+    id: String,
+    name: String,
+    lines: Vec<ChatLine>,
+    unread: usize,
+    /// Send results of the messages sent from here, with the index of their line.
+    sent: Vec<(usize, Value)>,
+}
+
+impl Conversation {
+    fn new(id: &str, name: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            lines: Vec::new(),
+            unread: 0,
+            sent: Vec::new(),
+        }
+    }
+}
+
+struct Chat {
+    client: ApiClient,
+    directory: ContactDirectory,
+    files: SharedFiles,
+    public_url: Option<String>,
+    conversations: Vec<Conversation>,
+    selected: usize,
+    input: String,
+    status: String,
+    members: HashMap<String, ChatroomMembers>,
+    /// `newMsgId`s of the messages sent from here, to skip their callback echo.
+    sent_ids: Vec<i64>,
+    quit: bool,
+}
+
+impl Chat {
+    fn new(
+        client: ApiClient,
+        directory: ContactDirectory,
+        files: SharedFiles,
+        public_url: Option<String>,
+    ) -> Self {
+        let mut contacts = directory.all();
+        contacts.retain(|c| !c.wxid.starts_with("gh_"));
+        contacts.sort_by(|a, b| a.name().cmp(b.name()));
+        let conversations = contacts
+            .iter()
+            .map(|c| Conversation::new(&c.wxid, c.name()))
+            .collect();
+        Self {
+            client,
+            directory,
+            files,
+            public_url,
+            conversations,
+            selected: 0,
+            input: String::new(),
+            status: String::new(),
+            members: HashMap::new(),
+            sent_ids: Vec::new(),
+            quit: false,
+        }
+    }
+
+    async fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        server: &mut CallbackServer,
+        keys: &mut mpsc::UnboundedReceiver<Event>,
+    ) -> Result<(), Box<dyn Error>> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            tokio::select! {
+                Some(event) = keys.recv() => self.on_key(event).await,
+                Some(event) = server.next_event() => self.on_callback(event),
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, input, status] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list, history] =
+            Layout::horizontal([Constraint::Length(32), Constraint::Min(10)]).areas(main);
+
+        let items: Vec<ListItem> = self
+            .conversations
+            .iter()
+            .map(|c| match c.unread {
+                0 => ListItem::new(c.name.clone()),
+                n => ListItem::new(format!("{} ({})", c.name, n))
+                    .style(Style::new().add_modifier(Modifier::BOLD)),
+            })
+            .collect();
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::bordered().title("Conversations"))
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+            list,
+            &mut state,
+        );
+
+        let (title, lines) = match self.conversations.get(self.selected) {
+            Some(c) => {
+                let height = history.height.saturating_sub(2) as usize;
+                let lines = c.lines[c.lines.len().saturating_sub(height)..]
+                    .iter()
+                    .map(|l| {
+                        let mut text = Span::raw(l.text.clone());
+                        if l.revoked {
+                            text = text.style(Style::new().add_modifier(Modifier::CROSSED_OUT));
+                        }
+                        Line::from(vec![
+                            Span::styled(
+                                format!("{}: ", l.from),
+                                Style::new().add_modifier(Modifier::BOLD),
+                            ),
+                            text,
+                        ])
+                    })
+                    .collect::<Vec<_>>();
+                (format!("{} ({})", c.name, c.id), lines)
+            }
+            None => (String::new(), Vec::new()),
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            history,
+        );
+
+        frame.render_widget(
+            Paragraph::new(self.input.as_str()).block(Block::bordered()),
+            input,
+        );
+        frame.set_cursor_position((input.x + 1 + self.input.chars().count() as u16, input.y + 1));
+        frame.render_widget(Paragraph::new(self.status.as_str()), status);
+    }
+
+    async fn on_key(&mut self, event: Event) {
+        let Event::Key(key) = event else {
+            return;
+        };
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        match key.code {
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Up => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Down => self.select(self.selected + 1),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Enter => {
+                let input = std::mem::take(&mut self.input);
+                if let Err(e) = self.submit(input.trim()).await {
+                    self.status = format!("error: {}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        if index < self.conversations.len() {
+            self.selected = index;
+            self.conversations[index].unread = 0;
+        }
+    }
+
+    async fn submit(&mut self, input: &str) -> Result<(), Box<dyn Error>> {
+        let (command, arg) = input.split_once(' ').unwrap_or((input, ""));
+        match command {
+            "" => Ok(()),
+            "/quit" => {
+                self.quit = true;
+                Ok(())
+            }
+            "/open" => {
+                let index = self
+                    .conversations
+                    .iter()
+                    .position(|c| {
+                        c.id == arg
+                            || self
+                                .directory
+                                .get(&c.id)
+                                .is_some_and(|contact| contact.matches(arg))
+                    })
+                    .ok_or_else(|| format!("No conversation matches {}", arg))?;
+                self.select(index);
+                Ok(())
+            }
+            "/image" | "/file" => self.send_file(command == "/image", arg.trim()).await,
+            "/revoke" => self.revoke_last().await,
+            _ => self.send_text(input).await,
+        }
+    }
+
+    fn current(&self) -> Result<Wxid, Box<dyn Error>> {
+        let conversation = self
+            .conversations
+            .get(self.selected)
+            .ok_or("No conversation selected")?;
+        Ok(Wxid::try_from(conversation.id.as_str())?)
+    }
+
+    async fn send_text(&mut self, input: &str) -> Result<(), Box<dyn Error>> {
+        let to = self.current()?;
+        let (content, ats) = self.resolve_mentions(to.as_str(), input).await?;
+        let sent = response_data(
+            self.client
+                .post_text(self.directory.app_id(), &to, &content, &ats.join(","))
+                .await?,
+        )?;
+        self.record_sent(content, sent);
+        Ok(())
+    }
+
+    /// Replaces the `@name`/`@wxid` mentions of a chatroom member with `@nickname` and collects their wxids.
+    async fn resolve_mentions(
+        &mut self,
+        chat: &str,
+        input: &str,
+    ) -> Result<(String, Vec<String>), Box<dyn Error>> {
+        if !chat.ends_with("@chatroom") || !input.contains('@') {
+            return Ok((input.to_string(), Vec::new()));
+        }
+        if !self.members.contains_key(chat) {
+            let data = response_data(
+                self.client
+                    .get_chatroom_member_list(self.directory.app_id(), chat)
+                    .await?,
+            )?;
+            self.members
+                .insert(chat.to_string(), ChatroomMembers::from_data(chat, &data)?);
+        }
+        let members = &self.members[chat];
+        let mut ats = Vec::new();
+        let words = input.split(' ').map(|word| {
+            let Some(name) = word.strip_prefix('@') else {
+                return word.to_string();
+            };
+            if name == "all" {
+                ats.push("notify@all".to_string());
+                return "@所有人".to_string();
+            }
+            match members
+                .members
+                .iter()
+                .find(|m| m.wxid == name || m.name() == name)
+            {
+                Some(member) => {
+                    ats.push(member.wxid.clone());
+                    format!("@{}", member.name())
+                }
+                None => word.to_string(),
+            }
+        });
+        let content = words.collect::<Vec<_>>().join(" ");
+        Ok((content, ats))
+    }
+
+    async fn send_file(&mut self, image: bool, path: &str) -> Result<(), Box<dyn Error>> {
+        let public_url = self
+            .public_url
+            .as_deref()
+            .ok_or("Sending files requires --public-url")?;
+        let to = self.current()?;
+        let path = Path::new(path).canonicalize()?;
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("file")
+            .to_string();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| format!(".{}", e))
+            .unwrap_or_default();
+        let key = format!("{}{}", random_key()?, extension);
+        self.files.lock().unwrap().insert(key.clone(), path);
+        let url = format!("{}/files/{}", public_url.trim_end_matches('/'), key);
+        let app_id = self.directory.app_id();
+        // Gewe downloads the file before answering, the key is not needed afterwards.
+        let ret = if image {
+            self.client.post_image(app_id, &to, &url).await
+        } else {
+            self.client.post_file(app_id, &to, &url, &file_name).await
+        };
+        self.files.lock().unwrap().remove(&key);
+        let sent = response_data(ret?)?;
+        let kind = if image { "image" } else { "file" };
+        self.record_sent(format!("[{}] {}", kind, file_name), sent);
+        Ok(())
+    }
+
+    fn record_sent(&mut self, text: String, sent: Value) {
+        if let Some(id) = sent.get("newMsgId").and_then(Value::as_i64) {
+            self.sent_ids.push(id);
+        }
+        let conversation = &mut self.conversations[self.selected];
+        conversation.lines.push(ChatLine {
+            from: "me".to_string(),
+            text,
+            revoked: false,
+        });
+        let line = conversation.lines.len() - 1;
+        conversation.sent.push((line, sent));
+        self.status = format!("sent to {}", conversation.name);
+    }
+
+    async fn revoke_last(&mut self) -> Result<(), Box<dyn Error>> {
+        let to = self.current()?;
+        let conversation = &mut self.conversations[self.selected];
+        let (line, sent) = conversation
+            .sent
+            .last()
+            .ok_or("Nothing sent to this conversation")?;
+        response_data(
+            self.client
+                .revoke_msg(
+                    self.directory.app_id(),
+                    &to,
+                    &id_of(sent, "msgId"),
+                    &id_of(sent, "newMsgId"),
+                    &id_of(sent, "createTime"),
+                )
+                .await?,
+        )?;
+        conversation.lines[*line].revoked = true;
+        conversation.sent.pop();
+        self.status = format!("revoked the last message to {}", conversation.name);
+        Ok(())
+    }
+
+    fn on_callback(&mut self, event: CallbackEvent) {
+        if event.app_id().is_some_and(|a| a != self.directory.app_id()) {
+            return;
+        }
+        match event {
+            CallbackEvent::Message(msg) => self.receive(&msg),
+            CallbackEvent::MessageRevoked(revoked) => self.receive(&revoked.notice),
+            CallbackEvent::Offline { .. } => self.status = "the account went offline".to_string(),
+            event @ (CallbackEvent::ContactModified { .. }
+            | CallbackEvent::ContactDeleted { .. }) => self.directory.apply_event(&event),
+            _ => {}
+        }
+    }
+
+    fn receive(&mut self, msg: &Message) {
+        if self.sent_ids.contains(&msg.new_msg_id) {
+            return;
+        }
+        let chat = msg.chat_id().to_string();
+        let index = match self.conversations.iter().position(|c| c.id == chat) {
+            Some(index) => index,
+            None => {
+                let name = self
+                    .directory
+                    .get(&chat)
+                    .map(|c| c.name().to_string())
+                    .unwrap_or_else(|| chat.clone());
+                self.conversations.push(Conversation::new(&chat, &name));
+                self.conversations.len() - 1
+            }
+        };
+        let from = if msg.is_from_self() {
+            "me".to_string()
+        } else {
+            self.name_of(msg.chatroom_id(), msg.sender())
+        };
+        let conversation = &mut self.conversations[index];
+        conversation.lines.push(ChatLine {
+            from,
+            text: display_text(msg),
+            revoked: false,
+        });
+        if index != self.selected {
+            conversation.unread += 1;
+        }
+    }
+
+    fn name_of(&self, chatroom: Option<&str>, wxid: &str) -> String {
+        chatroom
+            .and_then(|c| self.members.get(c))
+            .and_then(|m| m.get(wxid))
+            .map(|m| m.name().to_string())
+            .or_else(|| self.directory.get(wxid).map(|c| c.name().to_string()))
+            .unwrap_or_else(|| wxid.to_string())
+    }
+}
+
+/// The history text of a received message.
+fn display_text(msg: &Message) -> String {
+    if let Some(app_msg) = msg.app_msg().filter(|app_msg| app_msg.kind == 57) {
+        let quoted = match &app_msg.refer {
+            Some(refer) if MessageType::from(refer.msg_type) == MessageType::Text => {
+                format!("{}: {}", refer.display_name, refer.content)
+            }
+            Some(refer) => format!(
+                "{}: [{:?}]",
+                refer.display_name,
+                MessageType::from(refer.msg_type)
+            ),
+            None => "?".to_string(),
+        };
+        return format!("{} [quote {}]", app_msg.title, quoted);
+    }
+    match msg.message_type() {
+        MessageType::Text | MessageType::SystemNotice => msg.text().to_string(),
+        MessageType::Image => "[image]".to_string(),
+        MessageType::Voice => "[voice]".to_string(),
+        MessageType::Video => "[video]".to_string(),
+        MessageType::Emoji => "[emoji]".to_string(),
+        MessageType::Location => "[location]".to_string(),
+        MessageType::NameCard => "[name card]".to_string(),
+        MessageType::AppMsg => match msg.app_msg_type() {
+            Some(5) => "[link]".to_string(),
+            Some(6) => "[file]".to_string(),
+            Some(33 | 36) => "[mini program]".to_string(),
+            _ => "[app message]".to_string(),
+        },
+        other => format!("[{:?}]", other),
+    }
+}
+
+/// Reads an id of a send result, which Gewe returns as number or string.
+fn id_of(sent: &Value, key: &str) -> String {
+    match sent.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,61 @@
+//! A synchronous client mirroring the async [`api::ApiClient`](crate::api::ApiClient).
+//!
+//! [`ApiClient`] offers the same methods as the async client, generated by the
+//! same `impl_params_api!` invocations, and runs them to completion on its own
+//! single threaded tokio runtime. It must not be used from within an async
+//! runtime, where blocking would panic.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::api::ApiClientBuilder;
+//!
+//! fn main() {
+//!     let client = ApiClientBuilder::new()
+//!         .with_token("your_token")
+//!         .build_blocking()
+//!         .unwrap();
+//!     let value = client.check_online("your_app_id").unwrap();
+//!     assert!(value.get("ret").unwrap().as_u64().unwrap() == 200);
+//! }
+//! ```
+use serde_json::Value;
+use std::error::Error;
+use std::future::Future;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::api;
+
+/// A blocking Gewe API client, see the [module docs](self).
+#[derive(Debug)]
+pub struct ApiClient {
+    inner: api::ApiClient,
+    runtime: Runtime,
+}
+
+impl ApiClient {
+    /// Wraps an async client, starting the runtime its calls are run on.
+    pub fn new(inner: api::ApiClient) -> Result<Self, Box<dyn Error>> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// The wrapped async client.
+    pub fn inner(&self) -> &api::ApiClient {
+        &self.inner
+    }
+
+    /// Blocking version of [`api::ApiClient::gewe_post_json`].
+    pub fn gewe_post_json(
+        &self,
+        route: &str,
+        body: Option<Value>,
+    ) -> Result<Value, Box<dyn Error>> {
+        self.block_on(self.inner.gewe_post_json(route, body))
+    }
+
+    /// Runs `future` to completion on the client's runtime.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
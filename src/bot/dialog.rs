@@ -0,0 +1,273 @@
+//! Per-conversation state and multi-step dialogs.
+//!
+//! A conversation is a user within a chat: a private chat, or one member of a
+//! chatroom, see [`ConversationKey`]. [`Conversations`] stores a typed state
+//! per conversation and routes the next message of a conversation to a handler
+//! waiting for it instead of the bot routes. States expire [`DEFAULT_STATE_TTL`]
+//! after they were last set, see [`Conversations::with_state_ttl`].
+//!
+//! Handlers take a [`Dialog`] to ask questions and await the answers. A dialog
+//! awaits the next message of the same user in the same chat, and fails when
+//! no message arrives within the timeout set with
+//! [`Bot::with_dialog_timeout`](super::Bot::with_dialog_timeout), clearing the
+//! state of the conversation. Awaiting
+//! handlers must run concurrently with the dispatch of the next messages, i.e.
+//! through [`Bot::spawn`](super::Bot::spawn) or [`Bot::run`](super::Bot::run).
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::bot::dialog::Dialog;
+//! use std::error::Error;
+//!
+//! #[derive(Clone)]
+//! struct Intake {
+//!     name: String,
+//! }
+//!
+//! async fn intake(dialog: Dialog) -> Result<(), Box<dyn Error>> {
+//!     let name = dialog.ask("What is your name?").await?;
+//!     dialog.set_state(Intake { name: name.clone() });
+//!     let issue = dialog.ask(&format!("Hi {}, how can we help?", name)).await?;
+//!     dialog.say(&format!("Thanks, we will look into: {}", issue)).await?;
+//!     dialog.clear_state();
+//!     Ok(())
+//! }
+//! ```
+use serde_json::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use super::extract::FromContext;
+use super::Context;
+use crate::api::{response_data, ApiClient, Wxid};
+use crate::callback::Message;
+
+/// Identifies a conversation: the chat and the user within it.
+///
+/// For private chats `chat_id` and `sender` are the same wxid.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConversationKey {
+    pub chat_id: String,
+    pub sender: String,
+}
+
+impl ConversationKey {
+    pub fn new(chat_id: &str, sender: &str) -> Self {
+        Self {
+            chat_id: chat_id.to_string(),
+            sender: sender.to_string(),
+        }
+    }
+
+    /// The conversation of a received message.
+    pub fn of(msg: &Message) -> Self {
+        Self::new(msg.chat_id(), msg.sender())
+    }
+}
+
+/// How long the state of a conversation is kept after it was last set.
+pub const DEFAULT_STATE_TTL: Duration = Duration::from_secs(1800);
+
+/// A conversation state, with when it was set.
+type State = (Instant, Box<dyn Any + Send + Sync>);
+
+/// State and waiting dialogs of every conversation, see the [module docs](self).
+pub struct Conversations {
+    waiting: Mutex<HashMap<ConversationKey, oneshot::Sender<Message>>>,
+    states: Mutex<HashMap<ConversationKey, State>>,
+    state_ttl: Duration,
+}
+
+impl Default for Conversations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Conversations {
+    pub fn new() -> Self {
+        Self {
+            waiting: Mutex::new(HashMap::new()),
+            states: Mutex::new(HashMap::new()),
+            state_ttl: DEFAULT_STATE_TTL,
+        }
+    }
+
+    /// Expires the states `ttl` after they were last set instead of [`DEFAULT_STATE_TTL`].
+    pub fn with_state_ttl(mut self, ttl: Duration) -> Self {
+        self.state_ttl = ttl;
+        self
+    }
+
+    /// The state of the conversation, `None` if unset, expired or of another type.
+    pub fn state<T: Clone + 'static>(&self, key: &ConversationKey) -> Option<T> {
+        self.states
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|(set_at, _)| set_at.elapsed() < self.state_ttl)
+            .and_then(|(_, state)| state.downcast_ref::<T>())
+            .cloned()
+    }
+
+    /// Replaces the state of the conversation, and drops the expired states.
+    pub fn set_state<T: Send + Sync + 'static>(&self, key: &ConversationKey, state: T) {
+        let mut states = self.states.lock().unwrap();
+        states.retain(|_, (set_at, _)| set_at.elapsed() < self.state_ttl);
+        states.insert(key.clone(), (Instant::now(), Box::new(state)));
+    }
+
+    pub fn clear_state(&self, key: &ConversationKey) {
+        self.states.lock().unwrap().remove(key);
+    }
+
+    /// Whether a dialog awaits the next message of the conversation.
+    pub fn is_waiting(&self, key: &ConversationKey) -> bool {
+        self.waiting
+            .lock()
+            .unwrap()
+            .get(key)
+            .is_some_and(|sender| !sender.is_closed())
+    }
+
+    /// Hands `msg` to the dialog awaiting it, returns `false` if none does.
+    pub fn offer(&self, msg: &Message) -> bool {
+        let waiting = self
+            .waiting
+            .lock()
+            .unwrap()
+            .remove(&ConversationKey::of(msg));
+        match waiting {
+            Some(sender) => sender.send(msg.clone()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Waits for the next message of the conversation, replacing any other dialog waiting for it.
+    pub async fn next_message(
+        &self,
+        key: &ConversationKey,
+        timeout: Duration,
+    ) -> Result<Message, Box<dyn Error>> {
+        self.wait(key, timeout, async { Ok(()) }).await
+    }
+
+    /// Registers the waiter before running `before`, so that an answer to a
+    /// question sent by `before` cannot be missed.
+    async fn wait(
+        &self,
+        key: &ConversationKey,
+        timeout: Duration,
+        before: impl std::future::Future<Output = Result<(), Box<dyn Error>>>,
+    ) -> Result<Message, Box<dyn Error>> {
+        let (sender, receiver) = oneshot::channel();
+        self.waiting.lock().unwrap().insert(key.clone(), sender);
+        if let Err(e) = before.await {
+            self.waiting.lock().unwrap().remove(key);
+            return Err(e);
+        }
+        let ret = tokio::time::timeout(timeout, receiver).await;
+        {
+            let mut waiting = self.waiting.lock().unwrap();
+            if waiting.get(key).is_some_and(|sender| sender.is_closed()) {
+                waiting.remove(key);
+            }
+        }
+        match ret {
+            Ok(Ok(msg)) => Ok(msg),
+            Ok(Err(_)) => Err(format!(
+                "Another dialog took over the conversation with {} in {}",
+                key.sender, key.chat_id
+            )
+            .into()),
+            Err(_) => {
+                self.clear_state(key);
+                Err(format!(
+                    "No message from {} in {} within {:?}",
+                    key.sender, key.chat_id, timeout
+                )
+                .into())
+            }
+        }
+    }
+}
+
+/// A multi-step dialog with the user of the handled message, see the [module docs](self).
+#[derive(Clone)]
+pub struct Dialog {
+    client: Arc<ApiClient>,
+    conversations: Arc<Conversations>,
+    app_id: String,
+    key: ConversationKey,
+    timeout: Duration,
+}
+
+impl FromContext for Dialog {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        Some(Dialog {
+            client: ctx.client.clone(),
+            conversations: ctx.conversations.clone(),
+            app_id: ctx.message.app_id.clone(),
+            key: ConversationKey::of(&ctx.message),
+            timeout: ctx.dialog_timeout,
+        })
+    }
+}
+
+impl Dialog {
+    pub fn key(&self) -> &ConversationKey {
+        &self.key
+    }
+
+    /// Uses `timeout` instead of the bot default for the next waits.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Waits for the next message of the user in this chat.
+    pub async fn next_message(&self) -> Result<Message, Box<dyn Error>> {
+        self.conversations
+            .next_message(&self.key, self.timeout)
+            .await
+    }
+
+    /// Sends `question` to the chat and returns the text of the answer.
+    pub async fn ask(&self, question: &str) -> Result<String, Box<dyn Error>> {
+        let msg = self
+            .conversations
+            .wait(&self.key, self.timeout, async {
+                self.say(question).await.map(|_| ())
+            })
+            .await?;
+        Ok(msg.text().to_string())
+    }
+
+    /// Sends a text to the chat, returns the data of the send response.
+    pub async fn say(&self, content: &str) -> Result<Value, Box<dyn Error>> {
+        let to = Wxid::try_from(self.key.chat_id.as_str())?;
+        response_data(
+            self.client
+                .post_text(&self.app_id, &to, content, "")
+                .await?,
+        )
+    }
+
+    /// The state of the conversation, `None` if unset or of another type.
+    pub fn state<T: Clone + 'static>(&self) -> Option<T> {
+        self.conversations.state(&self.key)
+    }
+
+    pub fn set_state<T: Send + Sync + 'static>(&self, state: T) {
+        self.conversations.set_state(&self.key, state)
+    }
+
+    pub fn clear_state(&self) {
+        self.conversations.clear_state(&self.key)
+    }
+}
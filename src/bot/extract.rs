@@ -0,0 +1,154 @@
+//! Handler arguments extracted from the handled message.
+//!
+//! An extractor rejecting a message (e.g. [`Chatroom`] for a private message)
+//! makes the router skip the handler and try the next route. Custom extractors
+//! implement [`FromContext`].
+use serde_json::Value;
+use std::error::Error;
+use std::sync::Arc;
+
+use super::Context;
+use crate::api::{response_data, ApiClient, Wxid};
+use crate::callback::Message;
+
+/// Extracts a handler argument from the [`Context`], `None` rejects the message.
+pub trait FromContext: Sized {
+    fn from_context(ctx: &Context) -> Option<Self>;
+}
+
+impl FromContext for Context {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        Some(ctx.clone())
+    }
+}
+
+impl FromContext for Message {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        Some(ctx.message.clone())
+    }
+}
+
+impl FromContext for Arc<ApiClient> {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        Some(ctx.client.clone())
+    }
+}
+
+/// Parses a wxid of the handled message, a rejected one is traced as the
+/// handler is skipped without any other trace.
+fn wxid_of(value: &str) -> Option<Wxid> {
+    Wxid::try_from(value)
+        .inspect_err(|_e| {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = %_e, "rejected message wxid, skipping the handler");
+        })
+        .ok()
+}
+
+/// The wxid of the sender, the chatroom member for chatroom messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sender(pub Wxid);
+
+impl FromContext for Sender {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        wxid_of(ctx.message.sender()).map(Sender)
+    }
+}
+
+/// The chatroom id, rejects private messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chatroom(pub String);
+
+impl FromContext for Chatroom {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        ctx.message.chatroom_id().map(|id| Chatroom(id.to_string()))
+    }
+}
+
+/// The text of the message, without the chatroom sender prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Text(pub String);
+
+impl FromContext for Text {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        Some(Text(ctx.message.text().to_string()))
+    }
+}
+
+/// The words after the command of a [`Filter::Command`](super::Filter::Command).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args(pub Vec<String>);
+
+impl FromContext for Args {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        Some(Args(ctx.args.clone()))
+    }
+}
+
+/// The groups of a [`Filter::Pattern`](super::Filter::Pattern), unmatched groups are empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures(pub Vec<String>);
+
+impl FromContext for Captures {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        Some(Captures(ctx.captures.clone()))
+    }
+}
+
+/// Answers in the conversation of the handled message.
+///
+/// Every method returns the data of the send response, e.g. the ids needed by
+/// [`ApiClient::revoke_msg`].
+#[derive(Clone)]
+pub struct Reply {
+    client: Arc<ApiClient>,
+    app_id: String,
+    to: Wxid,
+}
+
+impl FromContext for Reply {
+    fn from_context(ctx: &Context) -> Option<Self> {
+        Some(Reply {
+            client: ctx.client.clone(),
+            app_id: ctx.message.app_id.clone(),
+            to: wxid_of(ctx.message.chat_id())?,
+        })
+    }
+}
+
+impl Reply {
+    /// The conversation the reply goes to.
+    pub fn to(&self) -> &Wxid {
+        &self.to
+    }
+
+    pub async fn text(&self, content: &str) -> Result<Value, Box<dyn Error>> {
+        self.text_at(content, &[]).await
+    }
+
+    /// Sends a text mentioning `wxids` (`notify@all` for everyone), which `content`
+    /// should contain as `@nickname`.
+    pub async fn text_at(&self, content: &str, wxids: &[&str]) -> Result<Value, Box<dyn Error>> {
+        response_data(
+            self.client
+                .post_text(&self.app_id, &self.to, content, &wxids.join(","))
+                .await?,
+        )
+    }
+
+    pub async fn image(&self, img_url: &str) -> Result<Value, Box<dyn Error>> {
+        response_data(
+            self.client
+                .post_image(&self.app_id, &self.to, img_url)
+                .await?,
+        )
+    }
+
+    pub async fn file(&self, file_url: &str, file_name: &str) -> Result<Value, Box<dyn Error>> {
+        response_data(
+            self.client
+                .post_file(&self.app_id, &self.to, file_url, file_name)
+                .await?,
+        )
+    }
+}
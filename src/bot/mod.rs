@@ -0,0 +1,376 @@
+//! Routing of incoming messages to handlers.
+//!
+//! A [`Bot`] holds a list of routes, each a [`Filter`] and a handler. Handlers
+//! are async functions taking any number of [extractors](extract), e.g. the
+//! [`Sender`](extract::Sender), the command [`Args`](extract::Args) or a
+//! [`Reply`](extract::Reply) answering in the same conversation. Every message
+//! is dispatched to the first route whose filter matches and whose extractors
+//! all succeed, so a handler taking a [`Chatroom`](extract::Chatroom) is only
+//! called for chatroom messages.
+//!
+//! Messages sent by the account itself are ignored, so that replies do not
+//! trigger the bot again.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::api::ApiClientBuilder;
+//! use rgewe_api::bot::extract::{Args, Reply, Sender};
+//! use rgewe_api::bot::{Bot, Filter};
+//! use rgewe_api::callback::CallbackEvent;
+//! use std::error::Error;
+//!
+//! async fn help(reply: Reply) -> Result<(), Box<dyn Error>> {
+//!     reply.text("/echo <text>: repeat the text").await?;
+//!     Ok(())
+//! }
+//!
+//! async fn echo(Args(args): Args, Sender(sender): Sender, reply: Reply) -> Result<(), Box<dyn Error>> {
+//!     reply.text(&format!("{} said {}", sender, args.join(" "))).await?;
+//!     Ok(())
+//! }
+//!
+//! async fn on_callback(payload: &serde_json::Value) {
+//!     let client = ApiClientBuilder::new().with_token("your_token").build();
+//!     let bot = Bot::new(client)
+//!         .with_command("/help", help)
+//!         .with_command("/echo", echo)
+//!         .with_route(Filter::Mentioned, help);
+//!     if let Ok(event) = CallbackEvent::try_from(payload) {
+//!         bot.handle_event(&event).await.unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! With the `server` feature, [`Bot::run`] dispatches the events of a
+//! [`CallbackServer`](crate::callback::server::CallbackServer).
+//!
+//! Handlers can keep a state per conversation and await the next messages of
+//! the user with a [`Dialog`](dialog::Dialog), see the [`dialog`] module.
+use regex::Regex;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::ApiClient;
+use crate::callback::{CallbackEvent, Message, MessageType};
+
+pub mod dialog;
+pub mod extract;
+
+use dialog::Conversations;
+use extract::FromContext;
+
+/// How long a [`Dialog`](dialog::Dialog) waits for the next message by default.
+pub const DEFAULT_DIALOG_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The future returned by a handler.
+pub type HandlerFuture = Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send>>;
+
+/// A condition a message is routed by.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// Every message.
+    Any,
+    /// The text starts with the command, e.g. `/help`, followed by whitespace or nothing.
+    ///
+    /// Leading `@` mentions are skipped, so `@bot /help` matches in chatrooms.
+    /// The words after the command are the [`Args`](extract::Args).
+    Command(String),
+    /// The text matches the regular expression, its groups are the
+    /// [`Captures`](extract::Captures).
+    Pattern(Regex),
+    /// The message has the given type.
+    Type(MessageType),
+    /// The message belongs to a private conversation.
+    Private,
+    /// The message belongs to a chatroom.
+    Chatroom,
+    /// The account was mentioned with `@`, or `@all` was used.
+    Mentioned,
+    /// All filters match.
+    All(Vec<Filter>),
+}
+
+/// What a matching [`Filter`] extracted from the message.
+#[derive(Debug, Clone, Default)]
+struct Matched {
+    args: Vec<String>,
+    captures: Vec<String>,
+}
+
+impl Filter {
+    /// Whether the message matches the filter.
+    pub fn matches(&self, msg: &Message) -> bool {
+        self.check(msg).is_some()
+    }
+
+    fn check(&self, msg: &Message) -> Option<Matched> {
+        match self {
+            Filter::Any => Some(Matched::default()),
+            Filter::Command(command) => {
+                let text = strip_mentions(msg.text());
+                let rest = text.strip_prefix(command.as_str())?;
+                if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                    return None;
+                }
+                Some(Matched {
+                    args: rest.split_whitespace().map(str::to_string).collect(),
+                    captures: Vec::new(),
+                })
+            }
+            Filter::Pattern(re) => {
+                let captures = re.captures(msg.text())?;
+                Some(Matched {
+                    args: Vec::new(),
+                    captures: captures
+                        .iter()
+                        .skip(1)
+                        .map(|c| c.map(|c| c.as_str().to_string()).unwrap_or_default())
+                        .collect(),
+                })
+            }
+            Filter::Type(kind) => (msg.message_type() == *kind).then(Matched::default),
+            Filter::Private => (!msg.is_chatroom()).then(Matched::default),
+            Filter::Chatroom => msg.is_chatroom().then(Matched::default),
+            Filter::Mentioned => {
+                (msg.mentions(&msg.wxid) || msg.mentions("notify@all")).then(Matched::default)
+            }
+            Filter::All(filters) => {
+                let mut matched = Matched::default();
+                for filter in filters {
+                    let m = filter.check(msg)?;
+                    matched.args.extend(m.args);
+                    matched.captures.extend(m.captures);
+                }
+                Some(matched)
+            }
+        }
+    }
+}
+
+/// Skips the leading `@name` mentions of a chatroom text.
+fn strip_mentions(text: &str) -> &str {
+    let mut text = text.trim_start();
+    while text.starts_with('@') {
+        // WeChat ends a mention with U+2005, nicknames may contain spaces.
+        match text.find('\u{2005}').or_else(|| text.find(' ')) {
+            Some(end) => text = text[end..].trim_start_matches(['\u{2005}', ' ']),
+            None => return "",
+        }
+    }
+    text
+}
+
+/// The message being handled, passed to the [extractors](extract).
+#[derive(Clone)]
+pub struct Context {
+    client: Arc<ApiClient>,
+    message: Message,
+    args: Vec<String>,
+    captures: Vec<String>,
+    conversations: Arc<Conversations>,
+    dialog_timeout: Duration,
+}
+
+impl Context {
+    pub fn client(&self) -> &Arc<ApiClient> {
+        &self.client
+    }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// The words after the command of a [`Filter::Command`].
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// The groups of a [`Filter::Pattern`].
+    pub fn captures(&self) -> &[String] {
+        &self.captures
+    }
+}
+
+/// An async function handling a message, see the [module docs](self).
+///
+/// Implemented for async functions of up to six [`FromContext`] arguments
+/// returning `Result<(), Box<dyn Error>>`.
+pub trait Handler<T>: Send + Sync + 'static {
+    /// Runs the handler, or returns `None` if an extractor rejected the message.
+    fn call(&self, ctx: &Context) -> Option<HandlerFuture>;
+}
+
+macro_rules! impl_handler {
+    ($($t:ident),*) => {
+        impl<F, Fut, $($t,)*> Handler<($($t,)*)> for F
+        where
+            F: Fn($($t),*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = Result<(), Box<dyn Error>>> + Send + 'static,
+            $($t: FromContext,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn call(&self, ctx: &Context) -> Option<HandlerFuture> {
+                $(let $t = $t::from_context(ctx)?;)*
+                Some(Box::pin((self)($($t),*)))
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(T1);
+impl_handler!(T1, T2);
+impl_handler!(T1, T2, T3);
+impl_handler!(T1, T2, T3, T4);
+impl_handler!(T1, T2, T3, T4, T5);
+impl_handler!(T1, T2, T3, T4, T5, T6);
+
+type BoxedHandler = Box<dyn Fn(&Context) -> Option<HandlerFuture> + Send + Sync>;
+
+struct Route {
+    filter: Filter,
+    handler: BoxedHandler,
+}
+
+/// Dispatches incoming messages to handlers, see the [module docs](self).
+pub struct Bot {
+    client: Arc<ApiClient>,
+    routes: Vec<Route>,
+    conversations: Arc<Conversations>,
+    dialog_timeout: Duration,
+}
+
+impl Bot {
+    pub fn new(client: impl Into<Arc<ApiClient>>) -> Self {
+        Self {
+            client: client.into(),
+            routes: Vec::new(),
+            conversations: Arc::new(Conversations::new()),
+            dialog_timeout: DEFAULT_DIALOG_TIMEOUT,
+        }
+    }
+
+    pub fn client(&self) -> &Arc<ApiClient> {
+        &self.client
+    }
+
+    /// The conversation states and waiting dialogs.
+    pub fn conversations(&self) -> &Arc<Conversations> {
+        &self.conversations
+    }
+
+    /// Sets how long a [`Dialog`](dialog::Dialog) waits for the next message,
+    /// defaults to [`DEFAULT_DIALOG_TIMEOUT`].
+    pub fn with_dialog_timeout(mut self, timeout: Duration) -> Self {
+        self.dialog_timeout = timeout;
+        self
+    }
+
+    /// Sets how long conversation states are kept after they were last set,
+    /// defaults to [`DEFAULT_STATE_TTL`](dialog::DEFAULT_STATE_TTL). Drops the
+    /// current states.
+    pub fn with_state_ttl(mut self, ttl: Duration) -> Self {
+        self.conversations = Arc::new(Conversations::new().with_state_ttl(ttl));
+        self
+    }
+
+    /// Adds a route, routes are tried in the order they were added.
+    pub fn with_route<T>(mut self, filter: Filter, handler: impl Handler<T>) -> Self {
+        self.routes.push(Route {
+            filter,
+            handler: Box::new(move |ctx| handler.call(ctx)),
+        });
+        self
+    }
+
+    /// Adds a [`Filter::Command`] route.
+    pub fn with_command<T>(self, command: &str, handler: impl Handler<T>) -> Self {
+        self.with_route(Filter::Command(command.to_string()), handler)
+    }
+
+    /// Adds a [`Filter::Pattern`] route, panics if `pattern` is not a valid regular expression.
+    pub fn with_pattern<T>(self, pattern: &str, handler: impl Handler<T>) -> Self {
+        let re = Regex::new(pattern).expect("invalid pattern");
+        self.with_route(Filter::Pattern(re), handler)
+    }
+
+    /// Runs the first matching handler, returns whether the message was handled.
+    ///
+    /// A message awaited by a [`Dialog`](dialog::Dialog) is handed to it instead.
+    /// Messages sent by the account itself are ignored.
+    ///
+    /// The handler is awaited, so a handler awaiting the next message would
+    /// wait for itself: dispatch with [`Bot::spawn`] when using dialogs.
+    pub async fn dispatch(&self, msg: &Message) -> Result<bool, Box<dyn Error>> {
+        if !msg.is_from_self() && self.conversations.offer(msg) {
+            return Ok(true);
+        }
+        match self.handler_for(msg) {
+            Some(fut) => fut.await.map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Like [`Bot::dispatch`], but runs the handler in a new tokio task.
+    ///
+    /// Handler errors are logged with the `tracing` feature and otherwise ignored.
+    pub fn spawn(&self, msg: &Message) -> bool {
+        if !msg.is_from_self() && self.conversations.offer(msg) {
+            return true;
+        }
+        match self.handler_for(msg) {
+            Some(fut) => {
+                tokio::spawn(async move {
+                    if let Err(_e) = fut.await {
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(error = %_e, "bot handler failed");
+                    }
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Dispatches the message of an `AddMsg` event, ignores any other event.
+    ///
+    /// Revocations are dispatched as their notice, see [`CallbackEvent::message`].
+    pub async fn handle_event(&self, event: &CallbackEvent) -> Result<bool, Box<dyn Error>> {
+        match event.message() {
+            Some(msg) => self.dispatch(msg).await,
+            None => Ok(false),
+        }
+    }
+
+    /// Dispatches the events received by `server` with [`Bot::spawn`] until it stops.
+    #[cfg(feature = "server")]
+    pub async fn run(&self, server: &mut crate::callback::server::CallbackServer) {
+        while let Some(event) = server.next_event().await {
+            if let Some(msg) = event.message() {
+                self.spawn(msg);
+            }
+        }
+    }
+
+    fn handler_for(&self, msg: &Message) -> Option<HandlerFuture> {
+        if msg.is_from_self() {
+            return None;
+        }
+        self.routes.iter().find_map(|route| {
+            let matched = route.filter.check(msg)?;
+            let ctx = Context {
+                client: self.client.clone(),
+                message: msg.clone(),
+                args: matched.args,
+                captures: matched.captures,
+                conversations: self.conversations.clone(),
+                dialog_timeout: self.dialog_timeout,
+            };
+            (route.handler)(&ctx)
+        })
+    }
+}
@@ -0,0 +1,359 @@
+//! Mass messaging to an audience of contacts and chatrooms.
+//!
+//! An [`Audience`] selects the recipients from a synced
+//! [`ContactDirectory`] by label names, remark/nickname patterns, explicit
+//! wxids and chatrooms. Recipients selected several times get the message
+//! once, excluded wxids never.
+//!
+//! A [`Broadcast`] sends a text, image or link to every recipient, one after
+//! the other with a pause in between, and returns a [`BroadcastReport`].
+//! Texts and link titles/descriptions are templates rendered per recipient:
+//!
+//! - `{remark}` - the remark of the contact, falling back to its nickname,
+//! - `{nickname}` - the nickname of the contact,
+//! - `{wxid}` - the wxid of the recipient.
+//!
+//! [`Broadcast::preview`] renders the messages without sending anything.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::api::ApiClientBuilder;
+//! use rgewe_api::broadcast::{Audience, Broadcast, Content};
+//! use rgewe_api::contacts::directory::ContactDirectory;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = ApiClientBuilder::new().with_token("your_token").build();
+//!     let directory = ContactDirectory::new("your_app_id");
+//!     directory.sync(&client, true).await.unwrap();
+//!     let audience = Audience::new()
+//!         .with_label("VIP")
+//!         .with_pattern("^Shop ")
+//!         .with_excluded(&["wxid_competitor"]);
+//!     let broadcast = Broadcast::new(audience, Content::text("Hi {remark}, our sale starts today!"));
+//!     for message in broadcast.preview(&client, &directory).await.unwrap() {
+//!         println!("{}: {:?}", message.to, message.content);
+//!     }
+//!     let report = broadcast.send(&client, &directory).await.unwrap();
+//!     println!("{} delivered, {} failed", report.delivered.len(), report.failed.len());
+//! }
+//! ```
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::api::{response_data, ApiClient, Wxid};
+use crate::contacts::directory::{Contact, ContactDirectory};
+use crate::contacts::labels::LabelManager;
+
+/// The default pause between two sends.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The recipients of a [`Broadcast`], see the [module docs](self).
+///
+/// Labels and patterns select friends only, chatrooms and official accounts
+/// are selected explicitly.
+#[derive(Debug, Clone, Default)]
+pub struct Audience {
+    labels: Vec<String>,
+    patterns: Vec<Regex>,
+    wxids: Vec<String>,
+    chatrooms: Vec<String>,
+    excluded: HashSet<String>,
+}
+
+impl Audience {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the friends tagged with the label `name`.
+    pub fn with_label(mut self, name: &str) -> Self {
+        self.labels.push(name.to_string());
+        self
+    }
+
+    /// Adds the friends whose remark or nickname matches `pattern`, panics if
+    /// `pattern` is not a valid regular expression.
+    pub fn with_pattern(mut self, pattern: &str) -> Self {
+        self.patterns
+            .push(Regex::new(pattern).expect("invalid pattern"));
+        self
+    }
+
+    /// Adds contacts by wxid.
+    pub fn with_wxids(mut self, wxids: &[&str]) -> Self {
+        self.wxids.extend(wxids.iter().map(|w| w.to_string()));
+        self
+    }
+
+    /// Adds chatrooms, the message is posted into them.
+    pub fn with_chatrooms(mut self, chatroom_ids: &[&str]) -> Self {
+        self.chatrooms
+            .extend(chatroom_ids.iter().map(|c| c.to_string()));
+        self
+    }
+
+    /// Never sends to these wxids or chatrooms.
+    pub fn with_excluded(mut self, wxids: &[&str]) -> Self {
+        self.excluded.extend(wxids.iter().map(|w| w.to_string()));
+        self
+    }
+
+    /// The recipients, in the order they were selected, without duplicates.
+    ///
+    /// Fails if a label does not exist. Recipients missing from the directory
+    /// have an empty nickname and remark.
+    pub async fn resolve(
+        &self,
+        client: &ApiClient,
+        directory: &ContactDirectory,
+    ) -> Result<Vec<Contact>, Box<dyn Error>> {
+        let is_friend = |c: &Contact| !c.is_chatroom() && !c.wxid.starts_with("gh_");
+        let mut selected = Vec::new();
+        if !self.labels.is_empty() {
+            let labels = LabelManager::new(directory.app_id());
+            for name in &self.labels {
+                let id = labels
+                    .id_of(client, name)
+                    .await?
+                    .ok_or_else(|| format!("Unknown label: {}", name))?;
+                selected.extend(directory.filter(|c| {
+                    is_friend(c) && c.label_ids.iter().any(|label_id| label_id == &id)
+                }));
+            }
+        }
+        for pattern in &self.patterns {
+            selected.extend(directory.filter(|c| {
+                is_friend(c) && (pattern.is_match(&c.remark) || pattern.is_match(&c.nick_name))
+            }));
+        }
+        for wxid in self.wxids.iter().chain(&self.chatrooms) {
+            selected.push(directory.get(wxid).unwrap_or_else(|| Contact {
+                wxid: wxid.clone(),
+                ..Contact::default()
+            }));
+        }
+
+        let mut seen = HashSet::new();
+        selected.retain(|c| !self.excluded.contains(&c.wxid) && seen.insert(c.wxid.clone()));
+        Ok(selected)
+    }
+}
+
+/// The message of a [`Broadcast`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Content {
+    /// Sent with `/message/postText`, the text is a template.
+    Text(String),
+    /// Sent with `/message/postImage`.
+    Image { url: String },
+    /// Sent with `/message/postLink`, the title and description are templates.
+    Link {
+        title: String,
+        desc: String,
+        url: String,
+        thumb_url: String,
+    },
+}
+
+impl Content {
+    pub fn text(template: &str) -> Self {
+        Content::Text(template.to_string())
+    }
+
+    pub fn image(url: &str) -> Self {
+        Content::Image {
+            url: url.to_string(),
+        }
+    }
+
+    pub fn link(title: &str, desc: &str, url: &str, thumb_url: &str) -> Self {
+        Content::Link {
+            title: title.to_string(),
+            desc: desc.to_string(),
+            url: url.to_string(),
+            thumb_url: thumb_url.to_string(),
+        }
+    }
+
+    /// The content for `contact`, with the placeholders of the templates replaced.
+    ///
+    /// The templates are scanned once, placeholders in the inserted values are kept as is.
+    pub fn render(&self, contact: &Contact) -> Content {
+        let values = [
+            ("{remark}", contact.name()),
+            ("{nickname}", contact.nick_name.as_str()),
+            ("{wxid}", contact.wxid.as_str()),
+        ];
+        let render = |template: &str| {
+            let mut rendered = String::with_capacity(template.len());
+            let mut rest = template;
+            while let Some(start) = rest.find('{') {
+                rendered.push_str(&rest[..start]);
+                rest = &rest[start..];
+                match values.iter().find(|(key, _)| rest.starts_with(key)) {
+                    Some((key, value)) => {
+                        rendered.push_str(value);
+                        rest = &rest[key.len()..];
+                    }
+                    None => {
+                        rendered.push('{');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            rendered.push_str(rest);
+            rendered
+        };
+        match self {
+            Content::Text(text) => Content::Text(render(text)),
+            Content::Image { url } => Content::Image { url: url.clone() },
+            Content::Link {
+                title,
+                desc,
+                url,
+                thumb_url,
+            } => Content::Link {
+                title: render(title),
+                desc: render(desc),
+                url: url.clone(),
+                thumb_url: thumb_url.clone(),
+            },
+        }
+    }
+
+    /// Sends the content to `to_wxid`, returns the `data` of the response.
+    async fn send_to(
+        &self,
+        client: &ApiClient,
+        app_id: &str,
+        to_wxid: &str,
+    ) -> Result<Value, Box<dyn Error>> {
+        let to_wxid = Wxid::try_from(to_wxid)?;
+        let ret = match self {
+            Content::Text(content) => client.post_text(app_id, &to_wxid, content, "").await?,
+            Content::Image { url } => client.post_image(app_id, &to_wxid, url).await?,
+            Content::Link {
+                title,
+                desc,
+                url,
+                thumb_url,
+            } => {
+                client
+                    .post_link(app_id, &to_wxid, title, desc, url, thumb_url)
+                    .await?
+            }
+        };
+        response_data(ret)
+    }
+}
+
+/// A message of a [`Broadcast`], as rendered for its recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Planned {
+    pub to: String,
+    /// The name of the recipient, see [`Contact::name`].
+    pub name: String,
+    pub content: Content,
+}
+
+/// The outcome of [`Broadcast::send`].
+#[derive(Debug, Clone, Default)]
+pub struct BroadcastReport {
+    /// The recipients the message was sent to, with the `data` of the send response.
+    pub delivered: Vec<(String, Value)>,
+    /// The recipients the message could not be sent to, with the error message.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Sends a message to an [`Audience`], see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Broadcast {
+    audience: Audience,
+    content: Content,
+    interval: Duration,
+    #[cfg(feature = "metrics")]
+    metrics: Option<std::sync::Arc<crate::metrics::Metrics>>,
+}
+
+impl Broadcast {
+    pub fn new(audience: Audience, content: Content) -> Self {
+        Self {
+            audience,
+            content,
+            interval: DEFAULT_INTERVAL,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Pauses `interval` between two sends instead of [`DEFAULT_INTERVAL`].
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Reports the number of messages left to send as `gewe_outbound_queue_depth`.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: std::sync::Arc<crate::metrics::Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// The messages [`Broadcast::send`] would send, without sending anything.
+    pub async fn preview(
+        &self,
+        client: &ApiClient,
+        directory: &ContactDirectory,
+    ) -> Result<Vec<Planned>, Box<dyn Error>> {
+        let recipients = self.audience.resolve(client, directory).await?;
+        Ok(recipients
+            .iter()
+            .map(|contact| Planned {
+                to: contact.wxid.clone(),
+                name: contact.name().to_string(),
+                content: self.content.render(contact),
+            })
+            .collect())
+    }
+
+    /// Sends the message to every recipient, from the account of `directory`.
+    ///
+    /// Fails only if the audience cannot be resolved, failed sends are
+    /// reported and do not stop the broadcast.
+    pub async fn send(
+        &self,
+        client: &ApiClient,
+        directory: &ContactDirectory,
+    ) -> Result<BroadcastReport, Box<dyn Error>> {
+        let planned = self.preview(client, directory).await?;
+        let mut report = BroadcastReport::default();
+        for (i, message) in planned.iter().enumerate() {
+            if i > 0 && !self.interval.is_zero() {
+                tokio::time::sleep(self.interval).await;
+            }
+            self.set_queue_depth(planned.len() - i);
+            match message
+                .content
+                .send_to(client, directory.app_id(), &message.to)
+                .await
+            {
+                Ok(data) => report.delivered.push((message.to.clone(), data)),
+                Err(e) => report.failed.push((message.to.clone(), e.to_string())),
+            }
+        }
+        self.set_queue_depth(0);
+        Ok(report)
+    }
+
+    fn set_queue_depth(&self, _depth: usize) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.set_queue_depth(_depth);
+        }
+    }
+}
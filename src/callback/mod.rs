@@ -0,0 +1,467 @@
+//! Typed model of the payloads Gewe posts to the callback URL.
+//!
+//! The callback URL is configured with [`ApiClient::set_call_back`](crate::api::ApiClient::set_call_back).
+//! Every payload is a JSON object with a `TypeName` field (`AddMsg`, `ModContacts`,
+//! `DelContacts`, `Offline`, ...), the `Appid` and `Wxid` of the logged in account
+//! and a `Data` object whose string fields are wrapped as `{"string": "..."}`.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::xml;
+
+pub mod revoke;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod system;
+
+/// Message types carried in the `MsgType` field of an `AddMsg` callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageType {
+    Text,
+    Image,
+    Voice,
+    /// Friend request (verify message).
+    FriendRequest,
+    NameCard,
+    Video,
+    Emoji,
+    Location,
+    /// App message (link, file, mini-app, quote, chat history, ...) with an `<appmsg>` XML body.
+    AppMsg,
+    /// Status notification, e.g. a conversation was opened on the phone.
+    StatusNotify,
+    /// Plain text system notice (type 10000).
+    SystemNotice,
+    /// XML system message (type 10002), e.g. `revokemsg` or `sysmsgtemplate`.
+    SystemMessage,
+    Other(u32),
+}
+
+impl From<u32> for MessageType {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => MessageType::Text,
+            3 => MessageType::Image,
+            34 => MessageType::Voice,
+            37 => MessageType::FriendRequest,
+            42 => MessageType::NameCard,
+            43 => MessageType::Video,
+            47 => MessageType::Emoji,
+            48 => MessageType::Location,
+            49 => MessageType::AppMsg,
+            51 => MessageType::StatusNotify,
+            10000 => MessageType::SystemNotice,
+            10002 => MessageType::SystemMessage,
+            other => MessageType::Other(other),
+        }
+    }
+}
+
+/// A message delivered by an `AddMsg` callback.
+///
+/// In chatrooms `from_user` is the chatroom id and `content` is prefixed with
+/// the sender wxid (`wxid_xxx:\n...`), use [`Message::sender`] and
+/// [`Message::text`] to split them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    /// The application identifier of the receiving account.
+    pub app_id: String,
+    /// The wxid of the receiving (logged in) account.
+    pub wxid: String,
+    pub msg_id: i64,
+    pub new_msg_id: i64,
+    pub from_user: String,
+    pub to_user: String,
+    pub msg_type: u32,
+    /// Raw content, including the sender prefix for chatroom messages.
+    pub content: String,
+    pub create_time: i64,
+    pub msg_source: String,
+    pub push_content: String,
+}
+
+impl Message {
+    /// Returns the typed `MsgType`.
+    pub fn message_type(&self) -> MessageType {
+        MessageType::from(self.msg_type)
+    }
+
+    /// Whether the message was sent by the logged in account itself (e.g. from the phone).
+    pub fn is_from_self(&self) -> bool {
+        self.from_user == self.wxid
+    }
+
+    /// Whether the message belongs to a chatroom/group conversation.
+    pub fn is_chatroom(&self) -> bool {
+        self.chatroom_id().is_some()
+    }
+
+    /// The chatroom id if the message belongs to a chatroom/group conversation.
+    pub fn chatroom_id(&self) -> Option<&str> {
+        [&self.from_user, &self.to_user]
+            .into_iter()
+            .find(|id| id.ends_with("@chatroom"))
+            .map(String::as_str)
+    }
+
+    /// The conversation the message belongs to, i.e. where a reply should go.
+    pub fn chat_id(&self) -> &str {
+        if self.is_from_self() {
+            &self.to_user
+        } else {
+            &self.from_user
+        }
+    }
+
+    /// The wxid of the actual sender, resolving the chatroom content prefix.
+    pub fn sender(&self) -> &str {
+        match self.split_content() {
+            Some((sender, _)) => sender,
+            None => &self.from_user,
+        }
+    }
+
+    /// The content without the chatroom sender prefix.
+    pub fn text(&self) -> &str {
+        match self.split_content() {
+            Some((_, text)) => text,
+            None => &self.content,
+        }
+    }
+
+    /// The `<appmsg><type>` of an [`MessageType::AppMsg`] message,
+    /// e.g. 5 for links, 6 for files, 33/36 for mini-apps and 57 for quotes.
+    pub fn app_msg_type(&self) -> Option<u32> {
+        self.app_msg().map(|app_msg| app_msg.kind)
+    }
+
+    /// The parsed `<appmsg>` of an [`MessageType::AppMsg`] message.
+    pub fn app_msg(&self) -> Option<AppMsg> {
+        if self.message_type() != MessageType::AppMsg {
+            return None;
+        }
+        AppMsg::parse(self.text())
+    }
+
+    /// The message quoted by an appmsg type 57 message.
+    pub fn quoted(&self) -> Option<ReferMsg> {
+        self.app_msg()?.refer
+    }
+
+    /// Wxids mentioned with `@` in a chatroom message.
+    pub fn at_list(&self) -> Vec<String> {
+        xml::parse(&self.msg_source)
+            .and_then(|doc| xml::text(doc.root(), "atuserlist"))
+            .map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether `wxid` was mentioned with `@` in this message.
+    pub fn mentions(&self, wxid: &str) -> bool {
+        self.at_list().iter().any(|at| at == wxid)
+    }
+
+    fn split_content(&self) -> Option<(&str, &str)> {
+        if !self.from_user.ends_with("@chatroom") {
+            return None;
+        }
+        let (sender, text) = self.content.split_once(":\n")?;
+        // XML bodies may contain ":\n" as well, a sender never contains '<' or spaces.
+        if sender.is_empty() || sender.contains(['<', ' ']) {
+            return None;
+        }
+        Some((sender, text))
+    }
+}
+
+impl TryFrom<&Value> for Message {
+    type Error = String;
+
+    /// Builds a message from a full `AddMsg` callback payload.
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let data = value
+            .get("Data")
+            .ok_or_else(|| "Missing `Data` in AddMsg callback".to_string())?;
+        Ok(Message {
+            app_id: string_field(value, "Appid"),
+            wxid: string_field(value, "Wxid"),
+            msg_id: int_field(data, "MsgId"),
+            new_msg_id: int_field(data, "NewMsgId"),
+            from_user: string_field(data, "FromUserName"),
+            to_user: string_field(data, "ToUserName"),
+            msg_type: int_field(data, "MsgType") as u32,
+            content: string_field(data, "Content"),
+            create_time: int_field(data, "CreateTime"),
+            msg_source: string_field(data, "MsgSource"),
+            push_content: string_field(data, "PushContent"),
+        })
+    }
+}
+
+/// The summary of an `<appmsg>` XML body, see [`Message::app_msg`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppMsg {
+    /// The `<type>`, e.g. 5 for links, 6 for files, 33/36 for mini-apps and 57 for quotes.
+    #[serde(rename = "type")]
+    pub kind: u32,
+    pub title: String,
+    pub des: String,
+    pub url: String,
+    /// The quoted message of a quote (type 57), whose `title` is the reply text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refer: Option<ReferMsg>,
+}
+
+impl AppMsg {
+    /// Parses an `<appmsg>` element, at any depth of `xml` (usually `<msg><appmsg>`).
+    pub fn parse(xml: &str) -> Option<AppMsg> {
+        let doc = xml::parse(xml)?;
+        let appmsg = xml::find(doc.root(), "appmsg")?;
+        // Direct children only, a quote nests the referenced message's fields.
+        let child = |tag: &str| {
+            appmsg
+                .children()
+                .find(|n| n.has_tag_name(tag))
+                .map(|n| xml::node_text(n).trim().to_string())
+                .unwrap_or_default()
+        };
+        Some(AppMsg {
+            kind: child("type").parse().ok()?,
+            title: child("title"),
+            des: child("des"),
+            url: child("url"),
+            refer: appmsg
+                .children()
+                .find(|n| n.has_tag_name("refermsg"))
+                .map(ReferMsg::from_node),
+        })
+    }
+}
+
+/// The `<refermsg>` of a quote (appmsg type 57): the quoted message.
+///
+/// In chatrooms `from_user` is the chatroom and `chat_user` the author of the
+/// quoted message, in private chats `from_user` is the author.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReferMsg {
+    /// The `MsgType` of the quoted message.
+    #[serde(rename = "type")]
+    pub msg_type: u32,
+    /// The `newMsgId` of the quoted message (`<svrid>`).
+    pub new_msg_id: i64,
+    pub from_user: String,
+    pub chat_user: String,
+    /// The name of the author shown in the quote.
+    pub display_name: String,
+    /// The text, or XML body, of the quoted message.
+    pub content: String,
+    pub create_time: i64,
+}
+
+impl ReferMsg {
+    fn from_node(node: roxmltree::Node) -> ReferMsg {
+        let text = |tag: &str| xml::text(node, tag).unwrap_or_default();
+        ReferMsg {
+            msg_type: text("type").parse().unwrap_or_default(),
+            new_msg_id: text("svrid").parse().unwrap_or_default(),
+            from_user: text("fromusr"),
+            chat_user: text("chatusr"),
+            display_name: text("displayname"),
+            content: text("content"),
+            create_time: text("createtime").parse().unwrap_or_default(),
+        }
+    }
+
+    /// The wxid of the author of the quoted message.
+    pub fn sender(&self) -> &str {
+        if self.chat_user.is_empty() {
+            &self.from_user
+        } else {
+            &self.chat_user
+        }
+    }
+
+    /// The `<appmsg>` of a quote replying `text` to this message, for
+    /// [`ApiClient::post_app_msg`](crate::api::ApiClient::post_app_msg).
+    pub fn to_appmsg(&self, text: &str) -> String {
+        format!(
+            "<appmsg appid=\"\" sdkver=\"0\"><title>{}</title><des></des><action></action><type>57</type><showtype>0</showtype><content></content><url></url><appattach></appattach><refermsg><type>{}</type><svrid>{}</svrid><fromusr>{}</fromusr><chatusr>{}</chatusr><displayname>{}</displayname><content>{}</content><createtime>{}</createtime></refermsg></appmsg>",
+            xml::escape(text),
+            self.msg_type,
+            self.new_msg_id,
+            xml::escape(&self.from_user),
+            xml::escape(&self.chat_user),
+            xml::escape(&self.display_name),
+            xml::escape(&self.content),
+            self.create_time,
+        )
+    }
+}
+
+/// A message that can be quoted with [`ApiClient::reply_to`](crate::api::ApiClient::reply_to).
+pub trait Quotable {
+    /// The application identifier of the account the message belongs to.
+    fn app_id(&self) -> &str;
+
+    /// The conversation of the message, where the reply goes.
+    fn chat_id(&self) -> &str;
+
+    /// The `<refermsg>` quoting the message.
+    fn refer_msg(&self) -> ReferMsg;
+}
+
+impl Quotable for Message {
+    fn app_id(&self) -> &str {
+        &self.app_id
+    }
+
+    fn chat_id(&self) -> &str {
+        Message::chat_id(self)
+    }
+
+    /// Quotes the message with the wxid of its author as display name.
+    fn refer_msg(&self) -> ReferMsg {
+        let (from_user, chat_user) = match self.chatroom_id() {
+            Some(chatroom_id) => (chatroom_id, self.sender()),
+            None => (self.sender(), ""),
+        };
+        ReferMsg {
+            msg_type: self.msg_type,
+            new_msg_id: self.new_msg_id,
+            from_user: from_user.to_string(),
+            chat_user: chat_user.to_string(),
+            display_name: self.sender().to_string(),
+            content: self.text().to_string(),
+            create_time: self.create_time,
+        }
+    }
+}
+
+/// A callback payload posted by the Gewe service.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallbackEvent {
+    /// `AddMsg`: a new message.
+    Message(Message),
+    /// `AddMsg` of a `revokemsg` system message: a message was revoked, see
+    /// [`revoke`]. [`RecentMessages::observe`](revoke::RecentMessages::observe)
+    /// attaches the original message.
+    MessageRevoked(Box<revoke::MessageRevoked>),
+    /// `ModContacts`: a contact or chatroom was added or modified.
+    ContactModified {
+        app_id: String,
+        wxid: String,
+        data: Value,
+    },
+    /// `DelContacts`: a contact or chatroom was removed.
+    ContactDeleted {
+        app_id: String,
+        wxid: String,
+        user_name: String,
+    },
+    /// `Offline`: the account went offline.
+    Offline { app_id: String, wxid: String },
+    /// The test payload posted by `/tools/setCallBack`.
+    Test,
+    /// Any other `TypeName`.
+    Other { type_name: String, payload: Value },
+}
+
+impl CallbackEvent {
+    /// The application identifier of the account the event belongs to, if any.
+    pub fn app_id(&self) -> Option<&str> {
+        match self {
+            CallbackEvent::Message(msg) => Some(&msg.app_id),
+            CallbackEvent::MessageRevoked(revoked) => Some(&revoked.app_id),
+            CallbackEvent::ContactModified { app_id, .. }
+            | CallbackEvent::ContactDeleted { app_id, .. }
+            | CallbackEvent::Offline { app_id, .. } => Some(app_id),
+            CallbackEvent::Test | CallbackEvent::Other { .. } => None,
+        }
+    }
+
+    /// The message of an `AddMsg` event, the [`notice`](revoke::MessageRevoked::notice)
+    /// of a revocation.
+    pub fn message(&self) -> Option<&Message> {
+        match self {
+            CallbackEvent::Message(msg) => Some(msg),
+            CallbackEvent::MessageRevoked(revoked) => Some(&revoked.notice),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&Value> for CallbackEvent {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        if value.get("testMsg").is_some() {
+            return Ok(CallbackEvent::Test);
+        }
+        let type_name = value
+            .get("TypeName")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("Missing `TypeName` in callback: {}", value))?;
+        let app_id = string_field(value, "Appid");
+        let wxid = string_field(value, "Wxid");
+        let event = match type_name {
+            "AddMsg" => {
+                let msg = Message::try_from(value)?;
+                match revoke::MessageRevoked::parse(&msg) {
+                    Some(revoked) => CallbackEvent::MessageRevoked(Box::new(revoked)),
+                    None => CallbackEvent::Message(msg),
+                }
+            }
+            "ModContacts" => CallbackEvent::ContactModified {
+                app_id,
+                wxid,
+                data: value.get("Data").cloned().unwrap_or(Value::Null),
+            },
+            "DelContacts" => CallbackEvent::ContactDeleted {
+                app_id,
+                wxid,
+                user_name: value
+                    .get("Data")
+                    .map(|data| string_field(data, "UserName"))
+                    .unwrap_or_default(),
+            },
+            "Offline" => CallbackEvent::Offline { app_id, wxid },
+            other => CallbackEvent::Other {
+                type_name: other.to_string(),
+                payload: value.clone(),
+            },
+        };
+        Ok(event)
+    }
+}
+
+/// Reads a string field that is either plain or wrapped as `{"string": "..."}`.
+pub(crate) fn string_field(value: &Value, key: &str) -> String {
+    match value.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Object(o)) => o
+            .get("string")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        Some(Value::Number(n)) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Reads an integer field that may also be encoded as a string.
+pub(crate) fn int_field(value: &Value, key: &str) -> i64 {
+    match value.get(key) {
+        Some(Value::Number(n)) => n
+            .as_i64()
+            .or_else(|| n.as_u64().map(|u| u as i64))
+            .unwrap_or_default(),
+        Some(Value::String(s)) => s.parse().unwrap_or_default(),
+        _ => 0,
+    }
+}
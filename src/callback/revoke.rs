@@ -0,0 +1,163 @@
+//! Messages revoked by their sender.
+//!
+//! When a message is revoked, WeChat posts an XML system message (type 10002)
+//! into the conversation, whose `revokemsg` references the revoked message by
+//! its `newmsgid`:
+//!
+//! ```xml
+//! <sysmsg type="revokemsg">
+//!     <revokemsg>
+//!         <session>34757816141@chatroom</session>
+//!         <msgid>1040356095</msgid>
+//!         <newmsgid>7773749793478223190</newmsgid>
+//!         <replacemsg><![CDATA["张三" 撤回了一条消息]]></replacemsg>
+//!     </revokemsg>
+//! </sysmsg>
+//! ```
+//!
+//! [`CallbackEvent::try_from`] turns it into a [`CallbackEvent::MessageRevoked`]
+//! event, [`MessageRevoked::parse`] reads it from a [`Message`]. The content of
+//! the revoked message is not repeated, a [`RecentMessages`] buffer keeps the
+//! last received messages to recover it.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use rgewe_api::callback::revoke::RecentMessages;
+//! use rgewe_api::callback::CallbackEvent;
+//!
+//! fn on_event(recent: &RecentMessages, event: &CallbackEvent) {
+//!     if let Some(revoked) = recent.observe(event) {
+//!         let content = revoked.original.as_ref().map(|msg| msg.text());
+//!         println!("{} revoked {:?}", revoked.revoker, content);
+//!     }
+//! }
+//! ```
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use super::{CallbackEvent, Message, MessageType};
+use crate::xml;
+
+/// A message revoked by its sender, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageRevoked {
+    /// The application identifier of the receiving account.
+    pub app_id: String,
+    /// The conversation of the revoked message.
+    pub chat_id: String,
+    /// The wxid of the member who revoked the message.
+    pub revoker: String,
+    pub msg_id: i64,
+    pub new_msg_id: i64,
+    /// The notice shown instead of the message, e.g. `"张三" 撤回了一条消息`.
+    pub replace_msg: String,
+    /// The revoked message, if still in the [`RecentMessages`] buffer.
+    pub original: Option<Message>,
+    /// The system message announcing the revocation.
+    pub notice: Message,
+}
+
+impl MessageRevoked {
+    /// Parses a `revokemsg` system message, returns `None` for any other message.
+    ///
+    /// `original` is always `None`, see [`RecentMessages::observe`].
+    pub fn parse(msg: &Message) -> Option<MessageRevoked> {
+        if msg.message_type() != MessageType::SystemMessage {
+            return None;
+        }
+        let doc = xml::parse(msg.text())?;
+        let sysmsg = xml::find(doc.root(), "sysmsg")?;
+        if sysmsg.attribute("type")? != "revokemsg" {
+            return None;
+        }
+        let revokemsg = xml::find(sysmsg, "revokemsg")?;
+        let chat_id = xml::text(revokemsg, "session")
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| msg.chat_id().to_string());
+        Some(MessageRevoked {
+            app_id: msg.app_id.clone(),
+            chat_id,
+            revoker: msg.sender().to_string(),
+            msg_id: xml::text(revokemsg, "msgid")?.parse().unwrap_or_default(),
+            new_msg_id: xml::text(revokemsg, "newmsgid")?.parse().ok()?,
+            replace_msg: xml::text(revokemsg, "replacemsg").unwrap_or_default(),
+            original: None,
+            notice: msg.clone(),
+        })
+    }
+}
+
+/// A bounded buffer of the last received messages, to recover the content
+/// of revoked messages.
+///
+/// The oldest messages are dropped once `capacity` is reached. System
+/// messages are not kept.
+#[derive(Debug)]
+pub struct RecentMessages {
+    capacity: usize,
+    messages: Mutex<VecDeque<Message>>,
+}
+
+impl RecentMessages {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            messages: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Keeps a received message.
+    pub fn record(&self, msg: &Message) {
+        if self.capacity == 0
+            || matches!(
+                msg.message_type(),
+                MessageType::SystemNotice | MessageType::SystemMessage
+            )
+        {
+            return;
+        }
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() == self.capacity {
+            messages.pop_front();
+        }
+        messages.push_back(msg.clone());
+    }
+
+    /// The kept message `new_msg_id` of the account `app_id`.
+    pub fn get(&self, app_id: &str, new_msg_id: i64) -> Option<Message> {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|msg| msg.new_msg_id == new_msg_id && msg.app_id == app_id)
+            .cloned()
+    }
+
+    /// Keeps the message of an `AddMsg` event, or returns the revocation it
+    /// announces with the original message attached if it was kept.
+    pub fn observe(&self, event: &CallbackEvent) -> Option<MessageRevoked> {
+        let mut revoked = match event {
+            CallbackEvent::MessageRevoked(revoked) => (**revoked).clone(),
+            CallbackEvent::Message(msg) => match MessageRevoked::parse(msg) {
+                Some(revoked) => revoked,
+                None => {
+                    self.record(msg);
+                    return None;
+                }
+            },
+            _ => return None,
+        };
+        revoked.original = self.get(&revoked.app_id, revoked.new_msg_id);
+        Some(revoked)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
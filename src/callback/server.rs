@@ -0,0 +1,184 @@
+//! HTTP receiver for the Gewe callbacks.
+//!
+//! [`CallbackServer`] accepts the payloads Gewe posts to the callback URL (on
+//! any path), parses them into [`CallbackEvent`]s and queues them on an event
+//! stream read with [`CallbackServer::next_event`]. Events can also be pushed
+//! into the stream directly through an [`EventSender`], e.g. by tests.
+//!
+//! Enabled by the `server` feature.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::api::ApiClientBuilder;
+//! use rgewe_api::callback::server::CallbackServer;
+//! use rgewe_api::callback::CallbackEvent;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = ApiClientBuilder::new().with_token("your_token").build();
+//!     let mut server = CallbackServer::bind("0.0.0.0:18080").await.unwrap();
+//!     client
+//!         .set_call_back("your_token", "http://your_host:18080/callback")
+//!         .await
+//!         .unwrap();
+//!     while let Some(event) = server.next_event().await {
+//!         if let CallbackEvent::Message(msg) = event {
+//!             println!("{}: {}", msg.sender(), msg.text());
+//!         }
+//!     }
+//! }
+//! ```
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::Router;
+use serde_json::Value;
+use std::error::Error;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+
+use super::CallbackEvent;
+
+/// Pushes events into the stream of a [`CallbackServer`].
+pub type EventSender = mpsc::UnboundedSender<CallbackEvent>;
+
+/// Receives Gewe callbacks over HTTP, see the [module docs](self).
+///
+/// The server runs in the background until dropped.
+pub struct CallbackServer {
+    addr: SocketAddr,
+    sender: EventSender,
+    events: mpsc::UnboundedReceiver<CallbackEvent>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl CallbackServer {
+    /// Binds the server to `addr`, e.g. `0.0.0.0:18080`, and starts serving.
+    pub async fn bind(addr: impl ToSocketAddrs) -> Result<CallbackServer, Box<dyn Error>> {
+        let (sender, events) = mpsc::unbounded_channel();
+        let app = Router::new()
+            .route("/", post(receive))
+            .route("/{*path}", post(receive))
+            .with_state(Receiver {
+                sender: sender.clone(),
+                #[cfg(feature = "metrics")]
+                metrics: None,
+            });
+        Self::serve(addr, app, sender, events).await
+    }
+
+    /// Like [`CallbackServer::bind`], also counting the events in `metrics` and serving them on `GET /metrics`.
+    #[cfg(feature = "metrics")]
+    pub async fn bind_with_metrics(
+        addr: impl ToSocketAddrs,
+        metrics: std::sync::Arc<crate::metrics::Metrics>,
+    ) -> Result<CallbackServer, Box<dyn Error>> {
+        let (sender, events) = mpsc::unbounded_channel();
+        let rendered = metrics.clone();
+        let app = Router::new()
+            .route(
+                "/metrics",
+                axum::routing::get(move || async move { rendered.render() }),
+            )
+            .route("/", post(receive))
+            .route("/{*path}", post(receive))
+            .with_state(Receiver {
+                sender: sender.clone(),
+                metrics: Some(metrics),
+            });
+        Self::serve(addr, app, sender, events).await
+    }
+
+    async fn serve(
+        addr: impl ToSocketAddrs,
+        app: Router,
+        sender: EventSender,
+        events: mpsc::UnboundedReceiver<CallbackEvent>,
+    ) -> Result<CallbackServer, Box<dyn Error>> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await;
+        });
+        Ok(CallbackServer {
+            addr,
+            sender,
+            events,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The callback URL of the server, to register with
+    /// [`ApiClient::set_call_back`](crate::api::ApiClient::set_call_back).
+    pub fn url(&self) -> String {
+        format!("http://{}/callback", self.addr)
+    }
+
+    /// A sender injecting events into the stream as if they were received over HTTP.
+    pub fn sender(&self) -> EventSender {
+        self.sender.clone()
+    }
+
+    /// Waits for the next received event.
+    pub async fn next_event(&mut self) -> Option<CallbackEvent> {
+        self.events.recv().await
+    }
+
+    /// Returns the next event if one is queued, without waiting.
+    pub fn try_next_event(&mut self) -> Option<CallbackEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+impl Drop for CallbackServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Receiver {
+    sender: EventSender,
+    #[cfg(feature = "metrics")]
+    metrics: Option<std::sync::Arc<crate::metrics::Metrics>>,
+}
+
+async fn receive(State(receiver): State<Receiver>, body: Bytes) -> StatusCode {
+    let Ok(payload) = serde_json::from_slice::<Value>(&body) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    match CallbackEvent::try_from(&payload) {
+        Ok(event) => {
+            #[cfg(feature = "tracing")]
+            crate::trace::callback_span(&event).in_scope(
+                || tracing::debug!(payload = %crate::trace::redact(&payload), "callback received"),
+            );
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &receiver.metrics {
+                metrics.observe_callback(&event);
+            }
+            let _ = receiver.sender.send(event);
+            StatusCode::OK
+        }
+        Err(_e) => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = %_e, "invalid callback payload");
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Chatroom events announced through system messages.
+//!
+//! WeChat announces membership and settings changes of a chatroom as system
+//! messages posted into the chatroom itself: plain text notices (type 10000),
+//! e.g. `"张三"邀请"李四"加入了群聊`, or XML messages (type 10002) carrying a
+//! `sysmsgtemplate` whose `$placeholders$` link to the involved members.
+use roxmltree::Node;
+use std::collections::HashMap;
+
+use super::{Message, MessageType};
+use crate::xml;
+
+/// A chatroom member referenced by a system message.
+///
+/// Plain text notices only carry nicknames, so `wxid` is `None` for them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub wxid: Option<String>,
+    pub nickname: String,
+}
+
+/// An event parsed from a chatroom system message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SystemEvent {
+    /// Members joined through an invitation or by scanning a shared QR code.
+    ///
+    /// For QR code joins `inviter` is the member who shared the QR code.
+    MemberJoined {
+        chatroom_id: String,
+        inviter: Option<Member>,
+        members: Vec<Member>,
+        via_qr: bool,
+    },
+    /// Members were kicked by `operator`, or left by themselves if `operator` is `None`.
+    MemberRemoved {
+        chatroom_id: String,
+        operator: Option<Member>,
+        members: Vec<Member>,
+    },
+    /// The chatroom was renamed to `name`.
+    ChatroomRenamed {
+        chatroom_id: String,
+        operator: Option<Member>,
+        name: String,
+    },
+    /// The chatroom announcement was changed to `content`.
+    AnnouncementChanged {
+        chatroom_id: String,
+        content: String,
+    },
+    /// The chatroom ownership was transferred to `new_owner`.
+    OwnerTransferred {
+        chatroom_id: String,
+        new_owner: Member,
+    },
+}
+
+impl SystemEvent {
+    /// Parses a chatroom system message, returns `None` for any other message.
+    pub fn parse(msg: &Message) -> Option<SystemEvent> {
+        let chatroom_id = msg.chatroom_id()?.to_string();
+        match msg.message_type() {
+            MessageType::SystemNotice => {
+                let resolver = Resolver::plain(msg);
+                parse_notice(chatroom_id, msg.text(), &resolver)
+            }
+            MessageType::SystemMessage => {
+                let doc = xml::parse(msg.text())?;
+                let sysmsg = xml::find(doc.root(), "sysmsg")?;
+                match sysmsg.attribute("type")? {
+                    "sysmsgtemplate" => {
+                        let template = xml::text(sysmsg, "template")?;
+                        let resolver = Resolver::template(msg, sysmsg);
+                        parse_notice(chatroom_id, &template, &resolver)
+                    }
+                    "mmchatroombarannouncememt" => Some(SystemEvent::AnnouncementChanged {
+                        chatroom_id,
+                        content: xml::text(sysmsg, "content").unwrap_or_default(),
+                    }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The chatroom the event happened in.
+    pub fn chatroom_id(&self) -> &str {
+        match self {
+            SystemEvent::MemberJoined { chatroom_id, .. }
+            | SystemEvent::MemberRemoved { chatroom_id, .. }
+            | SystemEvent::ChatroomRenamed { chatroom_id, .. }
+            | SystemEvent::AnnouncementChanged { chatroom_id, .. }
+            | SystemEvent::OwnerTransferred { chatroom_id, .. } => chatroom_id,
+        }
+    }
+}
+
+/// Resolves the member references of a notice segment.
+///
+/// A segment is either a `$placeholder$` (templates), a quoted list of
+/// nicknames separated by `、` (plain notices) or `你` for the account itself.
+struct Resolver {
+    self_wxid: String,
+    links: HashMap<String, Vec<Member>>,
+}
+
+impl Resolver {
+    fn plain(msg: &Message) -> Self {
+        Resolver {
+            self_wxid: msg.wxid.clone(),
+            links: HashMap::new(),
+        }
+    }
+
+    fn template(msg: &Message, sysmsg: Node) -> Self {
+        let links = sysmsg
+            .descendants()
+            .filter(|n| n.has_tag_name("link"))
+            .filter_map(|link| {
+                let members = link
+                    .descendants()
+                    .filter(|n| n.has_tag_name("member"))
+                    .map(|member| Member {
+                        wxid: xml::text(member, "username").filter(|s| !s.is_empty()),
+                        nickname: xml::text(member, "nickname").unwrap_or_default(),
+                    })
+                    .collect();
+                Some((link.attribute("name")?.to_string(), members))
+            })
+            .collect();
+        Resolver {
+            self_wxid: msg.wxid.clone(),
+            links,
+        }
+    }
+
+    fn members(&self, segment: &str) -> Vec<Member> {
+        if let Some(name) = placeholder(segment) {
+            return self.links.get(name).cloned().unwrap_or_default();
+        }
+        let unquoted = segment
+            .trim()
+            .trim_matches(|c| matches!(c, '"' | '“' | '”'))
+            .trim();
+        if unquoted.is_empty() {
+            return Vec::new();
+        }
+        if unquoted == "你" {
+            return vec![Member {
+                wxid: Some(self.self_wxid.clone()),
+                nickname: unquoted.to_string(),
+            }];
+        }
+        unquoted
+            .split('、')
+            .map(|nickname| Member {
+                wxid: None,
+                nickname: nickname.to_string(),
+            })
+            .collect()
+    }
+
+    fn member(&self, segment: &str) -> Option<Member> {
+        self.members(segment).into_iter().next()
+    }
+}
+
+/// Returns the name of the first `$placeholder$` in `segment`.
+fn placeholder(segment: &str) -> Option<&str> {
+    let start = segment.find('$')? + 1;
+    let len = segment[start..].find('$')?;
+    Some(&segment[start..start + len])
+}
+
+fn parse_notice(chatroom_id: String, notice: &str, resolver: &Resolver) -> Option<SystemEvent> {
+    let notice = notice.trim();
+    if notice.contains("二维码") && notice.contains("加入群聊") {
+        // "$adder$"通过扫描"$from$"分享的二维码加入群聊
+        let (joined, rest) = notice.split_once("通过扫描")?;
+        let (from, _) = rest.split_once("分享")?;
+        return Some(SystemEvent::MemberJoined {
+            chatroom_id,
+            inviter: resolver.member(from),
+            members: resolver.members(joined),
+            via_qr: true,
+        });
+    }
+    if notice.contains("加入了群聊") || notice.contains("加入群聊") {
+        // "$username$"邀请"$names$"加入了群聊
+        let (inviter, rest) = notice.split_once("邀请")?;
+        let (joined, _) = rest.split_once("加入")?;
+        return Some(SystemEvent::MemberJoined {
+            chatroom_id,
+            inviter: resolver.member(inviter),
+            members: resolver.members(joined),
+            via_qr: false,
+        });
+    }
+    if notice.contains("移出") && notice.contains("群聊") {
+        // "$kickoperator$"将"$kickedusers$"移出了群聊
+        let (operator, rest) = notice.split_once('将')?;
+        let (removed, _) = rest.split_once("移出")?;
+        return Some(SystemEvent::MemberRemoved {
+            chatroom_id,
+            operator: resolver.member(operator),
+            members: resolver.members(removed),
+        });
+    }
+    if let Some((left, _)) = notice
+        .split_once("退出了群聊")
+        .or_else(|| notice.split_once("退出群聊"))
+    {
+        return Some(SystemEvent::MemberRemoved {
+            chatroom_id,
+            operator: None,
+            members: resolver.members(left),
+        });
+    }
+    if let Some((operator, name)) = notice.split_once("修改群名为") {
+        return Some(SystemEvent::ChatroomRenamed {
+            chatroom_id,
+            operator: resolver.member(operator),
+            name: name
+                .trim()
+                .trim_matches(|c| matches!(c, '"' | '“' | '”'))
+                .to_string(),
+        });
+    }
+    if let Some((owner, _)) = notice.split_once("成为新群主") {
+        let owner = owner.trim_end_matches('已');
+        return Some(SystemEvent::OwnerTransferred {
+            chatroom_id,
+            new_owner: resolver.member(owner)?,
+        });
+    }
+    None
+}
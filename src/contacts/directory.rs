@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::RwLock;
+
+use crate::api::{response_data, ApiClient, Wxid};
+use crate::callback::{string_field, CallbackEvent};
+
+/// Maximum number of wxids accepted by a single `/contacts/getBriefInfo` call.
+pub const BRIEF_INFO_BATCH: usize = 100;
+
+/// Brief information of a contact (friend, chatroom or official account).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Contact {
+    pub wxid: String,
+    pub nick_name: String,
+    /// Pinyin initials of the nickname, e.g. `ZS` for `张三`.
+    pub py_initial: String,
+    pub quan_pin: String,
+    pub remark: String,
+    pub remark_py_initial: String,
+    pub remark_quan_pin: String,
+    pub alias: String,
+    pub sex: u32,
+    pub label_ids: Vec<String>,
+    pub small_head_img_url: String,
+}
+
+impl Contact {
+    /// Parses an entry of the `/contacts/getBriefInfo` response `data` (camelCase keys)
+    /// or the `Data` of a `ModContacts` callback (PascalCase keys).
+    pub fn from_value(value: &Value) -> Self {
+        let field = |keys: &[&str]| {
+            keys.iter()
+                .map(|key| string_field(value, key))
+                .find(|s| !s.is_empty())
+                .unwrap_or_default()
+        };
+        Contact {
+            wxid: field(&["userName", "UserName"]),
+            nick_name: field(&["nickName", "NickName"]),
+            py_initial: field(&["pyInitial", "PyInitial"]),
+            quan_pin: field(&["quanPin", "QuanPin"]),
+            remark: field(&["remark", "Remark"]),
+            remark_py_initial: field(&["remarkPyInitial", "RemarkPyinitial"]),
+            remark_quan_pin: field(&["remarkQuanPin", "RemarkQuanPin"]),
+            alias: field(&["alias", "Alias"]),
+            sex: field(&["sex", "Sex"]).parse().unwrap_or_default(),
+            label_ids: field(&["labelList", "LabelIdList"])
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+            small_head_img_url: field(&["smallHeadImgUrl", "SmallHeadImgUrl"]),
+        }
+    }
+
+    /// The name to show: the remark, falling back to the nickname.
+    pub fn name(&self) -> &str {
+        if self.remark.is_empty() {
+            &self.nick_name
+        } else {
+            &self.remark
+        }
+    }
+
+    pub fn is_chatroom(&self) -> bool {
+        self.wxid.ends_with("@chatroom")
+    }
+
+    /// Whether the contact matches `query` by remark, nickname, alias, wxid or pinyin.
+    ///
+    /// Matching is case-insensitive, pinyin initials and full pinyin match by prefix.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        if query.is_empty() {
+            return false;
+        }
+        [&self.remark, &self.nick_name, &self.alias, &self.wxid]
+            .iter()
+            .any(|s| s.to_lowercase().contains(&query))
+            || [
+                &self.py_initial,
+                &self.remark_py_initial,
+                &self.quan_pin,
+                &self.remark_quan_pin,
+            ]
+            .iter()
+            .any(|s| s.to_lowercase().starts_with(&query))
+    }
+}
+
+/// Local directory of contacts, synchronized from the Gewe service.
+///
+/// [`ContactDirectory::sync`] lists all contacts with
+/// [`ApiClient::fetch_contacts_list`] and fetches their brief information in
+/// batches of [`BRIEF_INFO_BATCH`]. Afterwards the directory is kept up to date
+/// by feeding it `ModContacts`/`DelContacts` callbacks with
+/// [`ContactDirectory::apply_event`].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rgewe_api::api::ApiClientBuilder;
+/// use rgewe_api::contacts::directory::ContactDirectory;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = ApiClientBuilder::new().with_token("your_token").build();
+///     let directory = ContactDirectory::new("your_app_id");
+///     directory.sync(&client, true).await.unwrap();
+///     for contact in directory.search("zs") {
+///         println!("{} ({})", contact.name(), contact.wxid);
+///     }
+/// }
+/// ```
+pub struct ContactDirectory {
+    app_id: String,
+    contacts: RwLock<HashMap<String, Contact>>,
+}
+
+impl ContactDirectory {
+    pub fn new(app_id: &str) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            contacts: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn app_id(&self) -> &str {
+        &self.app_id
+    }
+
+    /// Replaces the directory with a full listing of friends, chatrooms and official accounts.
+    ///
+    /// With `use_cache` the cached (10 mins) contact list is used, see
+    /// [`ApiClient::fetch_contacts_list_cache`]. Returns the number of contacts.
+    pub async fn sync(&self, client: &ApiClient, use_cache: bool) -> Result<usize, Box<dyn Error>> {
+        let ret = if use_cache {
+            client.fetch_contacts_list_cache(&self.app_id).await?
+        } else {
+            client.fetch_contacts_list(&self.app_id).await?
+        };
+        let data = response_data(ret)?;
+        let wxids: Vec<String> = ["friends", "chatrooms", "ghs"]
+            .iter()
+            .filter_map(|key| data.get(key).and_then(Value::as_array))
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect();
+        let contacts = self.fetch_brief(client, &wxids).await?;
+        let count = contacts.len();
+        *self.contacts.write().unwrap() =
+            contacts.into_iter().map(|c| (c.wxid.clone(), c)).collect();
+        Ok(count)
+    }
+
+    /// Fetches (and stores) the brief information of the given wxids.
+    pub async fn refresh(
+        &self,
+        client: &ApiClient,
+        wxids: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let contacts = self.fetch_brief(client, wxids).await?;
+        let mut map = self.contacts.write().unwrap();
+        for contact in contacts {
+            map.insert(contact.wxid.clone(), contact);
+        }
+        Ok(())
+    }
+
+    async fn fetch_brief(
+        &self,
+        client: &ApiClient,
+        wxids: &[String],
+    ) -> Result<Vec<Contact>, Box<dyn Error>> {
+        let mut contacts = Vec::with_capacity(wxids.len());
+        for chunk in wxids.chunks(BRIEF_INFO_BATCH) {
+            let wxids = chunk
+                .iter()
+                .map(|wxid| Wxid::try_from(wxid.as_str()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let ret = client.get_brief_list(&self.app_id, wxids).await?;
+            if let Value::Array(list) = response_data(ret)? {
+                contacts.extend(list.iter().map(Contact::from_value));
+            }
+        }
+        Ok(contacts)
+    }
+
+    /// Applies a `ModContacts`/`DelContacts` callback, ignores any other event.
+    pub fn apply_event(&self, event: &CallbackEvent) {
+        match event {
+            CallbackEvent::ContactModified { app_id, data, .. } if app_id == &self.app_id => {
+                let contact = Contact::from_value(data);
+                if !contact.wxid.is_empty() {
+                    self.insert(contact);
+                }
+            }
+            CallbackEvent::ContactDeleted {
+                app_id, user_name, ..
+            } if app_id == &self.app_id => {
+                self.contacts.write().unwrap().remove(user_name);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn insert(&self, contact: Contact) {
+        self.contacts
+            .write()
+            .unwrap()
+            .insert(contact.wxid.clone(), contact);
+    }
+
+    pub fn get(&self, wxid: &str) -> Option<Contact> {
+        self.contacts.read().unwrap().get(wxid).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.contacts.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// All contacts, sorted by name.
+    pub fn all(&self) -> Vec<Contact> {
+        self.filter(|_| true)
+    }
+
+    /// Contacts whose remark equals `remark`.
+    pub fn find_by_remark(&self, remark: &str) -> Vec<Contact> {
+        self.filter(|c| c.remark == remark)
+    }
+
+    /// Contacts whose nickname equals `nick_name`.
+    pub fn find_by_nickname(&self, nick_name: &str) -> Vec<Contact> {
+        self.filter(|c| c.nick_name == nick_name)
+    }
+
+    /// Contacts matching `query`, see [`Contact::matches`].
+    pub fn search(&self, query: &str) -> Vec<Contact> {
+        self.filter(|c| c.matches(query))
+    }
+
+    /// Contacts tagged with the label id.
+    pub fn with_label(&self, label_id: &str) -> Vec<Contact> {
+        self.filter(|c| c.label_ids.iter().any(|id| id == label_id))
+    }
+
+    /// Contacts accepted by `predicate`, sorted by name.
+    pub fn filter(&self, predicate: impl Fn(&Contact) -> bool) -> Vec<Contact> {
+        let mut contacts: Vec<Contact> = self
+            .contacts
+            .read()
+            .unwrap()
+            .values()
+            .filter(|c| predicate(c))
+            .cloned()
+            .collect();
+        contacts.sort_by(|a, b| a.name().cmp(b.name()));
+        contacts
+    }
+}
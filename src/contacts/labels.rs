@@ -0,0 +1,243 @@
+use serde_json::Value;
+use std::error::Error;
+use std::sync::RwLock;
+
+use crate::api::{response_data, ApiClient, Wxid};
+use crate::callback::string_field;
+
+/// A contact label/tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub id: String,
+    pub name: String,
+}
+
+impl Label {
+    fn from_value(value: &Value) -> Self {
+        Label {
+            id: string_field(value, "labelId"),
+            name: string_field(value, "labelName"),
+        }
+    }
+}
+
+/// Name based label management.
+///
+/// The raw label APIs work on label ids, and [`ApiClient::modify_label_members`]
+/// *resets* the label set of the given wxids. `LabelManager` resolves label names
+/// through [`ApiClient::list_labels`] (creating missing labels with
+/// [`ApiClient::add_label`]) and reads the current labels of a contact before
+/// computing the full reset, so tagging a contact never wipes its other labels.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rgewe_api::api::ApiClientBuilder;
+/// use rgewe_api::contacts::labels::LabelManager;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = ApiClientBuilder::new().with_token("your_token").build();
+///     let labels = LabelManager::new("your_app_id");
+///     labels.add_to(&client, "VIP", &["wxid_example"]).await.unwrap();
+///     labels.remove_from(&client, "Trial", &["wxid_example"]).await.unwrap();
+/// }
+/// ```
+pub struct LabelManager {
+    app_id: String,
+    labels: RwLock<Option<Vec<Label>>>,
+}
+
+impl LabelManager {
+    pub fn new(app_id: &str) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            labels: RwLock::new(None),
+        }
+    }
+
+    /// Fetches the label list, replacing the cached one.
+    pub async fn refresh(&self, client: &ApiClient) -> Result<Vec<Label>, Box<dyn Error>> {
+        let data = response_data(client.list_labels(&self.app_id).await?)?;
+        let labels: Vec<Label> = data
+            .get("labelList")
+            .and_then(Value::as_array)
+            .map(|list| list.iter().map(Label::from_value).collect())
+            .unwrap_or_default();
+        *self.labels.write().unwrap() = Some(labels.clone());
+        Ok(labels)
+    }
+
+    /// Returns the cached label list, fetching it on first use.
+    pub async fn labels(&self, client: &ApiClient) -> Result<Vec<Label>, Box<dyn Error>> {
+        if let Some(labels) = self.labels.read().unwrap().clone() {
+            return Ok(labels);
+        }
+        self.refresh(client).await
+    }
+
+    /// Resolves a label name to its id.
+    pub async fn id_of(
+        &self,
+        client: &ApiClient,
+        name: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self
+            .labels(client)
+            .await?
+            .into_iter()
+            .find(|l| l.name == name)
+            .map(|l| l.id))
+    }
+
+    /// Resolves a label id to its name.
+    pub async fn name_of(
+        &self,
+        client: &ApiClient,
+        id: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self
+            .labels(client)
+            .await?
+            .into_iter()
+            .find(|l| l.id == id)
+            .map(|l| l.name))
+    }
+
+    /// Returns the id of the label `name`, creating the label if it does not exist.
+    pub async fn ensure(&self, client: &ApiClient, name: &str) -> Result<String, Box<dyn Error>> {
+        if let Some(id) = self.id_of(client, name).await? {
+            return Ok(id);
+        }
+        let data = response_data(client.add_label(&self.app_id, name).await?)?;
+        let label = Label::from_value(&data);
+        if label.id.is_empty() {
+            return Err(format!("No label id returned when adding label: {}", name).into());
+        }
+        if let Some(labels) = self.labels.write().unwrap().as_mut() {
+            labels.push(label.clone());
+        }
+        Ok(label.id)
+    }
+
+    /// Deletes the labels with the given names, unknown names are ignored.
+    pub async fn delete(&self, client: &ApiClient, names: &[&str]) -> Result<(), Box<dyn Error>> {
+        let labels = self.labels(client).await?;
+        let ids: Vec<&str> = labels
+            .iter()
+            .filter(|l| names.contains(&l.name.as_str()))
+            .map(|l| l.id.as_str())
+            .collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+        response_data(client.delete_label(&self.app_id, &ids.join(",")).await?)?;
+        self.refresh(client).await?;
+        Ok(())
+    }
+
+    /// Returns the current label ids of a contact.
+    pub async fn label_ids_of(
+        &self,
+        client: &ApiClient,
+        wxid: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let wxids = vec![Wxid::try_from(wxid)?];
+        let data = response_data(client.get_brief_list(&self.app_id, wxids).await?)?;
+        let labels = data
+            .as_array()
+            .and_then(|list| list.first())
+            .map(|contact| string_field(contact, "labelList"))
+            .unwrap_or_default();
+        Ok(labels
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Returns the current label names of a contact.
+    pub async fn labels_of(
+        &self,
+        client: &ApiClient,
+        wxid: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let ids = self.label_ids_of(client, wxid).await?;
+        let labels = self.labels(client).await?;
+        Ok(labels
+            .into_iter()
+            .filter(|l| ids.contains(&l.id))
+            .map(|l| l.name)
+            .collect())
+    }
+
+    /// Tags the contacts with the label `name` (created if missing), keeping their other labels.
+    pub async fn add_to(
+        &self,
+        client: &ApiClient,
+        name: &str,
+        wxids: &[&str],
+    ) -> Result<(), Box<dyn Error>> {
+        let id = self.ensure(client, name).await?;
+        for wxid in wxids {
+            let mut ids = self.label_ids_of(client, wxid).await?;
+            if ids.contains(&id) {
+                continue;
+            }
+            ids.push(id.clone());
+            self.reset(client, wxid, &ids).await?;
+        }
+        Ok(())
+    }
+
+    /// Removes the label `name` from the contacts, keeping their other labels.
+    pub async fn remove_from(
+        &self,
+        client: &ApiClient,
+        name: &str,
+        wxids: &[&str],
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(id) = self.id_of(client, name).await? else {
+            return Ok(());
+        };
+        for wxid in wxids {
+            let mut ids = self.label_ids_of(client, wxid).await?;
+            if !ids.contains(&id) {
+                continue;
+            }
+            ids.retain(|l| l != &id);
+            self.reset(client, wxid, &ids).await?;
+        }
+        Ok(())
+    }
+
+    /// Replaces all labels of a contact with the given names (created if missing).
+    pub async fn set_labels(
+        &self,
+        client: &ApiClient,
+        wxid: &str,
+        names: &[&str],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut ids = Vec::with_capacity(names.len());
+        for name in names {
+            ids.push(self.ensure(client, name).await?);
+        }
+        self.reset(client, wxid, &ids).await
+    }
+
+    async fn reset(
+        &self,
+        client: &ApiClient,
+        wxid: &str,
+        label_ids: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let wxids = vec![Wxid::try_from(wxid)?];
+        response_data(
+            client
+                .modify_label_members(&self.app_id, &label_ids.join(","), wxids)
+                .await?,
+        )?;
+        Ok(())
+    }
+}
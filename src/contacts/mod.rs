@@ -0,0 +1,3 @@
+//! Contact helpers built on top of the [`ApiClient`](crate::api::ApiClient) contact APIs.
+pub mod directory;
+pub mod labels;
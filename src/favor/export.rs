@@ -0,0 +1,505 @@
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::item::{FavorData, FavorItem, Favorite};
+use super::sync::FavorSync;
+use crate::api::{response_data, ApiClient};
+use crate::callback::string_field;
+
+const DOWNLOAD_BASE_URL: &str = "http://localhost:2532/download/";
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// The default timeout of an attachment download.
+pub const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The file format of exported favorites.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// `favorite.md` per item and an `index.md`.
+    Markdown,
+    /// `favorite.json` per item and an `index.json`.
+    Json,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// An exported favorite, as recorded in the archive manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ExportedFavorite {
+    pub fav_id: i32,
+    pub title: String,
+    pub fav_type: u32,
+    pub update_time: i64,
+    /// Item file and attachments, relative to the archive directory.
+    pub files: Vec<String>,
+}
+
+/// The `manifest.json` of an archive.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Manifest {
+    /// The [`FavorSync`] key the next export resumes from.
+    #[serde(default)]
+    sync_key: String,
+    /// Favorites that failed to export, retried by the next export.
+    #[serde(default)]
+    failed: Vec<i32>,
+    #[serde(default)]
+    favorites: Vec<ExportedFavorite>,
+}
+
+/// The outcome of a [`FavorExporter::export`] run.
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    pub exported: Vec<i32>,
+    pub removed: Vec<i32>,
+    /// Favorites or attachments that failed, with the error message.
+    pub failed: Vec<(i32, String)>,
+}
+
+/// Exports the favorites into a directory archive.
+///
+/// Every favorite is written to `<dir>/<fav_id>/favorite.{md,json}` with its
+/// attachments (images, files, note media) downloaded alongside through
+/// [`ApiClient::download_cdn`]. An index of all favorites is written to
+/// `<dir>/index.{md,json}`.
+///
+/// The manifest of the archive keeps the exported items, the [`FavorSync`]
+/// key and the favorites that failed, so running the export again only writes
+/// changed favorites, retries the failed ones and removes deleted ones. The
+/// key is saved once the changes are written: an interrupted export fetches
+/// the same changes again.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rgewe_api::api::ApiClientBuilder;
+/// use rgewe_api::favor::export::{ExportFormat, FavorExporter};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = ApiClientBuilder::new().with_token("your_token").build();
+///     let exporter = FavorExporter::new("your_app_id", "favorites", ExportFormat::Markdown)
+///         .with_download_base_url("http://192.168.1.2:2532/download/");
+///     let report = exporter.export(&client).await.unwrap();
+///     println!("exported {} favorites", report.exported.len());
+/// }
+/// ```
+pub struct FavorExporter {
+    app_id: String,
+    dir: PathBuf,
+    format: ExportFormat,
+    download_base_url: String,
+    with_media: bool,
+    http: Client,
+}
+
+impl FavorExporter {
+    pub fn new(app_id: &str, dir: impl AsRef<Path>, format: ExportFormat) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            dir: dir.as_ref().to_path_buf(),
+            format,
+            download_base_url: DOWNLOAD_BASE_URL.to_string(),
+            with_media: true,
+            http: download_client(DOWNLOAD_TIMEOUT),
+        }
+    }
+
+    /// Sets the download service of the Gewe container (port 2532).
+    pub fn with_download_base_url(mut self, base_url: &str) -> Self {
+        self.download_base_url = base_url.to_string();
+        self
+    }
+
+    /// Gives up an attachment download after `timeout` instead of [`DOWNLOAD_TIMEOUT`].
+    pub fn with_download_timeout(mut self, timeout: Duration) -> Self {
+        self.http = download_client(timeout);
+        self
+    }
+
+    /// Whether to download attachments (default `true`).
+    pub fn with_media(mut self, with_media: bool) -> Self {
+        self.with_media = with_media;
+        self
+    }
+
+    /// Exports the favorites changed since the last export and rewrites the index.
+    pub async fn export(&self, client: &ApiClient) -> Result<ExportReport, Box<dyn Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        let manifest = self.load_manifest()?;
+        let retried = manifest.failed;
+        let mut exported: BTreeMap<i32, ExportedFavorite> = manifest
+            .favorites
+            .into_iter()
+            .map(|e| (e.fav_id, e))
+            .collect();
+        let changes = FavorSync::new(&self.app_id)
+            .with_sync_key(&manifest.sync_key)
+            .with_failed(&retried)
+            .changes(client)
+            .await?;
+
+        let mut report = ExportReport::default();
+        for &fav_id in &changes.deleted {
+            if exported.remove(&fav_id).is_some() {
+                let item_dir = self.dir.join(fav_id.to_string());
+                if item_dir.exists() {
+                    std::fs::remove_dir_all(item_dir)?;
+                }
+                report.removed.push(fav_id);
+            }
+        }
+        let mut failed = changes.failed.clone();
+        for &fav_id in &changes.failed {
+            report
+                .failed
+                .push((fav_id, "Failed to fetch the favorite".to_string()));
+        }
+        for fav in &changes.updated {
+            let unchanged = exported
+                .get(&fav.fav_id)
+                .is_some_and(|e| e.update_time == fav.update_time);
+            if unchanged && !retried.contains(&fav.fav_id) {
+                continue;
+            }
+            let errors = report.failed.len();
+            match self.write_item(client, fav, &mut report).await {
+                Ok(entry) => {
+                    exported.insert(fav.fav_id, entry);
+                    report.exported.push(fav.fav_id);
+                    // Missing attachments are downloaded again by the next export.
+                    if report.failed.len() > errors {
+                        failed.push(fav.fav_id);
+                    }
+                }
+                Err(e) => {
+                    report.failed.push((fav.fav_id, e.to_string()));
+                    failed.push(fav.fav_id);
+                }
+            }
+            // Save after each item, an interrupted export does not write it again.
+            self.save_manifest(&manifest.sync_key, &retried, &exported)?;
+        }
+        failed.sort_unstable();
+        failed.dedup();
+        self.save_manifest(&changes.sync_key, &failed, &exported)?;
+        self.write_index(&exported)?;
+        Ok(report)
+    }
+
+    async fn write_item(
+        &self,
+        client: &ApiClient,
+        fav: &Favorite,
+        report: &mut ExportReport,
+    ) -> Result<ExportedFavorite, Box<dyn Error>> {
+        let item_dir = self.dir.join(fav.fav_id.to_string());
+        if item_dir.exists() {
+            std::fs::remove_dir_all(&item_dir)?;
+        }
+        std::fs::create_dir_all(&item_dir)?;
+
+        let mut attachments = Vec::new();
+        if self.with_media {
+            for (index, data) in attachments_of(&fav.item).into_iter().enumerate() {
+                let name = attachment_name(index, data);
+                match self.download(client, data, &item_dir.join(&name)).await {
+                    Ok(()) => attachments.push((data, name)),
+                    Err(e) => report.failed.push((fav.fav_id, format!("{}: {}", name, e))),
+                }
+            }
+        }
+
+        let file_name = format!("favorite.{}", self.format.extension());
+        let content = match self.format {
+            ExportFormat::Markdown => render_markdown(fav, &attachments),
+            ExportFormat::Json => {
+                let mut value = serde_json::to_value(fav)?;
+                value["attachments"] = attachments
+                    .iter()
+                    .map(|(data, name)| json!({ "dataId": data.data_id, "file": name }))
+                    .collect();
+                serde_json::to_string_pretty(&value)?
+            }
+        };
+        std::fs::write(item_dir.join(&file_name), content)?;
+
+        let prefix = fav.fav_id.to_string();
+        let mut files = vec![format!("{}/{}", prefix, file_name)];
+        files.extend(
+            attachments
+                .iter()
+                .map(|(_, name)| format!("{}/{}", prefix, name)),
+        );
+        Ok(ExportedFavorite {
+            fav_id: fav.fav_id,
+            title: fav.title(),
+            fav_type: fav.fav_type,
+            update_time: fav.update_time,
+            files,
+        })
+    }
+
+    async fn download(
+        &self,
+        client: &ApiClient,
+        data: &FavorData,
+        path: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        let ret = client
+            .download_cdn(
+                &self.app_id,
+                &data.cdn_key,
+                &data.cdn_url,
+                cdn_file_type(data.data_type),
+                &data.size.to_string(),
+                &data.format,
+            )
+            .await?;
+        let file_url = string_field(&response_data(ret)?, "fileUrl");
+        if file_url.is_empty() {
+            return Err("No file url returned by downloadCdn".into());
+        }
+        let url = if file_url.starts_with("http") {
+            file_url
+        } else {
+            format!(
+                "{}/{}",
+                self.download_base_url.trim_end_matches('/'),
+                file_url.trim_start_matches('/')
+            )
+        };
+        let bytes = self
+            .http
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    fn load_manifest(&self) -> Result<Manifest, Box<dyn Error>> {
+        let path = self.dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    fn save_manifest(
+        &self,
+        sync_key: &str,
+        failed: &[i32],
+        exported: &BTreeMap<i32, ExportedFavorite>,
+    ) -> Result<(), Box<dyn Error>> {
+        let manifest = Manifest {
+            sync_key: sync_key.to_string(),
+            failed: failed.to_vec(),
+            favorites: exported.values().cloned().collect(),
+        };
+        std::fs::write(
+            self.dir.join(MANIFEST_FILE),
+            serde_json::to_vec_pretty(&manifest)?,
+        )?;
+        Ok(())
+    }
+
+    fn write_index(
+        &self,
+        manifest: &BTreeMap<i32, ExportedFavorite>,
+    ) -> Result<(), Box<dyn Error>> {
+        // Newest first.
+        let mut entries: Vec<&ExportedFavorite> = manifest.values().collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.update_time));
+        let content = match self.format {
+            ExportFormat::Markdown => {
+                let mut md = String::from(
+                    "# Favorites\n\n| Updated | Type | Title |\n| --- | --- | --- |\n",
+                );
+                for e in entries {
+                    let _ = writeln!(
+                        md,
+                        "| {} | {} | [{}]({}) |",
+                        format_time(e.update_time),
+                        type_name(e.fav_type),
+                        e.title.replace('|', "\\|"),
+                        e.files.first().map(String::as_str).unwrap_or_default()
+                    );
+                }
+                md
+            }
+            ExportFormat::Json => serde_json::to_string_pretty(&entries)?,
+        };
+        std::fs::write(
+            self.dir.join(format!("index.{}", self.format.extension())),
+            content,
+        )?;
+        Ok(())
+    }
+}
+
+/// A client for the download service of the Gewe container, without proxy
+/// like [`HttpTransport`](crate::api::transport::HttpTransport).
+fn download_client(timeout: Duration) -> Client {
+    Client::builder()
+        .no_proxy()
+        .timeout(timeout)
+        .build()
+        .unwrap()
+}
+
+fn attachments_of(item: &FavorItem) -> Vec<&FavorData> {
+    let downloadable = |d: &&FavorData| !d.cdn_url.is_empty() && !d.cdn_key.is_empty();
+    match item {
+        FavorItem::Image { data }
+        | FavorItem::Voice { data }
+        | FavorItem::Video { data }
+        | FavorItem::File { data, .. } => [data].into_iter().filter(downloadable).collect(),
+        FavorItem::Note { data, .. } => data.iter().filter(downloadable).collect(),
+        FavorItem::ChatHistory { records, .. } => records.iter().filter(downloadable).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The file name of the `index`th attachment, prefixed with its position to
+/// be unique within the favorite.
+fn attachment_name(index: usize, data: &FavorData) -> String {
+    let title = if data.title.is_empty() || data.title.contains(['/', '\\']) {
+        "attachment"
+    } else {
+        &data.title
+    };
+    let name = format!("{}_{}", index + 1, title);
+    if data.format.is_empty() || name.ends_with(&format!(".{}", data.format)) {
+        name
+    } else {
+        format!("{}.{}", name, data.format)
+    }
+}
+
+/// Maps the favorite data type to the `type` of `/message/downloadCdn`.
+fn cdn_file_type(data_type: u32) -> &'static str {
+    match data_type {
+        2 => "2",
+        4 => "4",
+        _ => "5",
+    }
+}
+
+fn type_name(fav_type: u32) -> &'static str {
+    match fav_type {
+        1 => "text",
+        2 => "image",
+        3 => "voice",
+        4 => "video",
+        5 => "link",
+        6 => "location",
+        8 => "file",
+        14 => "chat history",
+        18 => "note",
+        _ => "other",
+    }
+}
+
+fn render_markdown(fav: &Favorite, attachments: &[(&FavorData, String)]) -> String {
+    let mut md = format!("# {}\n\n", fav.title());
+    let _ = writeln!(md, "- Favorite id: {}", fav.fav_id);
+    let _ = writeln!(md, "- Type: {}", type_name(fav.fav_type));
+    let _ = writeln!(md, "- Updated: {}", format_time(fav.update_time));
+    if !fav.from_user.is_empty() {
+        let _ = writeln!(md, "- From: {}", fav.from_user);
+    }
+    md.push('\n');
+    let link = |data: &FavorData| {
+        attachments
+            .iter()
+            .find(|(downloaded, _)| std::ptr::eq(*downloaded, data))
+            .map(|(_, name)| name.clone())
+    };
+    match &fav.item {
+        FavorItem::Text { text } => md.push_str(text),
+        FavorItem::Link {
+            title, desc, url, ..
+        } => {
+            let _ = writeln!(md, "[{}]({})\n\n{}", title, url, desc);
+        }
+        FavorItem::Image { data } => {
+            if let Some(file) = link(data) {
+                let _ = writeln!(md, "![image]({})", file);
+            }
+        }
+        FavorItem::Voice { data } | FavorItem::Video { data } | FavorItem::File { data, .. } => {
+            if let Some(file) = link(data) {
+                let _ = writeln!(md, "[{}]({})", file, file);
+            }
+        }
+        FavorItem::Location { label, lat, lng } => {
+            let _ = writeln!(md, "{} ({}, {})", label, lat, lng);
+        }
+        FavorItem::Note { text, data, .. } => {
+            let _ = writeln!(md, "{}\n", text);
+            for file in data.iter().filter_map(link) {
+                let _ = writeln!(md, "- [{}]({})", file, file);
+            }
+        }
+        FavorItem::ChatHistory { desc, records, .. } => {
+            let _ = writeln!(md, "{}\n", desc);
+            for record in records {
+                let _ = write!(
+                    md,
+                    "- **{}** {}: {}",
+                    record.source_name, record.source_time, record.desc
+                );
+                if let Some(file) = link(record) {
+                    let _ = write!(md, " [{}]({})", file, file);
+                }
+                md.push('\n');
+            }
+        }
+        FavorItem::Other { .. } => {
+            let _ = writeln!(md, "```xml\n{}\n```", fav.xml);
+        }
+    }
+    md
+}
+
+/// Formats a unix timestamp as `YYYY-MM-DD HH:MM:SS` (UTC).
+fn format_time(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86400);
+    let secs = timestamp.rem_euclid(86400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
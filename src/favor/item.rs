@@ -0,0 +1,230 @@
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::callback::{int_field, string_field};
+use crate::xml;
+
+/// A data item (`<dataitem>`) of a favorite: the image, file, link target or chat record it holds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FavorData {
+    pub data_type: u32,
+    pub data_id: String,
+    pub title: String,
+    pub desc: String,
+    /// CDN url of the data, used together with `cdn_key` to download it.
+    pub cdn_url: String,
+    pub cdn_key: String,
+    pub cdn_thumb_url: String,
+    pub cdn_thumb_key: String,
+    pub full_md5: String,
+    /// File format (extension), e.g. `pdf`.
+    pub format: String,
+    pub size: u64,
+    /// Source wxid of a chat record item.
+    pub source_name: String,
+    pub source_time: String,
+}
+
+impl FavorData {
+    fn from_node(node: Node) -> Self {
+        let text = |tag: &str| {
+            node.children()
+                .find(|n| n.has_tag_name(tag))
+                .map(|n| xml::node_text(n).trim().to_string())
+                .unwrap_or_default()
+        };
+        FavorData {
+            data_type: node
+                .attribute("datatype")
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_default(),
+            data_id: node.attribute("dataid").unwrap_or_default().to_string(),
+            title: text("datatitle"),
+            desc: text("datadesc"),
+            cdn_url: text("cdn_dataurl"),
+            cdn_key: text("cdn_datakey"),
+            cdn_thumb_url: text("cdn_thumburl"),
+            cdn_thumb_key: text("cdn_thumbkey"),
+            full_md5: text("fullmd5"),
+            format: text("datafmt"),
+            size: text("datasize").parse().unwrap_or_default(),
+            source_name: text("sourcename"),
+            source_time: text("sourcetime"),
+        }
+    }
+}
+
+/// The typed content of a favorite (`<favitem>`).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FavorItem {
+    Text {
+        text: String,
+    },
+    Link {
+        title: String,
+        desc: String,
+        url: String,
+        thumb_url: String,
+    },
+    Image {
+        data: FavorData,
+    },
+    Voice {
+        data: FavorData,
+    },
+    Video {
+        data: FavorData,
+    },
+    File {
+        title: String,
+        data: FavorData,
+    },
+    Location {
+        label: String,
+        lat: String,
+        lng: String,
+    },
+    /// A note, text and attachments are stored as data items.
+    Note {
+        title: String,
+        text: String,
+        data: Vec<FavorData>,
+    },
+    /// A favorited chat history, one data item per record.
+    ChatHistory {
+        title: String,
+        desc: String,
+        records: Vec<FavorData>,
+    },
+    Other {
+        fav_type: u32,
+    },
+}
+
+impl FavorItem {
+    /// Parses the `<favitem>` XML returned by `/favor/getContent`.
+    pub fn parse(content: &str) -> Option<FavorItem> {
+        let doc = xml::parse(content)?;
+        let favitem = xml::find(doc.root(), "favitem")?;
+        let fav_type: u32 = favitem.attribute("type")?.parse().ok()?;
+        let text = |tag: &str| xml::text(favitem, tag).unwrap_or_default();
+        let data: Vec<FavorData> = xml::find(favitem, "datalist")
+            .map(|list| {
+                list.children()
+                    .filter(|n| n.has_tag_name("dataitem"))
+                    .map(FavorData::from_node)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let first = data.first().cloned().unwrap_or_default();
+        let item = match fav_type {
+            1 => FavorItem::Text { text: text("desc") },
+            2 => FavorItem::Image { data: first },
+            3 => FavorItem::Voice { data: first },
+            4 => FavorItem::Video { data: first },
+            5 => FavorItem::Link {
+                title: xml::text(favitem, "pagetitle").unwrap_or_else(|| first.title.clone()),
+                desc: xml::text(favitem, "pagedesc").unwrap_or_else(|| first.desc.clone()),
+                url: xml::find(favitem, "source")
+                    .and_then(|source| xml::text(source, "link"))
+                    .or_else(|| xml::text(favitem, "clean_url"))
+                    .unwrap_or_default(),
+                thumb_url: xml::text(favitem, "pagethumb_url")
+                    .unwrap_or_else(|| first.cdn_thumb_url.clone()),
+            },
+            6 => {
+                let location = xml::find(favitem, "locitem");
+                let field =
+                    |tag: &str| location.and_then(|l| xml::text(l, tag)).unwrap_or_default();
+                FavorItem::Location {
+                    label: field("label"),
+                    lat: field("lat"),
+                    lng: field("lng"),
+                }
+            }
+            8 => FavorItem::File {
+                title: if first.title.is_empty() {
+                    text("title")
+                } else {
+                    first.title.clone()
+                },
+                data: first,
+            },
+            14 => FavorItem::ChatHistory {
+                title: text("title"),
+                desc: text("desc"),
+                records: data,
+            },
+            18 => FavorItem::Note {
+                title: text("title"),
+                text: text("desc"),
+                data,
+            },
+            other => FavorItem::Other { fav_type: other },
+        };
+        Some(item)
+    }
+}
+
+/// A favorite with its metadata and parsed content.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Favorite {
+    pub fav_id: i32,
+    pub fav_type: u32,
+    pub update_time: i64,
+    /// The wxid the favorited content came from, if any.
+    pub from_user: String,
+    pub item: FavorItem,
+    /// The raw `<favitem>` XML.
+    pub xml: String,
+}
+
+impl Favorite {
+    /// Builds a favorite from the `data` of a `/favor/getContent` response.
+    pub fn from_data(data: &Value) -> Option<Favorite> {
+        let xml = string_field(data, "content");
+        let item = FavorItem::parse(&xml)?;
+        let from_user = xml::parse(&xml)
+            .and_then(|doc| xml::text(doc.root(), "fromusr"))
+            .unwrap_or_default();
+        let fav_type = xml::parse(&xml)
+            .and_then(|doc| {
+                xml::find(doc.root(), "favitem")
+                    .and_then(|n| n.attribute("type"))
+                    .and_then(|t| t.parse().ok())
+            })
+            .unwrap_or_default();
+        Some(Favorite {
+            fav_id: int_field(data, "favId") as i32,
+            fav_type,
+            update_time: int_field(data, "updateTime"),
+            from_user,
+            item,
+            xml,
+        })
+    }
+
+    /// A short title for listings: the link/file/note title or the beginning of the text.
+    pub fn title(&self) -> String {
+        let title = match &self.item {
+            FavorItem::Text { text } => text.clone(),
+            FavorItem::Link { title, .. }
+            | FavorItem::File { title, .. }
+            | FavorItem::Note { title, .. }
+            | FavorItem::ChatHistory { title, .. } => title.clone(),
+            FavorItem::Location { label, .. } => label.clone(),
+            FavorItem::Image { .. } => "Image".to_string(),
+            FavorItem::Voice { .. } => "Voice".to_string(),
+            FavorItem::Video { .. } => "Video".to_string(),
+            FavorItem::Other { fav_type } => format!("Favorite type {}", fav_type),
+        };
+        let title = title.lines().next().unwrap_or_default().trim().to_string();
+        if title.is_empty() {
+            format!("Favorite {}", self.fav_id)
+        } else {
+            title.chars().take(64).collect()
+        }
+    }
+}
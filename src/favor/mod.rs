@@ -0,0 +1,4 @@
+//! Favorites (收藏) helpers built on top of the [`ApiClient`](crate::api::ApiClient) favor APIs.
+pub mod export;
+pub mod item;
+pub mod sync;
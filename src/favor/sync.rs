@@ -0,0 +1,192 @@
+use serde_json::{json, Value};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::item::Favorite;
+use crate::api::{response_data, ApiClient};
+use crate::callback::{int_field, string_field};
+
+/// The outcome of a [`FavorSync::sync`] run.
+#[derive(Debug, Clone, Default)]
+pub struct FavorSyncResult {
+    /// New or updated favorites, with their content fetched.
+    pub updated: Vec<Favorite>,
+    /// Ids of favorites deleted since the last sync.
+    pub deleted: Vec<i32>,
+    /// Ids whose content could not be fetched or parsed, fetched again by the next sync.
+    pub failed: Vec<i32>,
+    /// The sync key after these changes, see [`FavorSync::commit`].
+    pub sync_key: String,
+}
+
+/// Incremental synchronization of the favorites (收藏).
+///
+/// `/favor/sync` returns the favorites changed since the given sync key, page by
+/// page, together with the key to use for the next page. `FavorSync` pages until
+/// no more changes are returned and fetches the content of every changed
+/// favorite with [`ApiClient::get_favor_content`].
+///
+/// The sync key is kept between runs, so the next [`FavorSync::sync`] only
+/// returns new changes. Favorites whose content failed to fetch are kept with
+/// it and fetched again by the next run. With [`FavorSync::with_state_file`]
+/// both are also persisted to resume after a restart.
+///
+/// [`FavorSync::changes`] and [`FavorSync::commit`] split a sync, to keep the
+/// key until the changes are stored.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rgewe_api::api::ApiClientBuilder;
+/// use rgewe_api::favor::sync::FavorSync;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = ApiClientBuilder::new().with_token("your_token").build();
+///     let favor = FavorSync::new("your_app_id")
+///         .with_state_file("favor_state.json")
+///         .unwrap();
+///     let result = favor.sync(&client).await.unwrap();
+///     for fav in result.updated {
+///         println!("{}: {}", fav.fav_id, fav.title());
+///     }
+/// }
+/// ```
+pub struct FavorSync {
+    app_id: String,
+    sync_key: Mutex<String>,
+    /// Ids that failed to fetch, retried by the next sync.
+    pending: Mutex<Vec<i32>>,
+    state_file: Option<PathBuf>,
+}
+
+impl FavorSync {
+    pub fn new(app_id: &str) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            sync_key: Mutex::new(String::new()),
+            pending: Mutex::new(Vec::new()),
+            state_file: None,
+        }
+    }
+
+    /// Resumes from a sync key returned by an earlier run.
+    pub fn with_sync_key(self, sync_key: &str) -> Self {
+        *self.sync_key.lock().unwrap() = sync_key.to_string();
+        self
+    }
+
+    /// Fetches again the favorites that failed in an earlier run.
+    pub fn with_failed(self, fav_ids: &[i32]) -> Self {
+        *self.pending.lock().unwrap() = fav_ids.to_vec();
+        self
+    }
+
+    /// Persists the sync key and the failed ids to `path`, resuming from the
+    /// stored state if the file exists.
+    pub fn with_state_file(mut self, path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            let state: Value = serde_json::from_slice(&std::fs::read(&path)?)?;
+            *self.sync_key.lock().unwrap() = string_field(&state, "syncKey");
+            *self.pending.lock().unwrap() = state
+                .get("failed")
+                .and_then(Value::as_array)
+                .map(|ids| {
+                    ids.iter()
+                        .filter_map(Value::as_i64)
+                        .map(|id| id as i32)
+                        .collect()
+                })
+                .unwrap_or_default();
+        }
+        self.state_file = Some(path);
+        Ok(self)
+    }
+
+    /// The current sync key, empty before the first sync.
+    pub fn sync_key(&self) -> String {
+        self.sync_key.lock().unwrap().clone()
+    }
+
+    /// The favorites that failed to fetch in the last sync, retried by the next one.
+    pub fn pending(&self) -> Vec<i32> {
+        self.pending.lock().unwrap().clone()
+    }
+
+    /// Pages through `/favor/sync` until exhausted, fetches the changed
+    /// favorites and keeps the new sync key and the failed ids.
+    pub async fn sync(&self, client: &ApiClient) -> Result<FavorSyncResult, Box<dyn Error>> {
+        let result = self.changes(client).await?;
+        self.commit(&result)?;
+        Ok(result)
+    }
+
+    /// Like [`FavorSync::sync`], without keeping the new state: the same
+    /// changes are returned again until they are [committed](FavorSync::commit).
+    ///
+    /// The favorites that failed in the last sync are fetched again.
+    pub async fn changes(&self, client: &ApiClient) -> Result<FavorSyncResult, Box<dyn Error>> {
+        let mut result = FavorSyncResult {
+            sync_key: self.sync_key(),
+            ..FavorSyncResult::default()
+        };
+        loop {
+            let key = result.sync_key.clone();
+            let data = response_data(client.sync_favor(&self.app_id, &key).await?)?;
+            let entries = data
+                .get("list")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            for entry in &entries {
+                let fav_id = int_field(entry, "favId") as i32;
+                if int_field(entry, "flag") == 1 {
+                    result.deleted.push(fav_id);
+                    continue;
+                }
+                self.fetch_into(client, fav_id, &mut result).await;
+            }
+            let next_key = string_field(&data, "syncKey");
+            if entries.is_empty() || next_key.is_empty() || next_key == key {
+                break;
+            }
+            result.sync_key = next_key;
+        }
+        for fav_id in self.pending() {
+            let seen = result.deleted.contains(&fav_id)
+                || result.failed.contains(&fav_id)
+                || result.updated.iter().any(|fav| fav.fav_id == fav_id);
+            if !seen {
+                self.fetch_into(client, fav_id, &mut result).await;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Keeps the sync key and the failed ids of `result`, returned by [`FavorSync::changes`].
+    pub fn commit(&self, result: &FavorSyncResult) -> Result<(), Box<dyn Error>> {
+        *self.sync_key.lock().unwrap() = result.sync_key.clone();
+        *self.pending.lock().unwrap() = result.failed.clone();
+        if let Some(path) = &self.state_file {
+            let state = json!({ "syncKey": result.sync_key, "failed": result.failed });
+            std::fs::write(path, serde_json::to_vec(&state)?)?;
+        }
+        Ok(())
+    }
+
+    /// Fetches and parses the content of a single favorite.
+    pub async fn fetch(&self, client: &ApiClient, fav_id: i32) -> Result<Favorite, Box<dyn Error>> {
+        let data = response_data(client.get_favor_content(&self.app_id, fav_id).await?)?;
+        Favorite::from_data(&data)
+            .ok_or_else(|| format!("Failed to parse the content of favorite {}", fav_id).into())
+    }
+
+    async fn fetch_into(&self, client: &ApiClient, fav_id: i32, result: &mut FavorSyncResult) {
+        match self.fetch(client, fav_id).await {
+            Ok(fav) => result.updated.push(fav),
+            Err(_) => result.failed.push(fav_id),
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::api::{response_data, ApiClient};
+use crate::callback::system::{Member, SystemEvent};
+use crate::callback::CallbackEvent;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// A member entry of `/group/getChatroomMemberList`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatroomMember {
+    pub wxid: String,
+    #[serde(default)]
+    pub nick_name: String,
+    /// The nickname set for this chatroom, if any.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default, rename = "inviterUserName")]
+    pub inviter: Option<String>,
+    #[serde(default)]
+    pub big_head_img_url: Option<String>,
+    #[serde(default)]
+    pub small_head_img_url: Option<String>,
+}
+
+impl ChatroomMember {
+    /// The name shown in the chatroom: the chatroom nickname, falling back to the nickname.
+    pub fn name(&self) -> &str {
+        match self.display_name.as_deref() {
+            Some(name) if !name.is_empty() => name,
+            _ => &self.nick_name,
+        }
+    }
+}
+
+/// The cached membership of a chatroom.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatroomMembers {
+    pub chatroom_id: String,
+    pub owner: Option<String>,
+    pub admins: Vec<String>,
+    pub members: Vec<ChatroomMember>,
+    /// Unix timestamp (secs) of the last full fetch.
+    pub fetched_at: u64,
+}
+
+impl ChatroomMembers {
+    /// Parses the `data` of a `/group/getChatroomMemberList` response.
+    pub fn from_data(chatroom_id: &str, data: &Value) -> Result<Self, Box<dyn Error>> {
+        let members = serde_json::from_value(
+            data.get("memberList")
+                .cloned()
+                .unwrap_or(Value::Array(Vec::new())),
+        )?;
+        let admins = match data.get("adminWxid") {
+            Some(Value::Array(list)) => list
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            Some(Value::String(s)) => s
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            chatroom_id: chatroom_id.to_string(),
+            owner: data
+                .get("chatroomOwner")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            admins,
+            members,
+            fetched_at: now(),
+        })
+    }
+
+    pub fn get(&self, wxid: &str) -> Option<&ChatroomMember> {
+        self.members.iter().find(|m| m.wxid == wxid)
+    }
+}
+
+/// The outcome of [`MemberCache::warm_up`].
+#[derive(Debug, Clone, Default)]
+pub struct WarmUpReport {
+    pub cached: Vec<String>,
+    /// Chatrooms that failed to fetch, with the error message.
+    pub failed: Vec<(String, String)>,
+}
+
+/// In-memory cache of chatroom memberships, keyed by chatroom id.
+///
+/// Entries are fetched lazily with [`ApiClient::get_chatroom_member_list`] and
+/// refetched once older than the TTL (30 mins by default). Join/leave events
+/// patch the cached entries through [`MemberCache::apply_event`], so the
+/// expensive member list is rarely refetched.
+///
+/// With [`MemberCache::with_persistence`] the cache is loaded from and saved
+/// (on [`MemberCache::save`]) to a JSON file.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rgewe_api::api::ApiClientBuilder;
+/// use rgewe_api::group::member_cache::MemberCache;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = ApiClientBuilder::new().with_token("your_token").build();
+///     let cache = MemberCache::new("your_app_id");
+///     cache.warm_up(&client).await.unwrap();
+///     let name = cache
+///         .display_name(&client, "123@chatroom", "wxid_example")
+///         .await
+///         .unwrap();
+///     println!("{}", name);
+/// }
+/// ```
+pub struct MemberCache {
+    app_id: String,
+    ttl: Duration,
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<String, ChatroomMembers>>,
+}
+
+impl MemberCache {
+    pub fn new(app_id: &str) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            ttl: DEFAULT_TTL,
+            path: None,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Persists the cache to `path`, loading the existing entries if the file exists.
+    pub fn with_persistence(mut self, path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            let entries = serde_json::from_slice(&std::fs::read(&path)?)?;
+            self.entries = Mutex::new(entries);
+        }
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Writes the cache to the persistence file, if configured.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec(&*self.entries.lock().unwrap())?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Returns the cached membership of `chatroom_id` without fetching, even if expired.
+    pub fn cached(&self, chatroom_id: &str) -> Option<ChatroomMembers> {
+        self.entries.lock().unwrap().get(chatroom_id).cloned()
+    }
+
+    /// Returns the membership of `chatroom_id`, fetching it if missing or expired.
+    pub async fn members(
+        &self,
+        client: &ApiClient,
+        chatroom_id: &str,
+    ) -> Result<ChatroomMembers, Box<dyn Error>> {
+        if let Some(entry) = self.cached(chatroom_id) {
+            if now().saturating_sub(entry.fetched_at) < self.ttl.as_secs() {
+                return Ok(entry);
+            }
+        }
+        self.refresh(client, chatroom_id).await
+    }
+
+    /// Fetches the membership of `chatroom_id` and replaces the cached entry.
+    pub async fn refresh(
+        &self,
+        client: &ApiClient,
+        chatroom_id: &str,
+    ) -> Result<ChatroomMembers, Box<dyn Error>> {
+        let ret = client
+            .get_chatroom_member_list(&self.app_id, chatroom_id)
+            .await?;
+        let entry = ChatroomMembers::from_data(chatroom_id, &response_data(ret)?)?;
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(chatroom_id.to_string(), entry.clone());
+        Ok(entry)
+    }
+
+    /// Returns a single member, refetching the chatroom once if the member is unknown.
+    pub async fn member(
+        &self,
+        client: &ApiClient,
+        chatroom_id: &str,
+        wxid: &str,
+    ) -> Result<Option<ChatroomMember>, Box<dyn Error>> {
+        let entry = self.members(client, chatroom_id).await?;
+        if let Some(member) = entry.get(wxid) {
+            return Ok(Some(member.clone()));
+        }
+        let entry = self.refresh(client, chatroom_id).await?;
+        Ok(entry.get(wxid).cloned())
+    }
+
+    /// Returns the name of `wxid` shown in the chatroom, falling back to the wxid itself.
+    pub async fn display_name(
+        &self,
+        client: &ApiClient,
+        chatroom_id: &str,
+        wxid: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        Ok(self
+            .member(client, chatroom_id, wxid)
+            .await?
+            .map(|m| m.name().to_string())
+            .unwrap_or_else(|| wxid.to_string()))
+    }
+
+    /// Drops the cached entry of `chatroom_id`.
+    pub fn invalidate(&self, chatroom_id: &str) {
+        self.entries.lock().unwrap().remove(chatroom_id);
+    }
+
+    /// Fetches every chatroom returned by [`ApiClient::fetch_contacts_list`].
+    ///
+    /// A chatroom failing to fetch does not stop the others, it is reported
+    /// in [`WarmUpReport::failed`].
+    pub async fn warm_up(&self, client: &ApiClient) -> Result<WarmUpReport, Box<dyn Error>> {
+        let data = response_data(client.fetch_contacts_list(&self.app_id).await?)?;
+        let chatrooms: Vec<String> = data
+            .get("chatrooms")
+            .and_then(Value::as_array)
+            .map(|list| {
+                list.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut report = WarmUpReport::default();
+        for chatroom_id in chatrooms {
+            match self.refresh(client, &chatroom_id).await {
+                Ok(_) => report.cached.push(chatroom_id),
+                Err(e) => report.failed.push((chatroom_id, e.to_string())),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Patches the cache with a callback event.
+    ///
+    /// Joined members with a known wxid are added, removed members are dropped
+    /// and ownership changes are recorded. Events that cannot be applied exactly
+    /// (e.g. plain text notices without wxids) invalidate the chatroom instead.
+    pub fn apply_event(&self, event: &CallbackEvent) {
+        match event {
+            CallbackEvent::Message(msg) => {
+                if let Some(event) = SystemEvent::parse(msg) {
+                    self.apply(&event);
+                }
+            }
+            CallbackEvent::ContactDeleted { user_name, .. } => self.invalidate(user_name),
+            _ => {}
+        }
+    }
+
+    /// Patches the cache with a chatroom system event.
+    pub fn apply(&self, event: &SystemEvent) {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get_mut(event.chatroom_id()) else {
+            return;
+        };
+        let exact = match event {
+            SystemEvent::MemberJoined {
+                inviter, members, ..
+            } => members.iter().all(|m| {
+                let Some(wxid) = &m.wxid else {
+                    return false;
+                };
+                if entry.get(wxid).is_none() {
+                    entry.members.push(ChatroomMember {
+                        wxid: wxid.clone(),
+                        nick_name: m.nickname.clone(),
+                        display_name: None,
+                        inviter: inviter.as_ref().and_then(|i| i.wxid.clone()),
+                        big_head_img_url: None,
+                        small_head_img_url: None,
+                    });
+                }
+                true
+            }),
+            SystemEvent::MemberRemoved { members, .. } => members.iter().all(|m| {
+                let before = entry.members.len();
+                entry.members.retain(|cached| !is_member(cached, m));
+                entry.members.len() < before
+            }),
+            SystemEvent::OwnerTransferred { new_owner, .. } => match &new_owner.wxid {
+                Some(wxid) => {
+                    entry.owner = Some(wxid.clone());
+                    true
+                }
+                None => false,
+            },
+            SystemEvent::ChatroomRenamed { .. } | SystemEvent::AnnouncementChanged { .. } => true,
+        };
+        if !exact {
+            entries.remove(event.chatroom_id());
+        }
+    }
+}
+
+fn is_member(cached: &ChatroomMember, member: &Member) -> bool {
+    match &member.wxid {
+        Some(wxid) => &cached.wxid == wxid,
+        None => cached.name() == member.nickname || cached.nick_name == member.nickname,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,5 @@
+//! Chatroom/group helpers built on top of the [`ApiClient`](crate::api::ApiClient) group APIs
+//! and the chatroom [`SystemEvent`](crate::callback::system::SystemEvent)s.
+pub mod member_cache;
+pub mod moderation;
+pub mod welcome;
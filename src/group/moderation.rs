@@ -0,0 +1,427 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::api::{response_data, AdminOperationType, ApiClient, Wxid};
+use crate::callback::{Message, MessageType};
+
+/// Inspects an image message, e.g. by downloading and decoding it.
+pub type ImageInspector = Arc<dyn Fn(&Message) -> bool + Send + Sync>;
+
+/// A condition a chatroom message is checked against.
+#[derive(Clone)]
+pub enum Rule {
+    /// The text contains any of the keywords (case-insensitive).
+    BannedKeywords(Vec<String>),
+    /// The text matches the regular expression.
+    BannedPattern(Regex),
+    /// The text contains a URL, or the message is a shared link.
+    Links,
+    /// The message is a shared mini-program.
+    MiniProgram,
+    /// The sender posted more than `max_messages` within `window`.
+    Flooding {
+        max_messages: usize,
+        window: Duration,
+    },
+    /// The message is an image the inspector recognises as a QR code.
+    ///
+    /// Decoding images is left to the caller, e.g. by downloading the image
+    /// and running it through a QR code decoder.
+    QrCodeImage(ImageInspector),
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::BannedKeywords(keywords) => {
+                f.debug_tuple("BannedKeywords").field(keywords).finish()
+            }
+            Rule::BannedPattern(re) => f.debug_tuple("BannedPattern").field(&re.as_str()).finish(),
+            Rule::Links => write!(f, "Links"),
+            Rule::MiniProgram => write!(f, "MiniProgram"),
+            Rule::Flooding {
+                max_messages,
+                window,
+            } => f
+                .debug_struct("Flooding")
+                .field("max_messages", max_messages)
+                .field("window", window)
+                .finish(),
+            Rule::QrCodeImage(_) => write!(f, "QrCodeImage"),
+        }
+    }
+}
+
+/// An action performed when a rule is violated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Post a warning that `@`-mentions the sender.
+    ///
+    /// The text supports the `{sender}` and `{rule}` placeholders.
+    Warn(String),
+    /// Revoke the message, only possible for messages sent by the account itself
+    /// (e.g. content relayed by another bot running on this account).
+    Revoke,
+    /// Remove the sender from the chatroom.
+    RemoveMember,
+}
+
+/// A named rule together with the actions to perform on violation.
+#[derive(Debug, Clone)]
+pub struct ModerationRule {
+    pub name: String,
+    pub rule: Rule,
+    pub actions: Vec<Action>,
+}
+
+impl ModerationRule {
+    pub fn new(name: &str, rule: Rule, actions: Vec<Action>) -> Self {
+        Self {
+            name: name.to_string(),
+            rule,
+            actions,
+        }
+    }
+}
+
+/// How many entries [`Moderator::audit_log`] keeps, the oldest are dropped first.
+pub const AUDIT_LOG_CAPACITY: usize = 1000;
+
+/// Outcome of a single action, recorded in the audit log.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub time: SystemTime,
+    pub chatroom_id: String,
+    pub sender: String,
+    pub new_msg_id: i64,
+    pub rule: String,
+    pub action: Action,
+    /// `Err` holds the error message of a failed or skipped action.
+    pub result: Result<(), String>,
+}
+
+/// Watches chatroom messages and enforces [`ModerationRule`]s.
+///
+/// Messages from exempted wxids (e.g. admins) are never moderated.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rgewe_api::api::ApiClientBuilder;
+/// use rgewe_api::callback::CallbackEvent;
+/// use rgewe_api::group::moderation::{Action, ModerationRule, Moderator, Rule};
+///
+/// async fn on_callback(payload: &serde_json::Value) {
+///     let client = ApiClientBuilder::new().with_token("your_token").build();
+///     let moderator = Moderator::new("your_app_id")
+///         .with_rule(ModerationRule::new(
+///             "no-ads",
+///             Rule::BannedKeywords(vec!["代购".to_string()]),
+///             vec![Action::Warn("@{sender} 请勿发广告".to_string())],
+///         ))
+///         .with_rule(ModerationRule::new("no-links", Rule::Links, vec![Action::RemoveMember]));
+///     if let Ok(CallbackEvent::Message(msg)) = CallbackEvent::try_from(payload) {
+///         let entries = moderator.handle(&client, &msg).await;
+///         println!("{:#?}", entries);
+///     }
+/// }
+/// ```
+pub struct Moderator {
+    app_id: String,
+    rules: Vec<ModerationRule>,
+    chatrooms: Option<HashSet<String>>,
+    exempt: HashSet<String>,
+    history: Mutex<HashMap<(String, String), VecDeque<i64>>>,
+    audit_log: Mutex<VecDeque<AuditEntry>>,
+}
+
+impl Moderator {
+    pub fn new(app_id: &str) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            rules: Vec::new(),
+            chatrooms: None,
+            exempt: HashSet::new(),
+            history: Mutex::new(HashMap::new()),
+            audit_log: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn with_rule(mut self, rule: ModerationRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Restricts moderation to the given chatrooms (all chatrooms by default).
+    pub fn with_chatrooms(mut self, chatroom_ids: &[&str]) -> Self {
+        self.chatrooms = Some(chatroom_ids.iter().map(|id| id.to_string()).collect());
+        self
+    }
+
+    /// Never moderates messages sent by `wxid`.
+    pub fn with_exempt(mut self, wxid: &str) -> Self {
+        self.exempt.insert(wxid.to_string());
+        self
+    }
+
+    /// Returns a copy of the last [`AUDIT_LOG_CAPACITY`] audit entries.
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.audit_log.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Removes and returns the recorded audit entries.
+    pub fn take_audit_log(&self) -> Vec<AuditEntry> {
+        self.audit_log.lock().unwrap().drain(..).collect()
+    }
+
+    /// Returns the rules `msg` violates, without performing any action.
+    ///
+    /// The message is not added to the flood history, it is counted as the
+    /// next message of its sender.
+    pub fn check(&self, msg: &Message) -> Vec<&ModerationRule> {
+        self.violations(msg, false)
+    }
+
+    /// Adds `msg` to the flood history and returns the rules it violates,
+    /// without performing any action. [`Moderator::handle`] observes every message.
+    pub fn observe(&self, msg: &Message) -> Vec<&ModerationRule> {
+        self.violations(msg, true)
+    }
+
+    fn violations(&self, msg: &Message, record: bool) -> Vec<&ModerationRule> {
+        let Some(chatroom_id) = msg.chatroom_id() else {
+            return Vec::new();
+        };
+        if self.exempt.contains(msg.sender())
+            || self
+                .chatrooms
+                .as_ref()
+                .is_some_and(|rooms| !rooms.contains(chatroom_id))
+        {
+            return Vec::new();
+        }
+        let flood_count = self.flood_count(chatroom_id, msg, record);
+        self.rules
+            .iter()
+            .filter(|r| violates(&r.rule, msg, &flood_count))
+            .collect()
+    }
+
+    /// Checks `msg` against all rules and performs the actions of every violated rule.
+    ///
+    /// Returns the audit entries of the performed actions, they are also appended to
+    /// [`Moderator::audit_log`]. A failing action does not stop the remaining ones.
+    pub async fn handle(&self, client: &ApiClient, msg: &Message) -> Vec<AuditEntry> {
+        let mut entries = Vec::new();
+        for rule in self.observe(msg) {
+            for action in &rule.actions {
+                let result = self
+                    .perform(client, msg, rule, action)
+                    .await
+                    .map_err(|e| e.to_string());
+                entries.push(AuditEntry {
+                    time: SystemTime::now(),
+                    chatroom_id: msg.chatroom_id().unwrap_or_default().to_string(),
+                    sender: msg.sender().to_string(),
+                    new_msg_id: msg.new_msg_id,
+                    rule: rule.name.clone(),
+                    action: action.clone(),
+                    result,
+                });
+            }
+        }
+        {
+            let mut audit_log = self.audit_log.lock().unwrap();
+            audit_log.extend(entries.iter().cloned());
+            let excess = audit_log.len().saturating_sub(AUDIT_LOG_CAPACITY);
+            audit_log.drain(..excess);
+        }
+        entries
+    }
+
+    /// Promotes `wxids` to chatroom admins.
+    pub async fn promote_admins(
+        &self,
+        client: &ApiClient,
+        chatroom_id: &str,
+        wxids: Vec<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.admin_operate(client, chatroom_id, wxids, AdminOperationType::AddAdmin)
+            .await
+    }
+
+    /// Revokes the admin role of `wxids`.
+    pub async fn demote_admins(
+        &self,
+        client: &ApiClient,
+        chatroom_id: &str,
+        wxids: Vec<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.admin_operate(client, chatroom_id, wxids, AdminOperationType::RemoveAdmin)
+            .await
+    }
+
+    /// Transfers the chatroom ownership to `wxid`.
+    pub async fn transfer_owner(
+        &self,
+        client: &ApiClient,
+        chatroom_id: &str,
+        wxid: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.admin_operate(
+            client,
+            chatroom_id,
+            vec![wxid.to_string()],
+            AdminOperationType::TransferOwner,
+        )
+        .await
+    }
+
+    async fn admin_operate(
+        &self,
+        client: &ApiClient,
+        chatroom_id: &str,
+        wxids: Vec<String>,
+        op: AdminOperationType,
+    ) -> Result<(), Box<dyn Error>> {
+        let ret = client
+            .admin_operate(&self.app_id, chatroom_id, wxids, op as u32)
+            .await?;
+        response_data(ret).map(|_| ())
+    }
+
+    /// Returns the message count of the sender per flood window (secs), `msg`
+    /// included, and records the message time if `record` is set.
+    fn flood_count(&self, chatroom_id: &str, msg: &Message, record: bool) -> HashMap<u64, usize> {
+        let windows: Vec<(u64, usize)> = self
+            .rules
+            .iter()
+            .filter_map(|r| match r.rule {
+                Rule::Flooding {
+                    max_messages,
+                    window,
+                } => Some((window.as_secs(), max_messages)),
+                _ => None,
+            })
+            .collect();
+        if windows.is_empty() {
+            return HashMap::new();
+        }
+        let longest = windows.iter().map(|(w, _)| *w).max().unwrap_or_default() as i64;
+        let mut history = self.history.lock().unwrap();
+        let key = (chatroom_id.to_string(), msg.sender().to_string());
+        let count = |times: &VecDeque<i64>, extra: usize| -> HashMap<u64, usize> {
+            windows
+                .iter()
+                .map(|(window, _)| {
+                    let since = msg.create_time - *window as i64;
+                    (
+                        *window,
+                        times.iter().filter(|t| **t >= since).count() + extra,
+                    )
+                })
+                .collect()
+        };
+        if !record {
+            return match history.get(&key) {
+                Some(times) => count(times, 1),
+                None => count(&VecDeque::new(), 1),
+            };
+        }
+        // Forget the senders silent for longer than any window, or the map grows without bound.
+        history.retain(|_, times| {
+            times
+                .back()
+                .is_some_and(|t| *t >= msg.create_time - longest)
+        });
+        let times = history.entry(key).or_default();
+        times.push_back(msg.create_time);
+        while times
+            .front()
+            .is_some_and(|t| *t < msg.create_time - longest)
+        {
+            times.pop_front();
+        }
+        count(times, 0)
+    }
+
+    async fn perform(
+        &self,
+        client: &ApiClient,
+        msg: &Message,
+        rule: &ModerationRule,
+        action: &Action,
+    ) -> Result<(), Box<dyn Error>> {
+        let chatroom_id = msg.chatroom_id().unwrap_or_default();
+        let to_wxid = Wxid::try_from(chatroom_id)?;
+        let ret = match action {
+            Action::Warn(template) => {
+                let content = template
+                    .replace("{sender}", sender_name(msg))
+                    .replace("{rule}", &rule.name);
+                client
+                    .post_text(&self.app_id, &to_wxid, &content, msg.sender())
+                    .await?
+            }
+            Action::Revoke => {
+                if !msg.is_from_self() {
+                    return Err("Only messages sent by this account can be revoked".into());
+                }
+                client
+                    .revoke_msg(
+                        &self.app_id,
+                        &to_wxid,
+                        &msg.msg_id.to_string(),
+                        &msg.new_msg_id.to_string(),
+                        &msg.create_time.to_string(),
+                    )
+                    .await?
+            }
+            Action::RemoveMember => {
+                client
+                    .remove_member(&self.app_id, msg.sender(), chatroom_id)
+                    .await?
+            }
+        };
+        response_data(ret).map(|_| ())
+    }
+}
+
+fn violates(rule: &Rule, msg: &Message, flood_count: &HashMap<u64, usize>) -> bool {
+    let text = msg.text();
+    match rule {
+        Rule::BannedKeywords(keywords) => {
+            msg.message_type() == MessageType::Text && {
+                let lower = text.to_lowercase();
+                keywords.iter().any(|k| lower.contains(&k.to_lowercase()))
+            }
+        }
+        Rule::BannedPattern(re) => msg.message_type() == MessageType::Text && re.is_match(text),
+        Rule::Links => {
+            (msg.message_type() == MessageType::Text
+                && (text.contains("http://") || text.contains("https://")))
+                || msg.app_msg_type() == Some(5)
+        }
+        Rule::MiniProgram => matches!(msg.app_msg_type(), Some(33) | Some(36)),
+        Rule::Flooding {
+            max_messages,
+            window,
+        } => flood_count
+            .get(&window.as_secs())
+            .is_some_and(|count| count > max_messages),
+        Rule::QrCodeImage(inspect) => msg.message_type() == MessageType::Image && inspect(msg),
+    }
+}
+
+/// The display name of the sender, taken from the push content (`nickname : text`).
+fn sender_name(msg: &Message) -> &str {
+    msg.push_content
+        .split_once(" : ")
+        .map(|(name, _)| name)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| msg.sender())
+}
@@ -0,0 +1,101 @@
+use serde_json::Value;
+use std::error::Error;
+
+use crate::api::{ApiClient, Wxid};
+use crate::callback::system::{Member, SystemEvent};
+
+/// Sends a welcome text when members join a chatroom.
+///
+/// The template supports the placeholders:
+///
+/// - `{mentions}` - `@nickname` of every new member, mentioned through `ats` when the wxid is known.
+/// - `{names}` - nicknames of the new members, separated by `、`.
+/// - `{inviter}` - nickname of the inviter (or the QR code sharer), empty if unknown.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rgewe_api::api::ApiClientBuilder;
+/// use rgewe_api::callback::{system::SystemEvent, CallbackEvent};
+/// use rgewe_api::group::welcome::Welcomer;
+///
+/// async fn on_callback(payload: &serde_json::Value) {
+///     let client = ApiClientBuilder::new().with_token("your_token").build();
+///     let welcomer = Welcomer::new("{mentions} 欢迎加入本群，邀请人：{inviter}");
+///     if let Ok(CallbackEvent::Message(msg)) = CallbackEvent::try_from(payload) {
+///         if let Some(event) = SystemEvent::parse(&msg) {
+///             welcomer.welcome(&client, &msg.app_id, &event).await.unwrap();
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Welcomer {
+    template: String,
+}
+
+impl Welcomer {
+    pub fn new(template: &str) -> Self {
+        Self {
+            template: template.to_string(),
+        }
+    }
+
+    /// Renders the welcome text, returns the content and the flattened `ats` wxids.
+    pub fn render(&self, members: &[Member], inviter: Option<&Member>) -> (String, String) {
+        let mentions = members
+            .iter()
+            .map(|m| format!("@{}", m.nickname))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let names = members
+            .iter()
+            .map(|m| m.nickname.as_str())
+            .collect::<Vec<&str>>()
+            .join("、");
+        let content = self
+            .template
+            .replace("{mentions}", &mentions)
+            .replace("{names}", &names)
+            .replace(
+                "{inviter}",
+                inviter.map(|m| m.nickname.as_str()).unwrap_or(""),
+            );
+        let ats = if self.template.contains("{mentions}") {
+            members
+                .iter()
+                .filter_map(|m| m.wxid.as_deref())
+                .collect::<Vec<&str>>()
+                .join(",")
+        } else {
+            String::new()
+        };
+        (content, ats)
+    }
+
+    /// Sends the welcome text through [`ApiClient::post_text`] if `event` is a
+    /// [`SystemEvent::MemberJoined`], otherwise does nothing and returns `Ok(None)`.
+    pub async fn welcome(
+        &self,
+        client: &ApiClient,
+        app_id: &str,
+        event: &SystemEvent,
+    ) -> Result<Option<Value>, Box<dyn Error>> {
+        let SystemEvent::MemberJoined {
+            chatroom_id,
+            inviter,
+            members,
+            ..
+        } = event
+        else {
+            return Ok(None);
+        };
+        if members.is_empty() {
+            return Ok(None);
+        }
+        let (content, ats) = self.render(members, inviter.as_ref());
+        let to_wxid = Wxid::try_from(chatroom_id.as_str())?;
+        let ret = client.post_text(app_id, &to_wxid, &content, &ats).await?;
+        Ok(Some(ret))
+    }
+}
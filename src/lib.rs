@@ -1 +1,16 @@
 pub mod api;
+pub mod blocking;
+pub mod bot;
+pub mod broadcast;
+pub mod callback;
+pub mod contacts;
+pub mod favor;
+pub mod group;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod store;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "tracing")]
+mod trace;
+mod xml;
@@ -0,0 +1,239 @@
+//! Prometheus metrics of the API usage and account health, enabled by the `metrics` feature.
+//!
+//! A [`Metrics`] registry is attached to a client with
+//! [`ApiClientBuilder::with_metrics`](crate::api::ApiClientBuilder::with_metrics),
+//! which wraps its transport in a [`MetricsTransport`] counting every call:
+//!
+//! - `gewe_api_requests_total{route, app_id}`
+//! - `gewe_api_failures_total{route, app_id, ret}`, `ret` is the Gewe `ret` code or `error`
+//!   for transport errors,
+//! - `gewe_api_latency_seconds{route, app_id}`,
+//! - `gewe_account_online{app_id}`, updated from the responses of `/login/checkOnline`,
+//! - `gewe_outbound_queue_depth`, set by senders queueing messages,
+//! - `gewe_callback_events_total{kind, app_id}`, counted by the
+//!   [`CallbackServer`](crate::callback::server::CallbackServer) when bound with
+//!   `bind_with_metrics`, which also serves the metrics on `GET /metrics`.
+//!
+//! [`Metrics::render`] returns the Prometheus text format, [`Metrics::serve`]
+//! runs a standalone endpoint.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::api::ApiClientBuilder;
+//! use rgewe_api::metrics::Metrics;
+//! use std::sync::Arc;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let metrics = Arc::new(Metrics::new());
+//!     let client = ApiClientBuilder::new()
+//!         .with_token("your_token")
+//!         .with_metrics(metrics.clone())
+//!         .build();
+//!     client.check_online("your_app_id").await.unwrap();
+//!     println!("{}", metrics.render());
+//! }
+//! ```
+use async_trait::async_trait;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use serde_json::Value;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::api::transport::Transport;
+use crate::callback::CallbackEvent;
+
+/// The metrics registry, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    failures: IntCounterVec,
+    latency: HistogramVec,
+    online: IntGaugeVec,
+    queue_depth: IntGauge,
+    callbacks: IntCounterVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let requests = IntCounterVec::new(
+            Opts::new("gewe_api_requests_total", "Gewe API calls"),
+            &["route", "app_id"],
+        )
+        .unwrap();
+        let failures = IntCounterVec::new(
+            Opts::new(
+                "gewe_api_failures_total",
+                "Failed Gewe API calls by ret code",
+            ),
+            &["route", "app_id", "ret"],
+        )
+        .unwrap();
+        let latency = HistogramVec::new(
+            HistogramOpts::new("gewe_api_latency_seconds", "Gewe API call latency"),
+            &["route", "app_id"],
+        )
+        .unwrap();
+        let online = IntGaugeVec::new(
+            Opts::new("gewe_account_online", "Whether the account is online"),
+            &["app_id"],
+        )
+        .unwrap();
+        let queue_depth =
+            IntGauge::new("gewe_outbound_queue_depth", "Messages waiting to be sent").unwrap();
+        let callbacks = IntCounterVec::new(
+            Opts::new("gewe_callback_events_total", "Received callback events"),
+            &["kind", "app_id"],
+        )
+        .unwrap();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(failures.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry.register(Box::new(online.clone())).unwrap();
+        registry.register(Box::new(queue_depth.clone())).unwrap();
+        registry.register(Box::new(callbacks.clone())).unwrap();
+        Self {
+            registry,
+            requests,
+            failures,
+            latency,
+            online,
+            queue_depth,
+            callbacks,
+        }
+    }
+
+    /// The underlying registry, to register further collectors.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Records a completed API call.
+    pub fn observe_call(
+        &self,
+        route: &str,
+        app_id: &str,
+        ret: &Result<Value, Box<dyn Error>>,
+        latency_secs: f64,
+    ) {
+        self.requests.with_label_values(&[route, app_id]).inc();
+        self.latency
+            .with_label_values(&[route, app_id])
+            .observe(latency_secs);
+        match ret {
+            Ok(resp) => {
+                let code = resp.get("ret").and_then(Value::as_i64).unwrap_or_default();
+                if code != 200 {
+                    self.failures
+                        .with_label_values(&[route, app_id, &code.to_string()])
+                        .inc();
+                } else if route == "/login/checkOnline" {
+                    let online = resp.get("data").and_then(Value::as_bool).unwrap_or(false);
+                    self.set_online(app_id, online);
+                }
+            }
+            Err(_) => self
+                .failures
+                .with_label_values(&[route, app_id, "error"])
+                .inc(),
+        }
+    }
+
+    /// Sets whether the account `app_id` is online.
+    pub fn set_online(&self, app_id: &str, online: bool) {
+        self.online.with_label_values(&[app_id]).set(online as i64);
+    }
+
+    /// Sets the number of messages waiting to be sent.
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.queue_depth.set(depth as i64);
+    }
+
+    /// Counts a received callback event, an `Offline` event also marks the account offline.
+    pub fn observe_callback(&self, event: &CallbackEvent) {
+        let kind = match event {
+            CallbackEvent::Message(_) | CallbackEvent::MessageRevoked(_) => "AddMsg",
+            CallbackEvent::ContactModified { .. } => "ModContacts",
+            CallbackEvent::ContactDeleted { .. } => "DelContacts",
+            CallbackEvent::Offline { .. } => "Offline",
+            CallbackEvent::Test => "Test",
+            CallbackEvent::Other { type_name, .. } => type_name.as_str(),
+        };
+        let app_id = event.app_id().unwrap_or_default();
+        self.callbacks.with_label_values(&[kind, app_id]).inc();
+        if let CallbackEvent::Offline { app_id, .. } = event {
+            self.set_online(app_id, false);
+        }
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    /// Serves the metrics on `GET /metrics` at `addr` until the future is dropped.
+    #[cfg(feature = "server")]
+    pub async fn serve(
+        self: Arc<Self>,
+        addr: impl tokio::net::ToSocketAddrs,
+    ) -> Result<(), Box<dyn Error>> {
+        let app = axum::Router::new().route(
+            "/metrics",
+            axum::routing::get(move || async move { self.render() }),
+        );
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, app).await?;
+        Ok(())
+    }
+}
+
+/// A [`Transport`] recording the calls sent through `inner` in [`Metrics`].
+pub struct MetricsTransport {
+    metrics: Arc<Metrics>,
+    inner: Arc<dyn Transport>,
+}
+
+impl MetricsTransport {
+    pub fn new(metrics: Arc<Metrics>, inner: Arc<dyn Transport>) -> Self {
+        Self { metrics, inner }
+    }
+}
+
+#[async_trait]
+impl Transport for MetricsTransport {
+    async fn post(
+        &self,
+        route: &str,
+        token: &str,
+        body: Option<Value>,
+    ) -> Result<Value, Box<dyn Error>> {
+        let app_id = body
+            .as_ref()
+            .and_then(|b| b.get("appId"))
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let start = Instant::now();
+        let ret = self.inner.post(route, token, body).await;
+        self.metrics
+            .observe_call(route, &app_id, &ret, start.elapsed().as_secs_f64());
+        ret
+    }
+}
@@ -0,0 +1,370 @@
+//! Persistent history of the received and sent messages.
+//!
+//! Gewe keeps no message history, a [`MessageStore`] records it instead:
+//!
+//! - received messages with [`MessageStore::record_event`], e.g. from the
+//!   events of a [`CallbackServer`](crate::callback::server::CallbackServer),
+//! - sent messages with a [`RecordingInterceptor`], which queues the
+//!   successful `post*`/`forward*` calls of an [`ApiClient`](crate::api::ApiClient)
+//!   for its [`RecordingWriter`] to record.
+//!
+//! Messages are identified by their `newMsgId`, recording a message twice (e.g.
+//! a sent message echoed by a callback) keeps the first record.
+//! [`SqliteStore`](sqlite::SqliteStore), enabled by the `sqlite` feature, is
+//! the default implementation; other databases implement the trait.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rgewe_api::api::ApiClientBuilder;
+//! use rgewe_api::store::{MessageQuery, MessageStore, RecordingInterceptor};
+//! use std::sync::Arc;
+//!
+//! async fn history(store: Arc<dyn MessageStore>) {
+//!     let (recording, writer) = RecordingInterceptor::new(store.clone());
+//!     tokio::spawn(writer.run(|e| eprintln!("failed to record a sent message: {}", e)));
+//!     let client = ApiClientBuilder::new()
+//!         .with_token("your_token")
+//!         .with_interceptor(recording)
+//!         .build();
+//!     let query = MessageQuery::new()
+//!         .with_chat("34757816141@chatroom")
+//!         .with_text("invoice");
+//!     for msg in store.query(&query).await.unwrap() {
+//!         println!("{} {}: {}", msg.create_time, msg.sender, msg.content);
+//!     }
+//! }
+//! ```
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+
+use crate::api::interceptor::Interceptor;
+use crate::callback::int_field;
+use crate::callback::{AppMsg, CallbackEvent, Message, MessageType, Quotable, ReferMsg};
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Whether a message was received or sent by the account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Incoming,
+    /// Sent through the API, or by the account itself from another device.
+    Outgoing,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Incoming => "incoming",
+            Direction::Outgoing => "outgoing",
+        }
+    }
+}
+
+impl TryFrom<&str> for Direction {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "incoming" => Ok(Direction::Incoming),
+            "outgoing" => Ok(Direction::Outgoing),
+            other => Err(format!("Invalid message direction: {}", other)),
+        }
+    }
+}
+
+/// A recorded message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredMessage {
+    pub app_id: String,
+    pub msg_id: i64,
+    pub new_msg_id: i64,
+    pub direction: Direction,
+    /// The conversation: the chatroom id, or the wxid of the other party.
+    pub chat_id: String,
+    /// The wxid of the author, empty for sent messages of an unknown account.
+    pub sender: String,
+    pub msg_type: u32,
+    /// The text of text messages, the XML body or URL of other messages.
+    pub content: String,
+    pub app_msg: Option<AppMsg>,
+    pub create_time: i64,
+}
+
+impl StoredMessage {
+    /// The record of a received message.
+    pub fn from_message(msg: &Message) -> Self {
+        StoredMessage {
+            app_id: msg.app_id.clone(),
+            msg_id: msg.msg_id,
+            new_msg_id: msg.new_msg_id,
+            direction: if msg.is_from_self() {
+                Direction::Outgoing
+            } else {
+                Direction::Incoming
+            },
+            chat_id: msg.chat_id().to_string(),
+            sender: msg.sender().to_string(),
+            msg_type: msg.msg_type,
+            content: msg.text().to_string(),
+            app_msg: msg.app_msg(),
+            create_time: msg.create_time,
+        }
+    }
+
+    /// The record of a message sent to `route` with `body`, from the data of the
+    /// response, `None` for any other route.
+    pub fn from_sent(route: &str, body: &Value, data: &Value, sender: &str) -> Option<Self> {
+        let field = |key: &str| body.get(key).and_then(Value::as_str).unwrap_or_default();
+        let content = match route {
+            "/message/postText" => field("content"),
+            "/message/postImage" => field("imgUrl"),
+            "/message/postFile" => field("fileUrl"),
+            "/message/postVoice" => field("voiceUrl"),
+            "/message/postVideo" => field("videoUrl"),
+            "/message/postLink" => field("linkUrl"),
+            "/message/postNameCard" => field("nameCardWxid"),
+            "/message/postEmoji" => field("emojiMd5"),
+            "/message/postAppMsg" => field("appmsg"),
+            "/message/postMiniApp" => field("pagePath"),
+            r if r.starts_with("/message/forward") => field("xml"),
+            _ => return None,
+        };
+        let app_msg = match route {
+            "/message/postLink" => Some(AppMsg {
+                kind: 5,
+                title: field("title").to_string(),
+                des: field("desc").to_string(),
+                url: field("linkUrl").to_string(),
+                refer: None,
+            }),
+            _ => AppMsg::parse(content),
+        };
+        Some(StoredMessage {
+            app_id: field("appId").to_string(),
+            msg_id: int_field(data, "msgId"),
+            new_msg_id: int_field(data, "newMsgId"),
+            direction: Direction::Outgoing,
+            chat_id: field("toWxid").to_string(),
+            sender: sender.to_string(),
+            msg_type: int_field(data, "type") as u32,
+            content: content.to_string(),
+            app_msg,
+            create_time: int_field(data, "createTime"),
+        })
+    }
+
+    /// The text indexed for full-text search: the text of text messages, the
+    /// title and description of app messages.
+    pub fn searchable_text(&self) -> String {
+        match (&self.app_msg, MessageType::from(self.msg_type)) {
+            (Some(app_msg), _) => format!("{}\n{}", app_msg.title, app_msg.des),
+            (None, MessageType::Text | MessageType::SystemNotice) => self.content.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+impl Quotable for StoredMessage {
+    fn app_id(&self) -> &str {
+        &self.app_id
+    }
+
+    fn chat_id(&self) -> &str {
+        &self.chat_id
+    }
+
+    /// Quotes the message with the wxid of its author as display name.
+    fn refer_msg(&self) -> ReferMsg {
+        let (from_user, chat_user) = if self.chat_id.ends_with("@chatroom") {
+            (self.chat_id.as_str(), self.sender.as_str())
+        } else {
+            (self.sender.as_str(), "")
+        };
+        ReferMsg {
+            msg_type: self.msg_type,
+            new_msg_id: self.new_msg_id,
+            from_user: from_user.to_string(),
+            chat_user: chat_user.to_string(),
+            display_name: self.sender.clone(),
+            content: self.content.clone(),
+            create_time: self.create_time,
+        }
+    }
+}
+
+/// Criteria of [`MessageStore::query`], all optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageQuery {
+    pub app_id: Option<String>,
+    pub chat_id: Option<String>,
+    pub sender: Option<String>,
+    /// Inclusive lower bound of the `create_time` (seconds).
+    pub since: Option<i64>,
+    /// Exclusive upper bound of the `create_time` (seconds).
+    pub until: Option<i64>,
+    /// Full-text search in the [searchable text](StoredMessage::searchable_text).
+    pub text: Option<String>,
+    /// Maximum number of results, the most recent first.
+    pub limit: Option<usize>,
+}
+
+impl MessageQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_app_id(mut self, app_id: &str) -> Self {
+        self.app_id = Some(app_id.to_string());
+        self
+    }
+
+    pub fn with_chat(mut self, chat_id: &str) -> Self {
+        self.chat_id = Some(chat_id.to_string());
+        self
+    }
+
+    pub fn with_sender(mut self, sender: &str) -> Self {
+        self.sender = Some(sender.to_string());
+        self
+    }
+
+    /// Restricts to messages created in `[since, until)` (seconds).
+    pub fn with_time_range(mut self, since: i64, until: i64) -> Self {
+        self.since = Some(since);
+        self.until = Some(until);
+        self
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = Some(text.to_string());
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// Storage of the message history, see the [module docs](self).
+#[async_trait]
+pub trait MessageStore: Send + Sync {
+    /// Records a message, returns `false` if its `newMsgId` was already recorded.
+    async fn insert(&self, msg: &StoredMessage) -> Result<bool, Box<dyn Error>>;
+
+    /// The messages matching `query`, the most recent first.
+    async fn query(&self, query: &MessageQuery) -> Result<Vec<StoredMessage>, Box<dyn Error>>;
+
+    /// Records the message of an `AddMsg` event, ignores any other event.
+    async fn record_event(&self, event: &CallbackEvent) -> Result<bool, Box<dyn Error>> {
+        match event.message() {
+            Some(msg) => self.insert(&StoredMessage::from_message(msg)).await,
+            None => Ok(false),
+        }
+    }
+}
+
+/// An [`Interceptor`] recording the messages sent through a client in a [`MessageStore`].
+///
+/// Only successful calls are recorded. Interceptors cannot wait, so the
+/// records are queued, without bound, for the [`RecordingWriter`] returned
+/// with the interceptor, which the caller runs. Keep the writer as long as the
+/// client sends: records of messages sent after it is dropped are lost. The
+/// sender of the records is the wxid set with
+/// [`RecordingInterceptor::with_account`], empty otherwise.
+pub struct RecordingInterceptor {
+    records: mpsc::UnboundedSender<StoredMessage>,
+    accounts: RwLock<HashMap<String, String>>,
+}
+
+impl RecordingInterceptor {
+    /// The interceptor, and the writer inserting its records into `store`.
+    pub fn new(store: Arc<dyn MessageStore>) -> (Self, RecordingWriter) {
+        let (records, queue) = mpsc::unbounded_channel();
+        let interceptor = Self {
+            records,
+            accounts: RwLock::new(HashMap::new()),
+        };
+        let writer = RecordingWriter { store, queue };
+        (interceptor, writer)
+    }
+
+    /// Sets the wxid of the account `app_id`, the sender of its sent messages.
+    pub fn with_account(self, app_id: &str, wxid: &str) -> Self {
+        self.accounts
+            .write()
+            .unwrap()
+            .insert(app_id.to_string(), wxid.to_string());
+        self
+    }
+}
+
+impl Interceptor for RecordingInterceptor {
+    fn after_response(&self, route: &str, body: &Value, response: &Value) {
+        if response.get("ret").and_then(Value::as_i64) != Some(200) {
+            return;
+        }
+        let app_id = body
+            .get("appId")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let sender = self
+            .accounts
+            .read()
+            .unwrap()
+            .get(app_id)
+            .cloned()
+            .unwrap_or_default();
+        let data = response.get("data").unwrap_or(&Value::Null);
+        if let Some(record) = StoredMessage::from_sent(route, body, data, &sender) {
+            if self.records.send(record).is_err() {
+                #[cfg(feature = "tracing")]
+                tracing::error!("recording writer dropped, a sent message is not recorded");
+            }
+        }
+    }
+}
+
+/// Inserts the records queued by a [`RecordingInterceptor`] into its store.
+pub struct RecordingWriter {
+    store: Arc<dyn MessageStore>,
+    queue: mpsc::UnboundedReceiver<StoredMessage>,
+}
+
+impl RecordingWriter {
+    /// Inserts the next record, waiting for one. `None` once the interceptor
+    /// is dropped and the queue is empty.
+    pub async fn write_next(&mut self) -> Option<Result<bool, Box<dyn Error>>> {
+        let record = self.queue.recv().await?;
+        Some(self.store.insert(&record).await)
+    }
+
+    /// Inserts the records queued so far without waiting for more, returns
+    /// how many were inserted. Stops at the first failed insert.
+    pub async fn write_pending(&mut self) -> Result<usize, Box<dyn Error>> {
+        let mut inserted = 0;
+        while let Ok(record) = self.queue.try_recv() {
+            if self.store.insert(&record).await? {
+                inserted += 1;
+            }
+        }
+        Ok(inserted)
+    }
+
+    /// Inserts the records until the interceptor is dropped, passing the
+    /// errors of the failed inserts to `on_error`.
+    pub async fn run(mut self, mut on_error: impl FnMut(Box<dyn Error>)) {
+        while let Some(result) = self.write_next().await {
+            if let Err(e) = result {
+                on_error(e);
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! SQLite [`MessageStore`], enabled by the `sqlite` feature.
+//!
+//! Messages live in the `messages` table, their
+//! [searchable text](super::StoredMessage::searchable_text) in the
+//! `messages_fts` FTS5 table. The trigram tokenizer makes the search match any
+//! substring, which suits Chinese texts without word separators; queries
+//! shorter than three characters fall back to a scan.
+//!
+//! The blocking SQLite calls run on the blocking threads of the tokio runtime.
+use async_trait::async_trait;
+use rusqlite::types::{Type, Value as SqlValue};
+use rusqlite::{params, params_from_iter, Connection, Row};
+use std::error::Error;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use super::{Direction, MessageQuery, MessageStore, StoredMessage};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY,
+    app_id TEXT NOT NULL,
+    msg_id INTEGER NOT NULL,
+    new_msg_id INTEGER NOT NULL,
+    direction TEXT NOT NULL,
+    chat_id TEXT NOT NULL,
+    sender TEXT NOT NULL,
+    msg_type INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    app_msg TEXT,
+    create_time INTEGER NOT NULL,
+    UNIQUE (app_id, new_msg_id)
+);
+CREATE INDEX IF NOT EXISTS messages_chat ON messages (chat_id, create_time);
+CREATE INDEX IF NOT EXISTS messages_sender ON messages (sender, create_time);
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5 (text, tokenize = 'trigram');
+";
+
+/// A [`MessageStore`] in a SQLite database, see the [module docs](self).
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+type SyncError = Box<dyn Error + Send + Sync>;
+
+impl SqliteStore {
+    /// Opens (or creates) the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// A store in memory, lost when dropped.
+    pub fn open_in_memory() -> Result<Self, Box<dyn Error>> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, Box<dyn Error>> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `f` on the connection, on a blocking thread.
+    async fn with_conn<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<T, SyncError> + Send + 'static,
+    ) -> Result<T, Box<dyn Error>> {
+        let conn = self.conn.clone();
+        let ret = tokio::task::spawn_blocking(move || f(&conn.lock().unwrap())).await?;
+        ret.map_err(|e| e as Box<dyn Error>)
+    }
+}
+
+/// Inserts the message and its search entry in one transaction, so a message
+/// is never stored unindexed.
+fn insert_sync(conn: &Connection, msg: &StoredMessage) -> Result<bool, SyncError> {
+    let app_msg = msg
+        .app_msg
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    let tx = conn.unchecked_transaction()?;
+    let inserted = tx.execute(
+        "INSERT OR IGNORE INTO messages (app_id, msg_id, new_msg_id, direction, chat_id,
+                sender, msg_type, content, app_msg, create_time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            msg.app_id,
+            msg.msg_id,
+            msg.new_msg_id,
+            msg.direction.as_str(),
+            msg.chat_id,
+            msg.sender,
+            msg.msg_type,
+            msg.content,
+            app_msg,
+            msg.create_time,
+        ],
+    )?;
+    if inserted == 0 {
+        return Ok(false);
+    }
+    let text = msg.searchable_text();
+    if !text.is_empty() {
+        tx.execute(
+            "INSERT INTO messages_fts (rowid, text) VALUES (?1, ?2)",
+            params![tx.last_insert_rowid(), text],
+        )?;
+    }
+    tx.commit()?;
+    Ok(true)
+}
+
+fn query_sync(conn: &Connection, query: &MessageQuery) -> Result<Vec<StoredMessage>, SyncError> {
+    let mut sql = "SELECT app_id, msg_id, new_msg_id, direction, chat_id, sender, msg_type,
+                content, app_msg, create_time
+             FROM messages WHERE 1 = 1"
+        .to_string();
+    let mut values: Vec<SqlValue> = Vec::new();
+    let mut filter = |clause: &str, value: SqlValue| {
+        sql.push_str(" AND ");
+        sql.push_str(clause);
+        values.push(value);
+    };
+    if let Some(app_id) = &query.app_id {
+        filter("app_id = ?", app_id.clone().into());
+    }
+    if let Some(chat_id) = &query.chat_id {
+        filter("chat_id = ?", chat_id.clone().into());
+    }
+    if let Some(sender) = &query.sender {
+        filter("sender = ?", sender.clone().into());
+    }
+    if let Some(since) = query.since {
+        filter("create_time >= ?", since.into());
+    }
+    if let Some(until) = query.until {
+        filter("create_time < ?", until.into());
+    }
+    if let Some(text) = &query.text {
+        if text.chars().count() >= 3 {
+            filter(
+                "id IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?)",
+                format!("\"{}\"", text.replace('"', "\"\"")).into(),
+            );
+        } else {
+            let escaped = text
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            filter(
+                "id IN (SELECT rowid FROM messages_fts WHERE text LIKE ? ESCAPE '\\')",
+                format!("%{}%", escaped).into(),
+            );
+        }
+    }
+    sql.push_str(" ORDER BY create_time DESC, id DESC");
+    if let Some(limit) = query.limit {
+        sql.push_str(&format!(" LIMIT {}", limit));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values), read_row)?;
+    let mut messages = Vec::new();
+    for row in rows {
+        messages.push(row?);
+    }
+    Ok(messages)
+}
+
+fn read_row(row: &Row) -> rusqlite::Result<StoredMessage> {
+    let direction: String = row.get(3)?;
+    let app_msg: Option<String> = row.get(8)?;
+    Ok(StoredMessage {
+        app_id: row.get(0)?,
+        msg_id: row.get(1)?,
+        new_msg_id: row.get(2)?,
+        direction: Direction::try_from(direction.as_str())
+            .map_err(|e| conversion_error(3, e.into()))?,
+        chat_id: row.get(4)?,
+        sender: row.get(5)?,
+        msg_type: row.get(6)?,
+        content: row.get(7)?,
+        app_msg: app_msg
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(|e| conversion_error(8, e.into()))?,
+        create_time: row.get(9)?,
+    })
+}
+
+fn conversion_error(column: usize, e: Box<dyn Error + Send + Sync>) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(column, Type::Text, e)
+}
+
+#[async_trait]
+impl MessageStore for SqliteStore {
+    async fn insert(&self, msg: &StoredMessage) -> Result<bool, Box<dyn Error>> {
+        let msg = msg.clone();
+        self.with_conn(move |conn| insert_sync(conn, &msg)).await
+    }
+
+    async fn query(&self, query: &MessageQuery) -> Result<Vec<StoredMessage>, Box<dyn Error>> {
+        let query = query.clone();
+        self.with_conn(move |conn| query_sync(conn, &query)).await
+    }
+}
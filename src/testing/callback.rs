@@ -0,0 +1,359 @@
+//! Builders of realistic Gewe callback payloads for bot integration tests.
+//!
+//! [`CallbackSimulator`] produces the payloads Gewe posts for messages received
+//! by an account and delivers them either by POSTing to a callback receiver
+//! ([`CallbackSimulator::with_receiver_url`]) or by injecting them into the
+//! event stream of a [`CallbackServer`](crate::callback::server::CallbackServer)
+//! ([`CallbackSimulator::with_event_sender`]).
+//!
+//! # Examples
+//!
+//! ```rust
+//! use rgewe_api::callback::server::CallbackServer;
+//! use rgewe_api::callback::CallbackEvent;
+//! use rgewe_api::testing::callback::CallbackSimulator;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut server = CallbackServer::bind("127.0.0.1:0").await.unwrap();
+//!     let sim = CallbackSimulator::new("wx_app", "wxid_bot").with_receiver_url(&server.url());
+//!
+//!     let payload = sim
+//!         .group_text("34757816141@chatroom", "wxid_alice", "@bot /help")
+//!         .with_mentions(&["wxid_bot"])
+//!         .build();
+//!     sim.send(&payload).await.unwrap();
+//!
+//!     let Some(CallbackEvent::Message(msg)) = server.next_event().await else {
+//!         panic!("expected a message");
+//!     };
+//!     assert_eq!(msg.sender(), "wxid_alice");
+//!     assert!(msg.mentions("wxid_bot"));
+//! }
+//! ```
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::callback::server::EventSender;
+use crate::callback::{CallbackEvent, Message, Quotable};
+use crate::xml::escape;
+
+static NEXT_MSG_ID: AtomicI64 = AtomicI64::new(1_040_356_095);
+
+enum Target {
+    None,
+    Url(String),
+    Events(EventSender),
+}
+
+/// Produces and delivers callback payloads of the account `wxid`, see the [module docs](self).
+pub struct CallbackSimulator {
+    app_id: String,
+    wxid: String,
+    target: Target,
+}
+
+impl CallbackSimulator {
+    /// A simulator for the logged in account `wxid` of the application `app_id`.
+    pub fn new(app_id: &str, wxid: &str) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            wxid: wxid.to_string(),
+            target: Target::None,
+        }
+    }
+
+    /// Delivers payloads by POSTing them to the callback receiver at `url`.
+    pub fn with_receiver_url(mut self, url: &str) -> Self {
+        self.target = Target::Url(url.to_string());
+        self
+    }
+
+    /// Delivers payloads by parsing and injecting them into an event stream.
+    pub fn with_event_sender(mut self, sender: EventSender) -> Self {
+        self.target = Target::Events(sender);
+        self
+    }
+
+    /// Delivers `payload` to the configured receiver or event stream.
+    pub async fn send(&self, payload: &Value) -> Result<(), Box<dyn Error>> {
+        match &self.target {
+            Target::None => Err("No callback receiver or event sender configured".into()),
+            Target::Url(url) => {
+                let resp = reqwest::Client::builder()
+                    .no_proxy()
+                    .build()?
+                    .post(url)
+                    .json(payload)
+                    .send()
+                    .await?;
+                if !resp.status().is_success() {
+                    return Err(format!("Callback receiver answered {}", resp.status()).into());
+                }
+                Ok(())
+            }
+            Target::Events(sender) => {
+                let event = CallbackEvent::try_from(payload)?;
+                sender
+                    .send(event)
+                    .map_err(|_| "The event stream is closed".into())
+            }
+        }
+    }
+
+    /// A private text message from `from` to the account.
+    pub fn text(&self, from: &str, content: &str) -> MessageBuilder {
+        self.message(from, 1, content)
+    }
+
+    /// A text message posted by `sender` in `chatroom_id`.
+    pub fn group_text(&self, chatroom_id: &str, sender: &str, content: &str) -> MessageBuilder {
+        self.message(chatroom_id, 1, content).with_sender(sender)
+    }
+
+    /// An image message from `from` (a wxid or chatroom id).
+    pub fn image(&self, from: &str) -> MessageBuilder {
+        let msg_id = NEXT_MSG_ID.load(Ordering::Relaxed);
+        let content = format!(
+            r#"<?xml version="1.0"?><msg><img aeskey="a3a3c7c5e2d4b0f1e6a7c8d9b0a1f2e3" encryver="1" cdnthumbaeskey="a3a3c7c5e2d4b0f1e6a7c8d9b0a1f2e3" cdnthumburl="3057020100044b304902010002043904752002032f7d6d02046bb5bade0204{msg_id:x}" cdnthumblength="3266" cdnthumbheight="120" cdnthumbwidth="90" cdnmidheight="0" cdnmidwidth="0" cdnhdheight="0" cdnhdwidth="0" cdnmidimgurl="3057020100044b304902010002043904752002032f7d6d02046bb5bade0204{msg_id:x}" length="48235" md5="a1b9c2f8d2e6f0b1c3e5a7d9f1b3c5e7" hevc_mid_size="48235" /></msg>"#,
+        );
+        self.message(from, 3, &content)
+    }
+
+    /// A link (appmsg type 5) shared by `from`.
+    pub fn link(&self, from: &str, title: &str, desc: &str, url: &str) -> MessageBuilder {
+        let content = format!(
+            r#"<?xml version="1.0"?><msg><appmsg appid="" sdkver="0"><title>{}</title><des>{}</des><type>5</type><url>{}</url><thumburl></thumburl></appmsg><fromusername>{}</fromusername><scene>0</scene><appinfo><version>1</version><appname></appname></appinfo></msg>"#,
+            escape(title),
+            escape(desc),
+            escape(url),
+            escape(from),
+        );
+        self.message(from, 49, &content)
+    }
+
+    /// A quote (appmsg type 57) from `from` replying `text` to the message `quoted`.
+    pub fn quote(&self, from: &str, text: &str, quoted: &Message) -> MessageBuilder {
+        let content = format!(
+            r#"<?xml version="1.0"?><msg>{}<fromusername>{}</fromusername><scene>0</scene></msg>"#,
+            quoted.refer_msg().to_appmsg(text),
+            escape(from),
+        );
+        self.message(from, 49, &content)
+    }
+
+    /// The system message (type 10002) posted when the sender of `original` revokes it.
+    pub fn revoke(&self, original: &Message) -> MessageBuilder {
+        let content = format!(
+            r#"<sysmsg type="revokemsg"><revokemsg><session>{}</session><msgid>{}</msgid><newmsgid>{}</newmsgid><replacemsg><![CDATA["{}" 撤回了一条消息]]></replacemsg></revokemsg></sysmsg>"#,
+            escape(original.chat_id()),
+            original.msg_id,
+            original.new_msg_id,
+            original.sender(),
+        );
+        let builder = self.message(original.chat_id(), 10002, &content);
+        match original.chatroom_id() {
+            Some(_) => builder.with_sender(original.sender()),
+            None => builder,
+        }
+    }
+
+    /// A friend request (type 37) from the stranger `from` with the greeting `content`.
+    pub fn friend_request(&self, from: &str, nickname: &str, content: &str) -> MessageBuilder {
+        let xml = format!(
+            r#"<msg fromusername="{from}" encryptusername="v3_{from}@stranger" fromnickname="{}" content="{}" fullpy="{from}" shortpy="" imagestatus="3" scene="30" country="CN" province="Shanghai" city="" sign="" percard="1" sex="1" alias="" weibo="" albumflag="0" albumstyle="0" albumbgimgid="" snsflag="273" snsbgimgid="" snsbgobjectid="0" mhash="" mfullhash="" bigheadimgurl="" smallheadimgurl="" ticket="v4_{from}@stranger" opcode="2" googlecontact="" qrticket="" chatroomusername="" sourceusername="" sourcenickname="" sharecardusername="" sharecardnickname="" cardversion="" extflag="0"><brandlist count="0" ver="0"></brandlist></msg>"#,
+            escape(nickname),
+            escape(content),
+            from = escape(from),
+        );
+        self.message("fmessage", 37, &xml)
+    }
+
+    /// The system notice (type 10000) posted in `chatroom_id` when `inviter` invites `members` (nicknames).
+    pub fn member_joined(
+        &self,
+        chatroom_id: &str,
+        inviter: &str,
+        members: &[&str],
+    ) -> MessageBuilder {
+        let content = format!("\"{}\"邀请\"{}\"加入了群聊", inviter, members.join("、"));
+        self.message(chatroom_id, 10000, &content)
+    }
+
+    /// The template system message (type 10002) posted in `chatroom_id` when
+    /// `inviter` invites `members`, both as `(wxid, nickname)`.
+    pub fn member_invited(
+        &self,
+        chatroom_id: &str,
+        inviter: (&str, &str),
+        members: &[(&str, &str)],
+    ) -> MessageBuilder {
+        let member_list = |members: &[(&str, &str)]| {
+            members
+                .iter()
+                .map(|(wxid, nickname)| {
+                    format!(
+                        "<member><username><![CDATA[{}]]></username><nickname><![CDATA[{}]]></nickname></member>",
+                        wxid, nickname
+                    )
+                })
+                .collect::<String>()
+        };
+        let content = format!(
+            r#"<sysmsg type="sysmsgtemplate"><sysmsgtemplate><content_template type="tmpl_type_profile"><plain><![CDATA[]]></plain><template><![CDATA["$username$"邀请"$names$"加入了群聊]]></template><link_list><link name="username" type="link_profile"><memberlist>{}</memberlist></link><link name="names" type="link_profile"><memberlist>{}</memberlist><separator><![CDATA[、]]></separator></link></link_list></content_template></sysmsgtemplate></sysmsg>"#,
+            member_list(&[inviter]),
+            member_list(members),
+        );
+        self.message(chatroom_id, 10002, &content)
+    }
+
+    /// The system notice (type 10000) posted in `chatroom_id` when the account removes `members` (nicknames).
+    pub fn member_removed(&self, chatroom_id: &str, members: &[&str]) -> MessageBuilder {
+        let content = format!("你将\"{}\"移出了群聊", members.join("、"));
+        self.message(chatroom_id, 10000, &content)
+    }
+
+    /// The system notice (type 10000) posted in `chatroom_id` when `operator` (a nickname) renames it.
+    pub fn chatroom_renamed(
+        &self,
+        chatroom_id: &str,
+        operator: &str,
+        name: &str,
+    ) -> MessageBuilder {
+        let content = format!("\"{}\"修改群名为“{}”", operator, name);
+        self.message(chatroom_id, 10000, &content)
+    }
+
+    /// The `Offline` payload posted when the account goes offline.
+    pub fn offline(&self) -> Value {
+        json!({
+            "TypeName": "Offline",
+            "Appid": self.app_id,
+            "Wxid": self.wxid,
+        })
+    }
+
+    fn message(&self, from: &str, msg_type: u32, content: &str) -> MessageBuilder {
+        let msg_id = NEXT_MSG_ID.fetch_add(1, Ordering::Relaxed);
+        MessageBuilder {
+            app_id: self.app_id.clone(),
+            wxid: self.wxid.clone(),
+            msg_id,
+            new_msg_id: 7_773_749_793_478_223_190 + msg_id,
+            from_user: from.to_string(),
+            to_user: self.wxid.clone(),
+            msg_type,
+            content: content.to_string(),
+            sender: None,
+            mentions: Vec::new(),
+            push_content: String::new(),
+            create_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Builder of an `AddMsg` callback payload.
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+    app_id: String,
+    wxid: String,
+    msg_id: i64,
+    new_msg_id: i64,
+    from_user: String,
+    to_user: String,
+    msg_type: u32,
+    content: String,
+    sender: Option<String>,
+    mentions: Vec<String>,
+    push_content: String,
+    create_time: i64,
+}
+
+impl MessageBuilder {
+    /// Sets the chatroom member who posted the message, prefixed to the content like Gewe does.
+    pub fn with_sender(mut self, sender: &str) -> Self {
+        self.sender = Some(sender.to_string());
+        self
+    }
+
+    /// Adds wxids mentioned with `@` (`notify@all` for everyone) to the `MsgSource`.
+    pub fn with_mentions(mut self, wxids: &[&str]) -> Self {
+        self.mentions.extend(wxids.iter().map(|w| w.to_string()));
+        self
+    }
+
+    pub fn with_msg_id(mut self, msg_id: i64, new_msg_id: i64) -> Self {
+        self.msg_id = msg_id;
+        self.new_msg_id = new_msg_id;
+        self
+    }
+
+    pub fn with_create_time(mut self, create_time: i64) -> Self {
+        self.create_time = create_time;
+        self
+    }
+
+    pub fn with_push_content(mut self, push_content: &str) -> Self {
+        self.push_content = push_content.to_string();
+        self
+    }
+
+    /// Makes the message sent by the account itself (e.g. from the phone) to `to`.
+    pub fn from_self(mut self, to: &str) -> Self {
+        self.from_user = self.wxid.clone();
+        self.to_user = to.to_string();
+        self
+    }
+
+    /// Builds the callback payload.
+    pub fn build(&self) -> Value {
+        let content = match &self.sender {
+            Some(sender) if self.from_user.ends_with("@chatroom") => {
+                format!("{}:\n{}", sender, self.content)
+            }
+            _ => self.content.clone(),
+        };
+        let at_list = if self.mentions.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "<atuserlist><![CDATA[,{}]]></atuserlist>",
+                self.mentions.join(",")
+            )
+        };
+        let msg_source = format!(
+            "<msgsource>{}<silence>0</silence><membercount>3</membercount><signature>V1_mock|v1_mock</signature><tmp_node><publisher-id></publisher-id></tmp_node></msgsource>",
+            at_list
+        );
+        json!({
+            "TypeName": "AddMsg",
+            "Appid": self.app_id,
+            "Wxid": self.wxid,
+            "Data": {
+                "MsgId": self.msg_id,
+                "FromUserName": { "string": self.from_user },
+                "ToUserName": { "string": self.to_user },
+                "MsgType": self.msg_type,
+                "Content": { "string": content },
+                "Status": 3,
+                "ImgStatus": if self.msg_type == 3 { 2 } else { 1 },
+                "ImgBuf": { "iLen": 0 },
+                "CreateTime": self.create_time,
+                "MsgSource": msg_source,
+                "PushContent": self.push_content,
+                "NewMsgId": self.new_msg_id,
+                "MsgSeq": 640356095,
+            }
+        })
+    }
+
+    /// Builds the payload and parses it into a [`Message`].
+    pub fn message(&self) -> Message {
+        Message::try_from(&self.build()).expect("built payloads are valid AddMsg payloads")
+    }
+}
@@ -0,0 +1,312 @@
+//! Fixture responses of the mock Gewe server, shaped like the real service's.
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Token returned by `/tools/getTokenId`.
+pub const MOCK_TOKEN: &str = "mock-token-5f1c2d";
+/// Application identifier used by the fixtures.
+pub const MOCK_APP_ID: &str = "wx_mock_app";
+/// Wxid of the mock logged in account.
+pub const MOCK_WXID: &str = "wxid_mock_self";
+/// Chatroom listed by the fixtures.
+pub const MOCK_CHATROOM: &str = "34757816141@chatroom";
+/// Friends listed by the fixtures.
+pub const MOCK_FRIENDS: [&str; 3] = ["wxid_mock_zhangsan", "wxid_mock_lisi", "filehelper"];
+/// Content of every file served by the download service of the mock server.
+pub const MOCK_DOWNLOAD: &[u8] = b"mock download";
+
+static NEXT_MSG_ID: AtomicI64 = AtomicI64::new(7_773_749_793_478_223_190);
+
+/// Wraps `data` in a successful Gewe response.
+pub fn ok(data: Value) -> Value {
+    json!({ "ret": 200, "msg": "操作成功", "data": data })
+}
+
+/// A failed Gewe response with the given `ret` code.
+pub fn failure(ret: i64, msg: &str) -> Value {
+    json!({ "ret": ret, "msg": msg, "data": null })
+}
+
+/// Returns the fixture response of `route` for the request `body`, `None` for unknown routes.
+pub fn response(route: &str, body: &Value) -> Option<Value> {
+    let str_param = |key: &str| body.get(key).and_then(Value::as_str).unwrap_or_default();
+    let data = match route {
+        "/tools/getTokenId" => json!(MOCK_TOKEN),
+        "/login/getLoginQrCode" => json!({
+            "appId": if str_param("appId").is_empty() { MOCK_APP_ID } else { str_param("appId") },
+            "qrData": "http://weixin.qq.com/x/mock-login-qr",
+            "qrImgBase64": "data:image/png;base64,iVBORw0KGgo=",
+            "uuid": "mock-login-uuid",
+        }),
+        "/login/checkLogin" => json!({
+            "uuid": str_param("uuid"),
+            "headImgUrl": "http://wx.qlogo.cn/mmhead/mock/0",
+            "nickName": "Mock Bot",
+            "expiredTime": 230,
+            "status": 2,
+            "loginInfo": {
+                "uin": 1234567890,
+                "wxid": MOCK_WXID,
+                "nickName": "Mock Bot",
+                "mobile": "13800000000",
+                "alias": "mock_bot",
+            },
+        }),
+        "/login/checkOnline" => json!(true),
+        "/personal/getProfile" => json!({
+            "alias": "mock_bot",
+            "wxid": MOCK_WXID,
+            "nickName": "Mock Bot",
+            "mobile": "13800000000",
+            "uin": 1234567890,
+            "sex": 1,
+            "province": "Shanghai",
+            "city": "Shanghai",
+            "signature": "Hello from the mock",
+            "country": "CN",
+            "bigHeadImgUrl": "http://wx.qlogo.cn/mmhead/mock/0",
+            "smallHeadImgUrl": "http://wx.qlogo.cn/mmhead/mock/132",
+            "regCountry": "CN",
+            "snsBgImg": null,
+        }),
+        "/personal/getQrCode" => json!({ "qrCode": "iVBORw0KGgo=" }),
+        "/personal/getSafetyInfo" => json!({
+            "list": [{
+                "uuid": "mock-device",
+                "deviceName": "iPad",
+                "deviceType": "iPad",
+                "lastTime": 1705043418,
+            }],
+        }),
+        "/contacts/fetchContactsList" | "/contacts/fetchContactsListCache" => json!({
+            "friends": MOCK_FRIENDS,
+            "chatrooms": [MOCK_CHATROOM],
+            "ghs": ["gh_mock_news"],
+        }),
+        "/contacts/search" => json!({
+            "v3": "v3_mock@stranger",
+            "nickName": "Searched User",
+            "sex": 2,
+            "signature": "",
+            "bigHeadImgUrl": "http://wx.qlogo.cn/mmhead/search/0",
+            "smallHeadImgUrl": "http://wx.qlogo.cn/mmhead/search/132",
+            "v4": "v4_mock@stranger",
+        }),
+        "/contacts/getBriefInfo" => {
+            Value::Array(wxids(body, "wxids").iter().map(|w| brief(w)).collect())
+        }
+        "/group/createChatroom" => json!({
+            "headImgBase64": "iVBORw0KGgo=",
+            "chatroomId": MOCK_CHATROOM,
+        }),
+        "/group/getChatroomInfo" => json!({
+            "chatroomId": str_param("chatroomId"),
+            "nickName": "Mock Group",
+            "pyInitial": "MOCKGROUP",
+            "quanPin": "mockgroup",
+            "sex": 0,
+            "remark": null,
+            "chatRoomNotify": 1,
+            "chatRoomOwner": MOCK_WXID,
+            "smallHeadImgUrl": "http://wx.qlogo.cn/mmcrhead/mock/0",
+            "memberList": members(),
+        }),
+        "/group/getChatroomMemberList" => json!({
+            "memberList": members(),
+            "chatroomOwner": MOCK_WXID,
+            "adminWxid": null,
+        }),
+        "/group/getChatroomMemberDetail" => Value::Array(
+            wxids(body, "memberWxids")
+                .iter()
+                .map(|w| {
+                    let mut detail = brief(w);
+                    detail["inviterUserName"] = json!(MOCK_WXID);
+                    detail["memberFlag"] = json!(0);
+                    detail
+                })
+                .collect(),
+        ),
+        "/group/getChatroomAnnouncement" => json!({
+            "announcement": "Welcome to the mock group",
+            "announcementEditor": MOCK_WXID,
+            "publishTime": 1705043418,
+        }),
+        "/group/getChatroomQrCode" => json!({
+            "qrBase64": "iVBORw0KGgo=",
+            "qrTips": "该二维码7天内(1月20日前)有效，重新进入将更新",
+        }),
+        "/group/modifyChatroomName"
+        | "/group/modifyChatroomRemark"
+        | "/group/modifyChatroomNickNameForSelf"
+        | "/group/inviteMember"
+        | "/group/removeMember"
+        | "/group/quitChatroom"
+        | "/group/disbandChatroom"
+        | "/group/setChatroomAnnouncement"
+        | "/group/agreeJoinRoom"
+        | "/group/addGroupMemberAsFriend"
+        | "/group/saveContractList"
+        | "/group/adminOperate"
+        | "/group/pinChat"
+        | "/group/setMsgSilence"
+        | "/group/joinRoomUsingQRCode"
+        | "/group/roomAccessApplyCheckApprove" => Value::Null,
+        "/label/add" => json!({ "labelName": str_param("labelName"), "labelId": 10 }),
+        "/label/list" => json!({
+            "labelList": [
+                { "labelName": "朋友", "labelId": 1 },
+                { "labelName": "同事", "labelId": 2 },
+            ],
+        }),
+        "/message/postText" => sent(body, 1),
+        "/message/postImage" => sent(body, 3),
+        "/message/postVoice" => sent(body, 34),
+        "/message/postNameCard" => sent(body, 42),
+        "/message/postVideo" => sent(body, 43),
+        "/message/postEmoji" => sent(body, 47),
+        "/message/postFile"
+        | "/message/postLink"
+        | "/message/postAppMsg"
+        | "/message/postMiniApp"
+        | "/message/forwardFile"
+        | "/message/forwardUrl"
+        | "/message/forwardMiniApp" => sent(body, 49),
+        "/message/forwardImage" => sent(body, 3),
+        "/message/forwardVideo" => sent(body, 43),
+        "/message/downloadCdn" => json!({ "fileUrl": "/20250101/mock_download.bin" }),
+        "/favor/sync" => {
+            if str_param("syncKey").is_empty() {
+                json!({
+                    "syncKey": "mock-sync-key-1",
+                    "list": [
+                        { "favId": 1, "type": 1, "flag": 0, "updateTime": 1705043418 },
+                        { "favId": 2, "type": 5, "flag": 0, "updateTime": 1705043500 },
+                    ],
+                })
+            } else {
+                json!({ "syncKey": str_param("syncKey"), "list": [] })
+            }
+        }
+        "/favor/getContent" => favorite(
+            body.get("favId")
+                .and_then(Value::as_i64)
+                .unwrap_or_default(),
+        ),
+        "/tools/setCallBack"
+        | "/login/logout"
+        | "/login/dialogLogin"
+        | "/personal/privacySettings"
+        | "/personal/updateProfile"
+        | "/personal/updateHeadImg"
+        | "/contacts/addContacts"
+        | "/contacts/deleteFriend"
+        | "/contacts/setFriendPermissions"
+        | "/contacts/setFriendRemark"
+        | "/contacts/uploadPhoneAddressList"
+        | "/label/delete"
+        | "/label/modifyMemberList"
+        | "/message/revokeMsg"
+        | "/favor/delete" => Value::Null,
+        _ => return None,
+    };
+    Some(ok(data))
+}
+
+fn wxids(body: &Value, key: &str) -> Vec<String> {
+    match body.get(key) {
+        Some(Value::Array(list)) => list
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        Some(Value::String(s)) => s.split(',').map(str::to_string).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn brief(wxid: &str) -> Value {
+    let name = wxid.trim_start_matches("wxid_mock_");
+    json!({
+        "userName": wxid,
+        "nickName": name,
+        "pyInitial": name.chars().next().map(|c| c.to_ascii_uppercase().to_string()).unwrap_or_default(),
+        "quanPin": name,
+        "sex": 1,
+        "remark": "",
+        "remarkPyInitial": "",
+        "remarkQuanPin": "",
+        "signature": null,
+        "alias": "",
+        "snsBgImg": null,
+        "country": "CN",
+        "bigHeadImgUrl": format!("http://wx.qlogo.cn/mmhead/{}/0", name),
+        "smallHeadImgUrl": format!("http://wx.qlogo.cn/mmhead/{}/132", name),
+        "description": null,
+        "cardImgUrl": null,
+        "labelList": "1",
+        "province": "Shanghai",
+        "city": "Shanghai",
+        "phoneNumList": null,
+    })
+}
+
+fn members() -> Value {
+    json!([
+        {
+            "wxid": MOCK_WXID,
+            "nickName": "Mock Bot",
+            "displayName": null,
+            "inviterUserName": MOCK_WXID,
+            "memberFlag": 0,
+            "bigHeadImgUrl": "http://wx.qlogo.cn/mmhead/mock/0",
+            "smallHeadImgUrl": "http://wx.qlogo.cn/mmhead/mock/132",
+        },
+        {
+            "wxid": MOCK_FRIENDS[0],
+            "nickName": "张三",
+            "displayName": "三哥",
+            "inviterUserName": MOCK_WXID,
+            "memberFlag": 0,
+            "bigHeadImgUrl": "http://wx.qlogo.cn/mmhead/zhangsan/0",
+            "smallHeadImgUrl": "http://wx.qlogo.cn/mmhead/zhangsan/132",
+        },
+    ])
+}
+
+fn sent(body: &Value, msg_type: u32) -> Value {
+    let new_msg_id = NEXT_MSG_ID.fetch_add(1, Ordering::Relaxed);
+    let create_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    json!({
+        "toWxid": body.get("toWxid").cloned().unwrap_or(Value::Null),
+        "createTime": create_time,
+        "msgId": new_msg_id % 1_000_000_000,
+        "newMsgId": new_msg_id,
+        "type": msg_type,
+    })
+}
+
+fn favorite(fav_id: i64) -> Value {
+    let content = match fav_id {
+        1 => {
+            r#"<favitem type="1"><desc><![CDATA[Remember to update the mock fixtures]]></desc><source sourcetype="1"><fromusr>wxid_mock_zhangsan</fromusr></source></favitem>"#
+        }
+        3 => {
+            r#"<favitem type="18"><title>Trip</title><desc>Photos of the trip</desc><datalist count="3"><dataitem datatype="2" dataid="d1"><datatitle>photo</datatitle><datafmt>jpg</datafmt><cdn_dataurl>http://mock.cdn/1</cdn_dataurl><cdn_datakey>key1</cdn_datakey><datasize>13</datasize></dataitem><dataitem datatype="2" dataid="d2"><datatitle>photo</datatitle><datafmt>jpg</datafmt><cdn_dataurl>http://mock.cdn/2</cdn_dataurl><cdn_datakey>key2</cdn_datakey><datasize>13</datasize></dataitem><dataitem datatype="8" dataid=""><datafmt>pdf</datafmt><cdn_dataurl>http://mock.cdn/3</cdn_dataurl><cdn_datakey>key3</cdn_datakey><datasize>13</datasize></dataitem></datalist></favitem>"#
+        }
+        _ => {
+            r#"<favitem type="5"><source sourcetype="1"><fromusr>wxid_mock_lisi</fromusr><link>https://example.com/rust</link></source><weburlitem><pagetitle>Rust</pagetitle><pagedesc>A language empowering everyone</pagedesc></weburlitem></favitem>"#
+        }
+    };
+    json!({
+        "favId": fav_id,
+        "status": 0,
+        "flag": 0,
+        "updateTime": 1705043418 + fav_id,
+        "content": content,
+    })
+}
@@ -0,0 +1,239 @@
+//! An in-process mock Gewe server for offline tests.
+//!
+//! [`MockServer`] answers every route wrapped by [`ApiClient`] with the fixture
+//! responses of [`fixtures`], records the requests it receives and can be told
+//! to fail routes, answer with other HTTP statuses or delay its responses.
+//!
+//! Payloads of the callbacks Gewe posts to bots are built with [`callback::CallbackSimulator`].
+//!
+//! Enabled by the `testing` feature.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use rgewe_api::testing::MockServer;
+//! use serde_json::json;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let server = MockServer::start().await.unwrap();
+//!     let client = server.client();
+//!
+//!     server.fail_route("/message/postText", 500, "发送失败");
+//!     let ret = client
+//!         .gewe_post_json("/message/postText", Some(json!({ "appId": "app", "toWxid": "filehelper", "content": "hi" })))
+//!         .await
+//!         .unwrap();
+//!     assert_eq!(ret["ret"], 500);
+//!     assert_eq!(server.requests_to("/message/postText").len(), 1);
+//! }
+//! ```
+pub mod callback;
+pub mod fixtures;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use crate::api::{ApiClient, ApiClientBuilder};
+
+/// A request received by the [`MockServer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    /// The route below `/v2/api`, e.g. `/message/postText`.
+    pub route: String,
+    /// The `X-GEWE-TOKEN` header.
+    pub token: String,
+    /// The json body, `Value::Null` if none was sent.
+    pub body: Value,
+}
+
+#[derive(Default)]
+struct MockState {
+    requests: Vec<RecordedRequest>,
+    responses: HashMap<String, Value>,
+    statuses: HashMap<String, u16>,
+    latency: Option<Duration>,
+    route_latency: HashMap<String, Duration>,
+}
+
+/// An in-process mock of the Gewe HTTP API, see the [module docs](self).
+///
+/// The server listens on a random local port and is shut down when dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Starts the server on a random port of `127.0.0.1`.
+    pub async fn start() -> Result<MockServer, Box<dyn Error>> {
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let app = Router::new()
+            .route("/v2/api/{*route}", post(handle))
+            .route("/download/{*file}", get(download))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await;
+        });
+        Ok(MockServer {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// The base url to configure an [`ApiClient`] with, e.g. `http://127.0.0.1:38211/v2/api`.
+    pub fn base_url(&self) -> String {
+        format!("http://{}/v2/api", self.addr)
+    }
+
+    /// The base url of the download service of the Gewe container, serving
+    /// [`fixtures::MOCK_DOWNLOAD`] for every file.
+    pub fn download_base_url(&self) -> String {
+        format!("http://{}/download/", self.addr)
+    }
+
+    /// An [`ApiClient`] pointing at this server, authenticated with [`fixtures::MOCK_TOKEN`].
+    pub fn client(&self) -> ApiClient {
+        ApiClientBuilder::new()
+            .with_token(fixtures::MOCK_TOKEN)
+            .with_base_url(&self.base_url())
+            .build()
+    }
+
+    /// All requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The requests received on `route`.
+    pub fn requests_to(&self, route: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.route == route)
+            .collect()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
+
+    /// Answers `route` with `response` (the whole body, including `ret` and `msg`) instead of its fixture.
+    pub fn set_response(&self, route: &str, response: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .responses
+            .insert(route.to_string(), response);
+    }
+
+    /// Makes `route` fail with the given `ret` code and `msg`, like Gewe reports failures.
+    pub fn fail_route(&self, route: &str, ret: i64, msg: &str) {
+        self.set_response(route, fixtures::failure(ret, msg));
+    }
+
+    /// Answers `route` with the HTTP status `status` and an empty body.
+    pub fn set_http_status(&self, route: &str, status: u16) {
+        self.state
+            .lock()
+            .unwrap()
+            .statuses
+            .insert(route.to_string(), status);
+    }
+
+    /// Delays every response by `latency`.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = Some(latency);
+    }
+
+    /// Delays the responses of `route` by `latency`, overriding [`MockServer::set_latency`].
+    pub fn set_route_latency(&self, route: &str, latency: Duration) {
+        self.state
+            .lock()
+            .unwrap()
+            .route_latency
+            .insert(route.to_string(), latency);
+    }
+
+    /// Removes all injected responses, statuses and latencies.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.responses.clear();
+        state.statuses.clear();
+        state.latency = None;
+        state.route_latency.clear();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn download() -> &'static [u8] {
+    fixtures::MOCK_DOWNLOAD
+}
+
+async fn handle(
+    State(state): State<Arc<Mutex<MockState>>>,
+    Path(route): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let route = format!("/{}", route);
+    // Missing or unparsable bodies are recorded as null.
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    let token = headers
+        .get("X-GEWE-TOKEN")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let (latency, status, response) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(RecordedRequest {
+            route: route.clone(),
+            token,
+            body: body.clone(),
+        });
+        (
+            state.route_latency.get(&route).copied().or(state.latency),
+            state.statuses.get(&route).copied(),
+            state.responses.get(&route).cloned(),
+        )
+    };
+    if let Some(latency) = latency {
+        tokio::time::sleep(latency).await;
+    }
+    if let Some(status) = status {
+        return StatusCode::from_u16(status)
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+            .into_response();
+    }
+    match response.or_else(|| fixtures::response(&route, &body)) {
+        Some(response) => Json(response).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
@@ -0,0 +1,160 @@
+//! `tracing` instrumentation of the API calls and callbacks, enabled by the `tracing` feature.
+//!
+//! Every [`ApiClient::gewe_post_json`](crate::api::ApiClient::gewe_post_json) call
+//! runs in a `gewe_api` span with the `route` and `app_id`, and records the
+//! `ret` code, `latency_ms` and error when it completes. Every callback received
+//! by the [`CallbackServer`](crate::callback::server::CallbackServer) is logged
+//! at `DEBUG` level, redacted, in a `gewe_callback` span; the span ends with the
+//! log line and does not cover the handling of the event.
+//!
+//! The token is never recorded. Message content, XML bodies and other sensitive
+//! fields are replaced by `<redacted>` in the logged callbacks, and in the
+//! request bodies logged at `TRACE` level unless enabled with
+//! [`ApiClientBuilder::with_trace_content`](crate::api::ApiClientBuilder::with_trace_content).
+use serde_json::Value;
+use std::error::Error;
+use std::time::Duration;
+use tracing::field::Empty;
+use tracing::Span;
+
+#[cfg(feature = "server")]
+use crate::callback::CallbackEvent;
+
+const REDACTED: &str = "<redacted>";
+
+/// Body and callback fields holding message content or secrets.
+const SENSITIVE_KEYS: [&str; 12] = [
+    "token",
+    "content",
+    "msgContent",
+    "xml",
+    "appmsg",
+    "aesKey",
+    "captchCode",
+    "phones",
+    "Content",
+    "PushContent",
+    "MsgSource",
+    "ImgBuf",
+];
+
+/// Replaces the sensitive fields of `value`, at any depth, with `<redacted>`.
+pub(crate) fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let v = if SENSITIVE_KEYS.contains(&k.as_str()) && !v.is_null() {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact(v)
+                    };
+                    (k.clone(), v)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+/// The span of an API call, logging its (redacted) body at `TRACE` level.
+pub(crate) fn api_span(route: &str, body: Option<&Value>, trace_content: bool) -> Span {
+    let app_id = body
+        .and_then(|b| b.get("appId"))
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "gewe_api",
+        route,
+        app_id,
+        ret = Empty,
+        latency_ms = Empty,
+        error = Empty,
+    );
+    if let Some(body) = body {
+        let body = if trace_content {
+            body.clone()
+        } else {
+            redact(body)
+        };
+        span.in_scope(|| tracing::trace!(%body, "request"));
+    }
+    span
+}
+
+/// Records the outcome of an API call on its span.
+pub(crate) fn record_api_result(
+    span: &Span,
+    ret: &Result<Value, Box<dyn Error>>,
+    latency: Duration,
+) {
+    span.record("latency_ms", latency.as_millis() as u64);
+    let _entered = span.enter();
+    match ret {
+        Ok(resp) => {
+            let code = resp.get("ret").and_then(Value::as_i64).unwrap_or_default();
+            span.record("ret", code);
+            if code == 200 {
+                tracing::debug!(ret = code, "response");
+            } else {
+                let msg = resp.get("msg").and_then(Value::as_str).unwrap_or_default();
+                tracing::warn!(ret = code, msg, "request failed");
+            }
+        }
+        Err(e) => {
+            span.record("error", tracing::field::display(e));
+            tracing::error!(error = %e, "request error");
+        }
+    }
+}
+
+/// The span of a received callback.
+#[cfg(feature = "server")]
+pub(crate) fn callback_span(event: &CallbackEvent) -> Span {
+    let (kind, msg_type, msg_id) = match event {
+        CallbackEvent::Message(msg) => ("AddMsg", msg.msg_type, msg.new_msg_id),
+        CallbackEvent::MessageRevoked(revoked) => {
+            ("AddMsg", revoked.notice.msg_type, revoked.notice.new_msg_id)
+        }
+        CallbackEvent::ContactModified { .. } => ("ModContacts", 0, 0),
+        CallbackEvent::ContactDeleted { .. } => ("DelContacts", 0, 0),
+        CallbackEvent::Offline { .. } => ("Offline", 0, 0),
+        CallbackEvent::Test => ("Test", 0, 0),
+        CallbackEvent::Other { type_name, .. } => (type_name.as_str(), 0, 0),
+    };
+    tracing::info_span!(
+        "gewe_callback",
+        kind,
+        app_id = event.app_id().unwrap_or_default(),
+        msg_type,
+        msg_id,
+    )
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::redact;
+    use crate::testing::callback::CallbackSimulator;
+
+    #[test]
+    fn test_redact_callback() {
+        let simulator = CallbackSimulator::new("wx_app", "wxid_self");
+        let payload = simulator
+            .group_text("34757816141@chatroom", "wxid_zhangsan", "你好 @李四")
+            .with_push_content("张三 : 你好 @李四")
+            .with_mentions(&["wxid_lisi"])
+            .build();
+        let logged = redact(&payload).to_string();
+        assert!(!logged.contains("你好"), "{}", logged);
+        assert!(!logged.contains("wxid_lisi"), "{}", logged);
+        assert_eq!(
+            redact(&payload)["Data"]["NewMsgId"],
+            payload["Data"]["NewMsgId"]
+        );
+        assert_eq!(
+            redact(&payload)["Data"]["FromUserName"],
+            payload["Data"]["FromUserName"]
+        );
+    }
+}
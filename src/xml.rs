@@ -0,0 +1,37 @@
+//! Helpers for the XML snippets embedded in Gewe callback messages.
+//!
+//! WeChat wraps most rich payloads (system notices, app messages, favorites)
+//! in small XML documents. Only read access is needed, so a lightweight DOM
+//! from `roxmltree` is used and the helpers here hide its lifetimes.
+use roxmltree::{Document, Node};
+
+/// Parses an XML document, ignoring surrounding whitespace.
+pub(crate) fn parse(xml: &str) -> Option<Document<'_>> {
+    Document::parse(xml.trim()).ok()
+}
+
+/// Finds the first descendant (including `node` itself) with the given tag name.
+pub(crate) fn find<'a, 'i>(node: Node<'a, 'i>, tag: &str) -> Option<Node<'a, 'i>> {
+    node.descendants().find(|n| n.has_tag_name(tag))
+}
+
+/// Returns the trimmed text (CDATA included) of the first descendant `tag`.
+pub(crate) fn text(node: Node, tag: &str) -> Option<String> {
+    find(node, tag).map(|n| node_text(n).trim().to_string())
+}
+
+/// Concatenates all direct text children of `node`.
+pub(crate) fn node_text(node: Node) -> String {
+    node.children()
+        .filter(|c| c.is_text())
+        .filter_map(|c| c.text())
+        .collect()
+}
+
+/// Escapes `s` for use in XML text and attribute values.
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,255 @@
+use rgewe_api::bot::dialog::{ConversationKey, Conversations, Dialog};
+use rgewe_api::bot::extract::{Args, Captures, Chatroom, Reply, Sender};
+use rgewe_api::bot::{Bot, Filter};
+use rgewe_api::callback::server::CallbackServer;
+use rgewe_api::callback::MessageType;
+use rgewe_api::testing::callback::CallbackSimulator;
+use rgewe_api::testing::{fixtures, MockServer};
+use std::error::Error;
+use std::time::Duration;
+
+async fn help(reply: Reply) -> Result<(), Box<dyn Error>> {
+    reply.text("commands: /echo").await?;
+    Ok(())
+}
+
+async fn echo(
+    Args(args): Args,
+    Sender(sender): Sender,
+    reply: Reply,
+) -> Result<(), Box<dyn Error>> {
+    reply
+        .text_at(
+            &format!("@{} {}", sender, args.join(" ")),
+            &[sender.as_str()],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn kick(Chatroom(chatroom): Chatroom, reply: Reply) -> Result<(), Box<dyn Error>> {
+    reply.text(&format!("kick in {}", chatroom)).await?;
+    Ok(())
+}
+
+async fn order(Captures(groups): Captures, reply: Reply) -> Result<(), Box<dyn Error>> {
+    reply.text(&format!("order {}", groups[0])).await?;
+    Ok(())
+}
+
+async fn image(reply: Reply) -> Result<(), Box<dyn Error>> {
+    reply.text("nice picture").await?;
+    Ok(())
+}
+
+#[derive(Clone)]
+struct Intake {
+    name: String,
+}
+
+async fn intake(dialog: Dialog) -> Result<(), Box<dyn Error>> {
+    let name = dialog.ask("name?").await?;
+    dialog.set_state(Intake { name });
+    let issue = dialog.ask("issue?").await?;
+    let state = dialog.state::<Intake>().unwrap();
+    dialog.say(&format!("{}: {}", state.name, issue)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bot_routing() {
+    let server = MockServer::start().await.unwrap();
+    let bot = Bot::new(server.client())
+        .with_command("/help", help)
+        .with_command("/echo", echo)
+        .with_command("/kick", kick)
+        .with_pattern(r"^order #(\d+)$", order)
+        .with_route(Filter::Type(MessageType::Image), image)
+        .with_route(Filter::All(vec![Filter::Chatroom, Filter::Mentioned]), help);
+    let sim = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID);
+    let last_sent = || {
+        let requests = server.requests_to("/message/postText");
+        let last = requests.last().unwrap().body.clone();
+        (
+            last["toWxid"].clone(),
+            last["content"].clone(),
+            last["ats"].clone(),
+        )
+    };
+
+    assert!(bot
+        .dispatch(&sim.text("wxid_friend", "/help").message())
+        .await
+        .unwrap());
+    assert_eq!(last_sent().1, "commands: /echo");
+
+    // `/helpme` is not the `/help` command.
+    assert!(!bot
+        .dispatch(&sim.text("wxid_friend", "/helpme").message())
+        .await
+        .unwrap());
+
+    let msg = sim
+        .group_text(
+            fixtures::MOCK_CHATROOM,
+            "wxid_friend",
+            "@Mock Bot\u{2005}/echo hello  world",
+        )
+        .with_mentions(&[fixtures::MOCK_WXID])
+        .message();
+    assert!(bot.dispatch(&msg).await.unwrap());
+    let (to, content, ats) = last_sent();
+    assert_eq!(to, fixtures::MOCK_CHATROOM);
+    assert_eq!(content, "@wxid_friend hello world");
+    assert_eq!(ats, "wxid_friend");
+
+    // `Chatroom` rejects private messages, no other route matches.
+    assert!(!bot
+        .dispatch(&sim.text("wxid_friend", "/kick").message())
+        .await
+        .unwrap());
+    bot.dispatch(
+        &sim.group_text(fixtures::MOCK_CHATROOM, "wxid_friend", "/kick")
+            .message(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        last_sent().1,
+        format!("kick in {}", fixtures::MOCK_CHATROOM)
+    );
+
+    bot.dispatch(&sim.text("wxid_friend", "order #42").message())
+        .await
+        .unwrap();
+    assert_eq!(last_sent().1, "order 42");
+
+    bot.dispatch(&sim.image("wxid_friend").message())
+        .await
+        .unwrap();
+    assert_eq!(last_sent().1, "nice picture");
+
+    let mentioned = sim
+        .group_text(
+            fixtures::MOCK_CHATROOM,
+            "wxid_friend",
+            "@Mock Bot\u{2005}hi",
+        )
+        .with_mentions(&[fixtures::MOCK_WXID])
+        .message();
+    bot.dispatch(&mentioned).await.unwrap();
+    assert_eq!(last_sent().1, "commands: /echo");
+
+    // Friends with a custom id are answered as well.
+    bot.dispatch(&sim.text("zhangsan_2024", "/echo hi").message())
+        .await
+        .unwrap();
+    let (to, content, _) = last_sent();
+    assert_eq!(to, "zhangsan_2024");
+    assert_eq!(content, "@zhangsan_2024 hi");
+
+    // Messages of the account itself never reach the handlers.
+    let sent = server.requests_to("/message/postText").len();
+    assert!(!bot
+        .dispatch(&sim.text("", "/help").from_self("wxid_friend").message())
+        .await
+        .unwrap());
+    assert_eq!(server.requests_to("/message/postText").len(), sent);
+}
+
+#[tokio::test]
+async fn test_bot_run() {
+    let mock = MockServer::start().await.unwrap();
+    let mut server = CallbackServer::bind("127.0.0.1:0").await.unwrap();
+    let sim = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID)
+        .with_event_sender(server.sender());
+    let bot = Bot::new(mock.client()).with_command("/help", help);
+
+    sim.send(&sim.text("wxid_friend", "/help").build())
+        .await
+        .unwrap();
+    let _ = tokio::time::timeout(Duration::from_millis(300), bot.run(&mut server)).await;
+    assert_eq!(mock.requests_to("/message/postText").len(), 1);
+}
+
+#[tokio::test]
+async fn test_dialog() {
+    let mock = MockServer::start().await.unwrap();
+    let bot = Bot::new(mock.client())
+        .with_command("/intake", intake)
+        .with_command("/help", help)
+        .with_dialog_timeout(Duration::from_millis(500));
+    let sim = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID);
+    let key = ConversationKey::new(fixtures::MOCK_CHATROOM, "wxid_alice");
+    let texts = || {
+        mock.requests_to("/message/postText")
+            .iter()
+            .map(|r| r.body["content"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    let answer = |text: &str| {
+        sim.group_text(fixtures::MOCK_CHATROOM, "wxid_alice", text)
+            .message()
+    };
+    let wait_for_question = || async {
+        for _ in 0..100 {
+            if bot.conversations().is_waiting(&key) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("the dialog does not wait for an answer");
+    };
+
+    assert!(bot.spawn(&answer("/intake")));
+    wait_for_question().await;
+    // Another member of the chatroom is routed as usual.
+    bot.dispatch(
+        &sim.group_text(fixtures::MOCK_CHATROOM, "wxid_bob", "/help")
+            .message(),
+    )
+    .await
+    .unwrap();
+    assert!(bot.spawn(&answer("Alice")));
+    for _ in 0..100 {
+        if bot.conversations().state::<Intake>(&key).is_some()
+            && bot.conversations().is_waiting(&key)
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    // `/help` is an answer here, not a command.
+    assert!(bot.spawn(&answer("/help")));
+    for _ in 0..100 {
+        if texts().len() == 4 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(
+        texts(),
+        vec!["name?", "commands: /echo", "issue?", "Alice: /help"]
+    );
+
+    // Without an answer the dialog times out, its state is cleared and the
+    // conversation is routed again.
+    assert!(bot.conversations().state::<Intake>(&key).is_some());
+    assert!(bot.spawn(&answer("/intake")));
+    wait_for_question().await;
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert!(!bot.conversations().is_waiting(&key));
+    assert!(bot.conversations().state::<Intake>(&key).is_none());
+    assert!(bot.dispatch(&answer("/help")).await.unwrap());
+    assert_eq!(texts().last().unwrap(), "commands: /echo");
+}
+
+#[tokio::test]
+async fn test_state_expiry() {
+    let conversations = Conversations::new().with_state_ttl(Duration::from_millis(50));
+    let key = ConversationKey::new(fixtures::MOCK_CHATROOM, "wxid_alice");
+    conversations.set_state(&key, 1u32);
+    assert_eq!(conversations.state::<u32>(&key), Some(1));
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(conversations.state::<u32>(&key), None);
+}
@@ -0,0 +1,170 @@
+use rgewe_api::callback::revoke::{MessageRevoked, RecentMessages};
+use rgewe_api::callback::system::{Member, SystemEvent};
+use rgewe_api::callback::{CallbackEvent, Message, MessageType};
+use rgewe_api::group::welcome::Welcomer;
+use serde_json::json;
+
+fn chatroom_msg(msg_type: u32, content: &str) -> Message {
+    let payload = json!({
+        "TypeName": "AddMsg",
+        "Appid": "wx_app",
+        "Wxid": "wxid_self",
+        "Data": {
+            "MsgId": 1040356095,
+            "FromUserName": {"string": "34757816141@chatroom"},
+            "ToUserName": {"string": "wxid_self"},
+            "MsgType": msg_type,
+            "Content": {"string": content},
+            "CreateTime": 1705043418,
+            "MsgSource": "<msgsource><atuserlist><![CDATA[,wxid_self]]></atuserlist></msgsource>",
+            "NewMsgId": 7773749793478223190i64,
+        }
+    });
+    match CallbackEvent::try_from(&payload).unwrap() {
+        CallbackEvent::Message(msg) => msg,
+        CallbackEvent::MessageRevoked(revoked) => revoked.notice,
+        other => panic!("unexpected event: {:?}", other),
+    }
+}
+
+fn nick(nickname: &str) -> Member {
+    Member {
+        wxid: None,
+        nickname: nickname.to_string(),
+    }
+}
+
+#[test]
+fn test_chatroom_text_message() {
+    let msg = chatroom_msg(1, "wxid_sender:\n@me hello");
+    assert_eq!(msg.message_type(), MessageType::Text);
+    assert_eq!(msg.chatroom_id(), Some("34757816141@chatroom"));
+    assert_eq!(msg.sender(), "wxid_sender");
+    assert_eq!(msg.text(), "@me hello");
+    assert!(msg.mentions("wxid_self"));
+}
+
+#[test]
+fn test_plain_invite_notice() {
+    let msg = chatroom_msg(10000, "\"张三\"邀请\"李四、王五\"加入了群聊");
+    assert_eq!(
+        SystemEvent::parse(&msg),
+        Some(SystemEvent::MemberJoined {
+            chatroom_id: "34757816141@chatroom".to_string(),
+            inviter: Some(nick("张三")),
+            members: vec![nick("李四"), nick("王五")],
+            via_qr: false,
+        })
+    );
+}
+
+#[test]
+fn test_template_qr_join() {
+    let content = r#"<sysmsg type="sysmsgtemplate"><sysmsgtemplate><content_template type="tmpl_type_profile"><plain><![CDATA[]]></plain><template><![CDATA["$adder$"通过扫描"$from$"分享的二维码加入群聊]]></template><link_list><link name="adder" type="link_profile"><memberlist><member><username><![CDATA[wxid_new]]></username><nickname><![CDATA[李四]]></nickname></member></memberlist></link><link name="from" type="link_profile"><memberlist><member><username><![CDATA[wxid_owner]]></username><nickname><![CDATA[张三]]></nickname></member></memberlist></link></link_list></content_template></sysmsgtemplate></sysmsg>"#;
+    let msg = chatroom_msg(10002, content);
+    let event = SystemEvent::parse(&msg).unwrap();
+    let SystemEvent::MemberJoined {
+        inviter,
+        members,
+        via_qr,
+        ..
+    } = &event
+    else {
+        panic!("unexpected event: {:?}", event);
+    };
+    assert!(via_qr);
+    assert_eq!(
+        inviter.as_ref().unwrap().wxid.as_deref(),
+        Some("wxid_owner")
+    );
+    assert_eq!(members[0].wxid.as_deref(), Some("wxid_new"));
+
+    let welcomer = Welcomer::new("{mentions} 欢迎，邀请人：{inviter}");
+    let (content, ats) = welcomer.render(members, inviter.as_ref());
+    assert_eq!(content, "@李四 欢迎，邀请人：张三");
+    assert_eq!(ats, "wxid_new");
+}
+
+#[test]
+fn test_other_notices() {
+    let kicked = chatroom_msg(10000, "你将\"李四\"移出了群聊");
+    assert!(matches!(
+        SystemEvent::parse(&kicked),
+        Some(SystemEvent::MemberRemoved { operator: Some(Member { wxid: Some(ref w), .. }), .. }) if w == "wxid_self"
+    ));
+
+    let renamed = chatroom_msg(10000, "\"张三\"修改群名为“Rust 学习群”");
+    assert!(matches!(
+        SystemEvent::parse(&renamed),
+        Some(SystemEvent::ChatroomRenamed { ref name, .. }) if name == "Rust 学习群"
+    ));
+
+    let owner = chatroom_msg(10000, "\"张三\"已成为新群主");
+    assert!(matches!(
+        SystemEvent::parse(&owner),
+        Some(SystemEvent::OwnerTransferred { ref new_owner, .. }) if new_owner.nickname == "张三"
+    ));
+}
+
+#[test]
+fn test_revoked_message() {
+    let recent = RecentMessages::new(2);
+    let original = chatroom_msg(1, "wxid_sender:\noops, wrong chat");
+    let revoke = chatroom_msg(
+        10002,
+        r#"wxid_sender:
+<sysmsg type="revokemsg"><revokemsg><session>34757816141@chatroom</session><msgid>1040356095</msgid><newmsgid>7773749793478223190</newmsgid><replacemsg><![CDATA["张三" 撤回了一条消息]]></replacemsg></revokemsg></sysmsg>"#,
+    );
+    let parsed = MessageRevoked::parse(&revoke).unwrap();
+    assert_eq!(parsed.chat_id, "34757816141@chatroom");
+    assert_eq!(parsed.revoker, "wxid_sender");
+    assert_eq!(parsed.new_msg_id, 7773749793478223190);
+    assert_eq!(parsed.replace_msg, "\"张三\" 撤回了一条消息");
+    assert_eq!(parsed.original, None);
+    assert_eq!(MessageRevoked::parse(&original), None);
+
+    assert_eq!(
+        recent.observe(&CallbackEvent::Message(original.clone())),
+        None
+    );
+    let revoked = recent
+        .observe(&CallbackEvent::Message(revoke.clone()))
+        .unwrap();
+    assert_eq!(revoked.original.unwrap().text(), "oops, wrong chat");
+    let event = CallbackEvent::MessageRevoked(Box::new(parsed.clone()));
+    assert_eq!(event.app_id(), Some("wx_app"));
+    let revoked = recent.observe(&event).unwrap();
+    assert_eq!(revoked.original.unwrap().text(), "oops, wrong chat");
+    assert_eq!(revoked.notice, revoke);
+    assert_eq!(recent.len(), 1);
+
+    // Messages pushed out of the buffer cannot be recovered.
+    let mut other = original.clone();
+    for new_msg_id in [1, 2] {
+        other.new_msg_id = new_msg_id;
+        recent.record(&other);
+    }
+    let revoked = recent.observe(&CallbackEvent::Message(revoke)).unwrap();
+    assert_eq!(revoked.new_msg_id, original.new_msg_id);
+    assert_eq!(revoked.original, None);
+}
+
+#[test]
+fn test_quote_message() {
+    let msg = chatroom_msg(
+        49,
+        r#"wxid_sender:
+<?xml version="1.0"?><msg><appmsg appid="" sdkver="0"><title>noon</title><des></des><type>57</type><refermsg><type>1</type><svrid>7773749793478223001</svrid><fromusr>34757816141@chatroom</fromusr><chatusr>wxid_alice</chatusr><displayname>Alice</displayname><content>what time?</content><createtime>1705043000</createtime></refermsg></appmsg><fromusername>wxid_sender</fromusername></msg>"#,
+    );
+    let app_msg = msg.app_msg().unwrap();
+    assert_eq!(app_msg.kind, 57);
+    assert_eq!(app_msg.title, "noon");
+    let quoted = msg.quoted().unwrap();
+    assert_eq!(quoted.msg_type, 1);
+    assert_eq!(quoted.new_msg_id, 7773749793478223001);
+    assert_eq!(quoted.sender(), "wxid_alice");
+    assert_eq!(quoted.display_name, "Alice");
+    assert_eq!(quoted.content, "what time?");
+    assert_eq!(quoted.create_time, 1705043000);
+    assert_eq!(chatroom_msg(1, "wxid_sender:\nhi").quoted(), None);
+}
@@ -1,11 +1,16 @@
+//! Smoke tests against the live services, run with `cargo test -- --ignored`.
+//! The offline equivalents against the mock server are in `tests/mock.rs`.
 use rgewe_api::api;
+
 #[tokio::test]
+#[ignore = "calls httpbin.org"]
 async fn test_get_ip() {
     let fu = api::test_get_ip();
     assert!(fu.await.is_ok());
 }
 
 #[tokio::test]
+#[ignore = "needs a Gewe service on localhost:2531"]
 async fn test_get_token() {
     let c = api::ApiClientBuilder::new().build();
     let ret = c.get_token().await.unwrap();
@@ -15,6 +20,7 @@ async fn test_get_token() {
 }
 
 #[tokio::test]
+#[ignore = "needs a Gewe service on localhost:2531"]
 async fn test_get_profile() {
     let no_token_c = api::ApiClientBuilder::new().build();
     let token = no_token_c
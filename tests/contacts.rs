@@ -0,0 +1,41 @@
+use rgewe_api::callback::CallbackEvent;
+use rgewe_api::contacts::directory::ContactDirectory;
+use serde_json::json;
+
+#[test]
+fn test_directory_mod_contacts() {
+    let directory = ContactDirectory::new("wx_app");
+    let modified = json!({
+        "TypeName": "ModContacts",
+        "Appid": "wx_app",
+        "Wxid": "wxid_self",
+        "Data": {
+            "UserName": {"string": "wxid_zhangsan"},
+            "NickName": {"string": "张三"},
+            "PyInitial": {"string": "ZS"},
+            "QuanPin": {"string": "zhangsan"},
+            "Remark": {"string": "老张"},
+            "Sex": 1,
+            "LabelIdList": "1,3",
+        }
+    });
+    directory.apply_event(&CallbackEvent::try_from(&modified).unwrap());
+
+    let contact = directory.get("wxid_zhangsan").unwrap();
+    assert_eq!(contact.name(), "老张");
+    assert_eq!(contact.label_ids, ["1", "3"]);
+    assert_eq!(directory.search("zs").len(), 1);
+    assert_eq!(directory.search("zhang").len(), 1);
+    assert_eq!(directory.find_by_remark("老张").len(), 1);
+    assert_eq!(directory.with_label("3").len(), 1);
+    assert!(directory.search("ls").is_empty());
+
+    let deleted = json!({
+        "TypeName": "DelContacts",
+        "Appid": "wx_app",
+        "Wxid": "wxid_self",
+        "Data": {"UserName": {"string": "wxid_zhangsan"}, "DeleteContactScen": 0}
+    });
+    directory.apply_event(&CallbackEvent::try_from(&deleted).unwrap());
+    assert!(directory.is_empty());
+}
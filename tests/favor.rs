@@ -0,0 +1,39 @@
+use rgewe_api::favor::item::{FavorItem, Favorite};
+use serde_json::json;
+
+#[test]
+fn test_parse_favorites() {
+    let link = json!({
+        "favId": 12,
+        "updateTime": 1720000000,
+        "content": r#"<favitem type="5"><source sourcetype="1"><fromusr>wxid_a</fromusr><link>https://example.com/post</link></source><datalist count="1"><dataitem datatype="5" dataid="d1"><datatitle>Post</datatitle></dataitem></datalist><weburlitem><pagetitle>A post</pagetitle><pagedesc>About Rust</pagedesc></weburlitem></favitem>"#,
+    });
+    let fav = Favorite::from_data(&link).unwrap();
+    assert_eq!(fav.fav_id, 12);
+    assert_eq!(fav.from_user, "wxid_a");
+    assert_eq!(
+        fav.item,
+        FavorItem::Link {
+            title: "A post".to_string(),
+            desc: "About Rust".to_string(),
+            url: "https://example.com/post".to_string(),
+            thumb_url: String::new(),
+        }
+    );
+
+    let file = r#"<favitem type="8"><datalist count="1"><dataitem datatype="8" dataid="d2"><datatitle>report.pdf</datatitle><datafmt>pdf</datafmt><datasize>2048</datasize><cdn_dataurl>http://cdn/x</cdn_dataurl><cdn_datakey>k</cdn_datakey></dataitem></datalist></favitem>"#;
+    let FavorItem::File { title, data } = FavorItem::parse(file).unwrap() else {
+        panic!("not a file");
+    };
+    assert_eq!(title, "report.pdf");
+    assert_eq!((data.format.as_str(), data.size), ("pdf", 2048));
+
+    let text = r#"<favitem type="1"><desc><![CDATA[hello
+world]]></desc></favitem>"#;
+    assert_eq!(
+        FavorItem::parse(text),
+        Some(FavorItem::Text {
+            text: "hello\nworld".to_string()
+        })
+    );
+}
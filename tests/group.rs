@@ -0,0 +1,71 @@
+use rgewe_api::callback::{CallbackEvent, Message};
+use rgewe_api::group::moderation::{Action, ModerationRule, Moderator, Rule};
+use serde_json::json;
+use std::time::Duration;
+
+fn chatroom_msg(sender: &str, text: &str, create_time: i64) -> Message {
+    let payload = json!({
+        "TypeName": "AddMsg",
+        "Appid": "wx_app",
+        "Wxid": "wxid_self",
+        "Data": {
+            "MsgId": 1,
+            "NewMsgId": create_time,
+            "FromUserName": {"string": "34757816141@chatroom"},
+            "ToUserName": {"string": "wxid_self"},
+            "MsgType": 1,
+            "Content": {"string": format!("{}:\n{}", sender, text)},
+            "CreateTime": create_time,
+        }
+    });
+    match CallbackEvent::try_from(&payload).unwrap() {
+        CallbackEvent::Message(msg) => msg,
+        other => panic!("unexpected event: {:?}", other),
+    }
+}
+
+#[test]
+fn test_moderation_rules() {
+    let moderator = Moderator::new("wx_app")
+        .with_exempt("wxid_admin")
+        .with_rule(ModerationRule::new(
+            "ads",
+            Rule::BannedKeywords(vec!["代购".to_string()]),
+            vec![Action::Warn("@{sender} 请勿发广告".to_string())],
+        ))
+        .with_rule(ModerationRule::new(
+            "links",
+            Rule::Links,
+            vec![Action::RemoveMember],
+        ))
+        .with_rule(ModerationRule::new(
+            "flood",
+            Rule::Flooding {
+                max_messages: 2,
+                window: Duration::from_secs(10),
+            },
+            vec![Action::RemoveMember],
+        ));
+
+    let names = |msg: &Message| -> Vec<String> {
+        moderator
+            .observe(msg)
+            .into_iter()
+            .map(|r| r.name.clone())
+            .collect()
+    };
+    assert_eq!(names(&chatroom_msg("wxid_a", "专业代购", 100)), ["ads"]);
+    assert_eq!(
+        names(&chatroom_msg("wxid_a", "see https://x.y", 101)),
+        ["links"]
+    );
+    assert_eq!(names(&chatroom_msg("wxid_a", "hi", 102)), ["flood"]);
+    // Checking does not count the message, even when repeated.
+    assert!(names(&chatroom_msg("wxid_b", "hi", 100)).is_empty());
+    let second = chatroom_msg("wxid_b", "hi", 101);
+    assert!(moderator.check(&second).is_empty());
+    assert!(moderator.check(&second).is_empty());
+    assert!(names(&second).is_empty());
+    assert!(names(&chatroom_msg("wxid_a", "hi", 120)).is_empty());
+    assert!(names(&chatroom_msg("wxid_admin", "代购 https://x.y", 121)).is_empty());
+}
@@ -0,0 +1,66 @@
+use rgewe_api::api::ApiClientBuilder;
+use rgewe_api::callback::server::CallbackServer;
+use rgewe_api::metrics::Metrics;
+use rgewe_api::testing::callback::CallbackSimulator;
+use rgewe_api::testing::{fixtures, MockServer};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_metrics() {
+    let server = MockServer::start().await.unwrap();
+    let metrics = Arc::new(Metrics::new());
+    let client = ApiClientBuilder::new()
+        .with_token(fixtures::MOCK_TOKEN)
+        .with_base_url(&server.base_url())
+        .with_metrics(metrics.clone())
+        .build();
+
+    client.check_online(fixtures::MOCK_APP_ID).await.unwrap();
+    server.fail_route("/personal/getProfile", 500, "获取失败");
+    client.get_profile(fixtures::MOCK_APP_ID).await.unwrap();
+    server.set_http_status("/personal/getProfile", 502);
+    assert!(client.get_profile(fixtures::MOCK_APP_ID).await.is_err());
+    metrics.set_queue_depth(3);
+
+    let mut callbacks = CallbackServer::bind_with_metrics("127.0.0.1:0", metrics.clone())
+        .await
+        .unwrap();
+    let sim = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID)
+        .with_receiver_url(&callbacks.url());
+    sim.send(&sim.text("wxid_alice", "hi").build())
+        .await
+        .unwrap();
+    sim.send(&sim.offline()).await.unwrap();
+    callbacks.next_event().await.unwrap();
+    callbacks.next_event().await.unwrap();
+
+    let url = format!("http://{}/metrics", callbacks.local_addr());
+    let text = reqwest::Client::builder()
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let app = fixtures::MOCK_APP_ID;
+    for line in [
+        format!(r#"gewe_api_requests_total{{app_id="{app}",route="/login/checkOnline"}} 1"#),
+        format!(r#"gewe_api_requests_total{{app_id="{app}",route="/personal/getProfile"}} 2"#),
+        format!(
+            r#"gewe_api_failures_total{{app_id="{app}",ret="500",route="/personal/getProfile"}} 1"#
+        ),
+        format!(
+            r#"gewe_api_failures_total{{app_id="{app}",ret="error",route="/personal/getProfile"}} 1"#
+        ),
+        format!(r#"gewe_api_latency_seconds_count{{app_id="{app}",route="/login/checkOnline"}} 1"#),
+        format!(r#"gewe_account_online{{app_id="{app}"}} 0"#),
+        format!(r#"gewe_callback_events_total{{app_id="{app}",kind="AddMsg"}} 1"#),
+        "gewe_outbound_queue_depth 3".to_string(),
+    ] {
+        assert!(text.contains(&line), "missing {} in\n{}", line, text);
+    }
+}
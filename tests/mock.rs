@@ -0,0 +1,851 @@
+use async_trait::async_trait;
+use rgewe_api::api::cassette::Cassette;
+use rgewe_api::api::interceptor::Interceptor;
+use rgewe_api::api::transport::{HttpTransport, Transport};
+use rgewe_api::api::{response_data, ApiClientBuilder, Wxid};
+use rgewe_api::broadcast::{Audience, Broadcast, Content};
+use rgewe_api::callback::server::CallbackServer;
+use rgewe_api::callback::system::SystemEvent;
+use rgewe_api::callback::{AppMsg, CallbackEvent, MessageType, Quotable};
+use rgewe_api::contacts::directory::{Contact, ContactDirectory};
+use rgewe_api::contacts::labels::LabelManager;
+use rgewe_api::favor::export::{ExportFormat, FavorExporter};
+use rgewe_api::favor::sync::FavorSync;
+use rgewe_api::group::member_cache::MemberCache;
+use rgewe_api::testing::callback::CallbackSimulator;
+use rgewe_api::testing::{fixtures, MockServer};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn test_mock_server() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+
+    let token = response_data(client.get_token().await.unwrap()).unwrap();
+    assert_eq!(token, fixtures::MOCK_TOKEN);
+
+    let to = Wxid::try_from("wxid_mock_zhangsan").unwrap();
+    let sent = response_data(
+        client
+            .post_text(fixtures::MOCK_APP_ID, &to, "hello", "")
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(sent["toWxid"], "wxid_mock_zhangsan");
+    assert!(sent["newMsgId"].as_i64().unwrap() > 0);
+
+    let requests = server.requests_to("/message/postText");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].token, fixtures::MOCK_TOKEN);
+    assert_eq!(requests[0].body["content"], "hello");
+
+    let favor = FavorSync::new(fixtures::MOCK_APP_ID);
+    let result = favor.sync(&client).await.unwrap();
+    assert_eq!(result.updated.len(), 2);
+    assert_eq!(favor.sync_key(), "mock-sync-key-1");
+
+    server.fail_route("/message/postText", 500, "发送失败");
+    let ret = client
+        .post_text(fixtures::MOCK_APP_ID, &to, "hello", "")
+        .await
+        .unwrap();
+    assert_eq!(ret["ret"], 500);
+    assert!(response_data(ret).is_err());
+
+    server.set_http_status("/personal/getProfile", 502);
+    assert!(client.get_profile(fixtures::MOCK_APP_ID).await.is_err());
+    assert!(client
+        .gewe_post_json("/unknown/route", Some(json!({})))
+        .await
+        .is_err());
+
+    server.reset();
+    server.set_route_latency("/login/checkOnline", Duration::from_millis(200));
+    let start = Instant::now();
+    let online = client.check_online(fixtures::MOCK_APP_ID).await.unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(online["data"], true);
+}
+
+#[tokio::test]
+async fn test_favor_sync_retries_failed() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+    let path = std::env::temp_dir().join(format!("rgewe_favor_state_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    server.fail_route("/favor/getContent", 500, "failed");
+    let favor = FavorSync::new(fixtures::MOCK_APP_ID)
+        .with_state_file(&path)
+        .unwrap();
+    let result = favor.sync(&client).await.unwrap();
+    assert_eq!(result.failed, [1, 2]);
+    assert_eq!(favor.sync_key(), "mock-sync-key-1");
+    assert_eq!(favor.pending(), [1, 2]);
+
+    // Uncommitted changes leave the state as it was.
+    server.reset();
+    let resumed = FavorSync::new(fixtures::MOCK_APP_ID)
+        .with_state_file(&path)
+        .unwrap();
+    assert_eq!(resumed.pending(), [1, 2]);
+    let changes = resumed.changes(&client).await.unwrap();
+    assert_eq!(changes.updated.len(), 2);
+    assert_eq!(resumed.pending(), [1, 2]);
+
+    // The failed favorites are fetched again after the key advanced.
+    let result = resumed.sync(&client).await.unwrap();
+    let ids: Vec<i32> = result.updated.iter().map(|fav| fav.fav_id).collect();
+    assert_eq!(ids, [1, 2]);
+    assert!(result.failed.is_empty());
+    assert!(resumed.pending().is_empty());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        r#"{"failed":[],"syncKey":"mock-sync-key-1"}"#
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_favor_export() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+    let dir = std::env::temp_dir().join(format!("rgewe_favor_export_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let exporter = FavorExporter::new(fixtures::MOCK_APP_ID, &dir, ExportFormat::Markdown)
+        .with_download_base_url(&server.download_base_url());
+    let manifest = || -> Value {
+        serde_json::from_slice(&std::fs::read(dir.join("manifest.json")).unwrap()).unwrap()
+    };
+
+    // The failed favorites are kept in the manifest, the key still advances.
+    server.fail_route("/favor/getContent", 500, "failed");
+    let report = exporter.export(&client).await.unwrap();
+    assert!(report.exported.is_empty());
+    assert_eq!(report.failed.len(), 2);
+    assert_eq!(manifest()["sync_key"], "mock-sync-key-1");
+    assert_eq!(manifest()["failed"], json!([1, 2]));
+
+    // The next export retries them.
+    server.reset();
+    let report = exporter.export(&client).await.unwrap();
+    assert_eq!(report.exported, [1, 2]);
+    assert!(report.failed.is_empty());
+    assert_eq!(manifest()["failed"], json!([]));
+    assert_eq!(
+        manifest()["favorites"][0]["files"],
+        json!(["1/favorite.md"])
+    );
+    let index = std::fs::read_to_string(dir.join("index.md")).unwrap();
+    assert!(index.contains("| 2024-01-12 07:10:20 | link | [Rust](2/favorite.md) |"));
+    assert!(index.contains("| 2024-01-12 07:10:19 | text |"));
+    let favorite = std::fs::read_to_string(dir.join("1/favorite.md")).unwrap();
+    assert!(favorite.contains("- Updated: 2024-01-12 07:10:19"));
+
+    // Nothing changed since.
+    let report = exporter.export(&client).await.unwrap();
+    assert!(report.exported.is_empty() && report.removed.is_empty());
+
+    // Attachments with the same or no title get distinct files.
+    server.set_response(
+        "/favor/sync",
+        fixtures::ok(json!({
+            "syncKey": "mock-sync-key-2",
+            "list": [
+                { "favId": 1, "type": 1, "flag": 1, "updateTime": 1705050000 },
+                { "favId": 3, "type": 18, "flag": 0, "updateTime": 1705050000 },
+            ],
+        })),
+    );
+    let report = exporter.export(&client).await.unwrap();
+    assert_eq!(report.removed, [1]);
+    assert_eq!(report.exported, [3]);
+    assert!(!dir.join("1").exists());
+    assert_eq!(manifest()["sync_key"], "mock-sync-key-2");
+    let files = [
+        "3/favorite.md",
+        "3/1_photo.jpg",
+        "3/2_photo.jpg",
+        "3/3_attachment.pdf",
+    ];
+    assert_eq!(manifest()["favorites"][1]["files"], json!(files));
+    for file in &files[1..] {
+        assert_eq!(
+            std::fs::read(dir.join(file)).unwrap(),
+            fixtures::MOCK_DOWNLOAD
+        );
+    }
+    let note = std::fs::read_to_string(dir.join("3/favorite.md")).unwrap();
+    assert!(note.contains("- [2_photo.jpg](2_photo.jpg)"));
+    assert!(note.contains("- [3_attachment.pdf](3_attachment.pdf)"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_callback_simulator() {
+    let mut server = CallbackServer::bind("127.0.0.1:0").await.unwrap();
+    let sim = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID)
+        .with_receiver_url(&server.url());
+
+    let question = sim.text("wxid_alice", "what time is it?").message();
+    let payloads = vec![
+        sim.group_text(fixtures::MOCK_CHATROOM, "wxid_alice", "@bot /help")
+            .with_mentions(&[fixtures::MOCK_WXID])
+            .build(),
+        sim.image("wxid_alice").build(),
+        sim.link(
+            "wxid_alice",
+            "Rust",
+            "A language",
+            "https://www.rust-lang.org",
+        )
+        .build(),
+        sim.quote("wxid_bob", "noon", &question).build(),
+        sim.friend_request("wxid_carol", "Carol", "Hi, I'm Carol")
+            .build(),
+        sim.member_joined(fixtures::MOCK_CHATROOM, "张三", &["李四", "王五"])
+            .build(),
+        sim.revoke(&question).build(),
+        sim.offline(),
+    ];
+    for payload in &payloads {
+        sim.send(payload).await.unwrap();
+    }
+
+    let mut messages = Vec::new();
+    for _ in 0..payloads.len() - 2 {
+        match server.next_event().await.unwrap() {
+            CallbackEvent::Message(msg) => messages.push(msg),
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+    assert_eq!(messages[0].sender(), "wxid_alice");
+    assert!(messages[0].mentions(fixtures::MOCK_WXID));
+    assert_eq!(messages[1].message_type(), MessageType::Image);
+    assert_eq!(messages[2].app_msg_type(), Some(5));
+    assert_eq!(messages[3].app_msg_type(), Some(57));
+    let quoted = messages[3].quoted().unwrap();
+    assert_eq!(quoted.new_msg_id, question.new_msg_id);
+    assert_eq!(quoted.sender(), "wxid_alice");
+    assert_eq!(quoted.content, "what time is it?");
+    assert_eq!(messages[4].message_type(), MessageType::FriendRequest);
+    assert!(matches!(
+        SystemEvent::parse(&messages[5]),
+        Some(SystemEvent::MemberJoined { members, .. }) if members.len() == 2
+    ));
+    let Some(CallbackEvent::MessageRevoked(revoked)) = server.next_event().await else {
+        panic!("expected a revocation");
+    };
+    assert_eq!(revoked.revoker, "wxid_alice");
+    assert_eq!(revoked.new_msg_id, question.new_msg_id);
+    assert!(matches!(
+        server.next_event().await,
+        Some(CallbackEvent::Offline { .. })
+    ));
+
+    let injected = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID)
+        .with_event_sender(server.sender());
+    injected
+        .send(
+            &injected
+                .text("wxid_alice", "hi")
+                .from_self("wxid_alice")
+                .build(),
+        )
+        .await
+        .unwrap();
+    let Some(CallbackEvent::Message(msg)) = server.try_next_event() else {
+        panic!("expected an injected message");
+    };
+    assert!(msg.is_from_self());
+    assert_eq!(msg.chat_id(), "wxid_alice");
+}
+
+#[tokio::test]
+async fn test_cassette_record_replay() {
+    let path = std::env::temp_dir().join(format!("rgewe_cassette_{}.json", std::process::id()));
+    let server = MockServer::start().await.unwrap();
+    let recording = ApiClientBuilder::new()
+        .with_token(fixtures::MOCK_TOKEN)
+        .with_base_url(&server.base_url())
+        .with_cassette(Cassette::record(&path))
+        .build();
+    let to = Wxid::try_from("wxid_mock_zhangsan").unwrap();
+    recording
+        .post_text(fixtures::MOCK_APP_ID, &to, "hello", "")
+        .await
+        .unwrap();
+    let profile = recording.get_profile(fixtures::MOCK_APP_ID).await.unwrap();
+    recording
+        .set_call_back(fixtures::MOCK_TOKEN, "http://127.0.0.1:18080/callback")
+        .await
+        .unwrap();
+    drop(server);
+
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    assert!(!recorded.contains(fixtures::MOCK_TOKEN));
+    assert!(!recorded.contains("wxid_mock"));
+    assert!(recorded.contains("wxid_redacted_1"));
+
+    let replaying = ApiClientBuilder::new()
+        .with_cassette(Cassette::replay(&path).unwrap())
+        .build();
+    let replayed = replaying.get_profile(fixtures::MOCK_APP_ID).await.unwrap();
+    assert_eq!(replayed["data"]["nickName"], profile["data"]["nickName"]);
+    assert_eq!(replayed["data"]["wxid"], "wxid_redacted_2");
+    assert!(replaying.get_profile(fixtures::MOCK_APP_ID).await.is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_cassette_replay_out_of_order() {
+    let path =
+        std::env::temp_dir().join(format!("rgewe_cassette_order_{}.json", std::process::id()));
+    let server = MockServer::start().await.unwrap();
+    let recording = ApiClientBuilder::new()
+        .with_token(fixtures::MOCK_TOKEN)
+        .with_base_url(&server.base_url())
+        .with_cassette(Cassette::record(&path))
+        .build();
+    let custom = Wxid::try_from("zhangsan_2024").unwrap();
+    let chatroom = Wxid::try_from(fixtures::MOCK_CHATROOM).unwrap();
+    let app_id = fixtures::MOCK_APP_ID;
+    let first = recording
+        .post_text(app_id, &custom, "first", "")
+        .await
+        .unwrap();
+    let second = recording
+        .post_text(app_id, &custom, "second", "")
+        .await
+        .unwrap();
+    recording
+        .post_text(app_id, &chatroom, "@zhangsan_2024 hi", "zhangsan_2024")
+        .await
+        .unwrap();
+    server.set_http_status("/personal/getProfile", 502);
+    let error = recording.get_profile(app_id).await.unwrap_err().to_string();
+    drop(server);
+
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    assert!(!recorded.contains("zhangsan_2024"));
+    assert!(!recorded.contains(fixtures::MOCK_CHATROOM));
+    assert!(recorded.contains("redacted_2@chatroom"));
+
+    let replaying = ApiClientBuilder::new()
+        .with_cassette(Cassette::replay(&path).unwrap())
+        .build();
+    let replayed = replaying
+        .post_text(app_id, &custom, "second", "")
+        .await
+        .unwrap();
+    assert_eq!(replayed["data"]["newMsgId"], second["data"]["newMsgId"]);
+    assert!(replaying
+        .post_text(app_id, &custom, "third", "")
+        .await
+        .is_err());
+    let replayed = replaying
+        .post_text(app_id, &custom, "first", "")
+        .await
+        .unwrap();
+    assert_eq!(replayed["data"]["newMsgId"], first["data"]["newMsgId"]);
+    // Another contact in place of the recorded one does not match.
+    let other = Wxid::try_from("lisi_2024").unwrap();
+    assert!(replaying
+        .post_text(app_id, &chatroom, "@lisi_2024 hi", "lisi_2024")
+        .await
+        .is_err());
+    replaying
+        .post_text(app_id, &chatroom, "@zhangsan_2024 hi", "zhangsan_2024")
+        .await
+        .unwrap();
+    assert!(replaying
+        .post_text(app_id, &other, "first", "")
+        .await
+        .is_err());
+    let replayed = replaying.get_profile(app_id).await.unwrap_err().to_string();
+    assert_eq!(replayed, error);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// A transport layer counting the calls it forwards.
+struct Counting {
+    inner: HttpTransport,
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl Transport for Counting {
+    async fn post(
+        &self,
+        route: &str,
+        token: &str,
+        body: Option<Value>,
+    ) -> Result<Value, Box<dyn Error>> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.inner.post(route, token, body).await
+    }
+}
+
+#[tokio::test]
+async fn test_custom_transport() {
+    let server = MockServer::start().await.unwrap();
+    let counting = Arc::new(Counting {
+        inner: HttpTransport::new(&server.base_url()),
+        calls: AtomicUsize::new(0),
+    });
+    let client = ApiClientBuilder::new()
+        .with_token(fixtures::MOCK_TOKEN)
+        .with_transport(counting.clone())
+        .build();
+    client.check_online(fixtures::MOCK_APP_ID).await.unwrap();
+    client.get_profile(fixtures::MOCK_APP_ID).await.unwrap();
+    assert_eq!(counting.calls.load(Ordering::Relaxed), 2);
+    assert_eq!(server.requests().len(), 2);
+}
+
+/// Signs every request and vetoes texts containing "secret".
+#[derive(Default)]
+struct Audit {
+    responses: Mutex<Vec<String>>,
+    errors: Mutex<Vec<String>>,
+}
+
+impl Interceptor for Audit {
+    fn before_request(&self, route: &str, body: &mut Value) -> Result<(), Box<dyn Error>> {
+        if body["content"]
+            .as_str()
+            .is_some_and(|c| c.contains("secret"))
+        {
+            return Err(format!("Vetoed {}", route).into());
+        }
+        if let Some(body) = body.as_object_mut() {
+            body.insert("signature".to_string(), json!("signed"));
+        }
+        Ok(())
+    }
+
+    fn after_response(&self, route: &str, _body: &Value, response: &Value) {
+        self.responses
+            .lock()
+            .unwrap()
+            .push(format!("{} {}", route, response["ret"]));
+    }
+
+    fn on_error(&self, route: &str, _body: &Value, error: &dyn Error) {
+        self.errors
+            .lock()
+            .unwrap()
+            .push(format!("{} {}", route, error));
+    }
+}
+
+#[tokio::test]
+async fn test_interceptors() {
+    let server = MockServer::start().await.unwrap();
+    let audit = Arc::new(Audit::default());
+    let client = ApiClientBuilder::new()
+        .with_token(fixtures::MOCK_TOKEN)
+        .with_base_url(&server.base_url())
+        .with_interceptor(audit.clone())
+        .build();
+    let to = Wxid::try_from("wxid_mock_zhangsan").unwrap();
+
+    client
+        .post_text(fixtures::MOCK_APP_ID, &to, "hello", "")
+        .await
+        .unwrap();
+    assert_eq!(
+        server.requests_to("/message/postText")[0].body["signature"],
+        "signed"
+    );
+    assert!(client
+        .post_text(fixtures::MOCK_APP_ID, &to, "the secret", "")
+        .await
+        .is_err());
+    assert_eq!(server.requests_to("/message/postText").len(), 1);
+
+    assert_eq!(
+        *audit.responses.lock().unwrap(),
+        vec!["/message/postText 200"]
+    );
+    assert_eq!(
+        *audit.errors.lock().unwrap(),
+        vec!["/message/postText Vetoed /message/postText"]
+    );
+}
+
+#[test]
+fn test_blocking_client() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let server = rt.block_on(MockServer::start()).unwrap();
+    let client = ApiClientBuilder::new()
+        .with_token(fixtures::MOCK_TOKEN)
+        .with_base_url(&server.base_url())
+        .build_blocking()
+        .unwrap();
+
+    let online = client.check_online(fixtures::MOCK_APP_ID).unwrap();
+    assert_eq!(online["data"], true);
+    let to = Wxid::try_from("wxid_mock_zhangsan").unwrap();
+    let sent = client
+        .post_text(fixtures::MOCK_APP_ID, &to, "hello", "")
+        .unwrap();
+    assert_eq!(sent["data"]["toWxid"], "wxid_mock_zhangsan");
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_revoke_sent_messages() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+    let to = Wxid::try_from("wxid_mock_zhangsan").unwrap();
+    let other = Wxid::try_from(fixtures::MOCK_CHATROOM).unwrap();
+
+    for content in ["one", "two", "three"] {
+        client
+            .post_text(fixtures::MOCK_APP_ID, &to, content, "")
+            .await
+            .unwrap();
+    }
+    let sent = client
+        .post_image(fixtures::MOCK_APP_ID, &other, "https://example.com/a.png")
+        .await
+        .unwrap();
+    assert_eq!(client.sent_messages().len(), 4);
+
+    let last = client.last_sent(&other).unwrap();
+    assert_eq!(last.route, "/message/postImage");
+    assert_eq!(last.new_msg_id, sent["data"]["newMsgId"].as_i64().unwrap());
+    client.revoke(last.handle).await.unwrap();
+    let revoke = &server.requests_to("/message/revokeMsg")[0].body;
+    assert_eq!(revoke["toWxid"], fixtures::MOCK_CHATROOM);
+    assert_eq!(revoke["newMsgId"], last.new_msg_id.to_string());
+    assert_eq!(revoke["createTime"], last.create_time.to_string());
+    assert!(client.revoke(last.handle).await.is_err());
+
+    // More messages than were sent are rejected before any call.
+    assert!(client.revoke_last(&to, 4).await.is_err());
+    assert_eq!(server.requests_to("/message/revokeMsg").len(), 1);
+    let tracked = client.sent_messages();
+    let outcomes = client.revoke_last(&to, 2).await.unwrap();
+    assert_eq!(
+        outcomes.iter().map(|r| r.handle).collect::<Vec<_>>(),
+        vec![tracked[2].handle, tracked[1].handle]
+    );
+    assert!(outcomes.iter().all(|r| r.result.is_ok()));
+    let revoked = server.requests_to("/message/revokeMsg");
+    assert_eq!(revoked.len(), 3);
+    // The most recent first.
+    assert_eq!(
+        revoked[1].body["newMsgId"],
+        tracked[2].new_msg_id.to_string()
+    );
+    assert_eq!(
+        revoked[2].body["newMsgId"],
+        tracked[1].new_msg_id.to_string()
+    );
+    assert_eq!(client.sent_messages(), vec![tracked[0].clone()]);
+
+    // A failed revocation is reported and the message stays tracked.
+    server.fail_route("/message/revokeMsg", 500, "failed");
+    let outcomes = client.revoke_last(&to, 1).await.unwrap();
+    assert_eq!(outcomes[0].handle, tracked[0].handle);
+    assert!(outcomes[0].result.is_err());
+    assert_eq!(client.sent_messages().len(), 1);
+}
+
+#[tokio::test]
+async fn test_reply_to() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+    let sim = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID);
+    let question = sim
+        .group_text(
+            fixtures::MOCK_CHATROOM,
+            "wxid_alice",
+            "<b>lunch</b> at noon?",
+        )
+        .message();
+
+    client.reply_to(&question, "yes & sure").await.unwrap();
+    let body = &server.requests_to("/message/postAppMsg")[0].body;
+    assert_eq!(body["appId"], fixtures::MOCK_APP_ID);
+    assert_eq!(body["toWxid"], fixtures::MOCK_CHATROOM);
+    let app_msg = AppMsg::parse(body["appmsg"].as_str().unwrap()).unwrap();
+    assert_eq!(app_msg.kind, 57);
+    assert_eq!(app_msg.title, "yes & sure");
+    let refer = app_msg.refer.unwrap();
+    assert_eq!(refer, question.refer_msg());
+    assert_eq!(refer.from_user, fixtures::MOCK_CHATROOM);
+    assert_eq!(refer.sender(), "wxid_alice");
+    assert_eq!(refer.content, "<b>lunch</b> at noon?");
+}
+
+#[tokio::test]
+async fn test_forward() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+    let sim = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID);
+    let to = [
+        Wxid::try_from("wxid_mock_zhangsan").unwrap(),
+        Wxid::try_from(fixtures::MOCK_CHATROOM).unwrap(),
+    ];
+
+    let text = sim
+        .group_text(fixtures::MOCK_CHATROOM, "wxid_alice", "hello")
+        .message();
+    let forwarded = client.forward(&text, &to).await.unwrap();
+    assert_eq!(forwarded.len(), 2);
+    assert_eq!(forwarded[1].to, to[1]);
+    assert_eq!(
+        forwarded[1].result.as_ref().unwrap()["toWxid"],
+        fixtures::MOCK_CHATROOM
+    );
+    let posted = server.requests_to("/message/postText");
+    assert_eq!(posted.len(), 2);
+    assert_eq!(posted[0].body["content"], "hello");
+
+    let image = sim.image("wxid_alice").message();
+    client.forward(&image, &to[..1]).await.unwrap();
+    assert_eq!(
+        server.requests_to("/message/forwardImage")[0].body["xml"],
+        image.text()
+    );
+    let link = sim
+        .link(
+            "wxid_alice",
+            "Rust",
+            "A language",
+            "https://www.rust-lang.org",
+        )
+        .message();
+    client.forward(&link, &to[..1]).await.unwrap();
+    assert_eq!(server.requests_to("/message/forwardUrl").len(), 1);
+    let quote = sim.quote("wxid_bob", "noon", &text).message();
+    client.forward(&quote, &to[..1]).await.unwrap();
+    assert_eq!(
+        server.requests_to("/message/postText")[2].body["content"],
+        "noon"
+    );
+
+    // Unsupported messages fail before any call, failed sends per recipient.
+    let mut voice = text.clone();
+    voice.msg_type = 34;
+    assert!(client.forward(&voice, &to).await.is_err());
+    server.fail_route("/message/forwardImage", 500, "failed");
+    let forwarded = client.forward(&image, &to).await.unwrap();
+    assert!(forwarded.iter().all(|f| f.result.is_err()));
+    assert_eq!(server.requests().len(), 7);
+}
+
+#[tokio::test]
+async fn test_label_members() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+    let labels = LabelManager::new(fixtures::MOCK_APP_ID);
+    let modified = || server.requests_to("/label/modifyMemberList");
+
+    // The fixture contacts have the label 朋友 (1), custom ids are accepted.
+    labels
+        .add_to(&client, "同事", &["wxid_mock_zhangsan", "zhangsan_2024"])
+        .await
+        .unwrap();
+    assert_eq!(modified().len(), 2);
+    assert_eq!(modified()[0].body["labelIds"], "1,2");
+    assert_eq!(modified()[0].body["wxIds"], json!(["wxid_mock_zhangsan"]));
+    assert_eq!(modified()[1].body["wxIds"], json!(["zhangsan_2024"]));
+    assert_eq!(
+        server.requests_to("/contacts/getBriefInfo")[1].body["wxids"],
+        json!(["zhangsan_2024"])
+    );
+
+    // Already tagged or unknown label: nothing to modify.
+    labels
+        .add_to(&client, "朋友", &["wxid_mock_lisi"])
+        .await
+        .unwrap();
+    labels
+        .remove_from(&client, "VIP", &["wxid_mock_lisi"])
+        .await
+        .unwrap();
+    assert_eq!(modified().len(), 2);
+
+    labels
+        .remove_from(&client, "朋友", &["wxid_mock_lisi"])
+        .await
+        .unwrap();
+    assert_eq!(modified()[2].body["labelIds"], "");
+    assert_eq!(modified()[2].body["wxIds"], json!(["wxid_mock_lisi"]));
+
+    labels
+        .set_labels(&client, "wxid_mock_lisi", &["同事", "VIP"])
+        .await
+        .unwrap();
+    assert_eq!(server.requests_to("/label/add")[0].body["labelName"], "VIP");
+    assert_eq!(modified()[3].body["labelIds"], "2,10");
+    assert_eq!(
+        labels.labels_of(&client, "wxid_mock_lisi").await.unwrap(),
+        ["朋友"]
+    );
+    assert!(labels.add_to(&client, "同事", &["bad id"]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_broadcast() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+    let directory = ContactDirectory::new(fixtures::MOCK_APP_ID);
+    directory.sync(&client, true).await.unwrap();
+    for (wxid, nick_name, remark, label_ids) in [
+        ("wxid_vip", "Alpha", "Shop Alpha", vec!["2"]),
+        ("wxid_blocked", "Blocked", "", vec!["2"]),
+        ("wxid_shop_b", "Shop Beta", "", vec![]),
+    ] {
+        directory.insert(Contact {
+            wxid: wxid.to_string(),
+            nick_name: nick_name.to_string(),
+            remark: remark.to_string(),
+            label_ids: label_ids.into_iter().map(str::to_string).collect(),
+            ..Contact::default()
+        });
+    }
+
+    let audience = Audience::new()
+        .with_label("同事")
+        .with_pattern("^Shop")
+        .with_wxids(&["wxid_mock_zhangsan", "wxid_vip"])
+        .with_chatrooms(&[fixtures::MOCK_CHATROOM])
+        .with_excluded(&["wxid_blocked"]);
+    let broadcast =
+        Broadcast::new(audience, Content::text("Hi {remark}")).with_interval(Duration::ZERO);
+    let planned = broadcast.preview(&client, &directory).await.unwrap();
+    let to: Vec<&str> = planned.iter().map(|p| p.to.as_str()).collect();
+    assert_eq!(
+        to,
+        [
+            "wxid_vip",
+            "wxid_shop_b",
+            "wxid_mock_zhangsan",
+            fixtures::MOCK_CHATROOM
+        ]
+    );
+    assert_eq!(planned[0].content, Content::text("Hi Shop Alpha"));
+    assert_eq!(planned[1].content, Content::text("Hi Shop Beta"));
+    assert!(server.requests_to("/message/postText").is_empty());
+
+    let report = broadcast.send(&client, &directory).await.unwrap();
+    assert_eq!(report.delivered.len(), 4);
+    assert!(report.failed.is_empty());
+    let posted = server.requests_to("/message/postText");
+    assert_eq!(posted[2].body["toWxid"], "wxid_mock_zhangsan");
+    assert_eq!(posted[2].body["content"], "Hi zhangsan");
+
+    let link = Content::link("For {remark}", "", "https://example.com", "");
+    let report = Broadcast::new(Audience::new().with_wxids(&["wxid_vip"]), link)
+        .send(&client, &directory)
+        .await
+        .unwrap();
+    assert_eq!(report.delivered[0].0, "wxid_vip");
+    assert_eq!(
+        server.requests_to("/message/postLink")[0].body["title"],
+        "For Shop Alpha"
+    );
+
+    server.fail_route("/message/postImage", 500, "failed");
+    let report = Broadcast::new(
+        Audience::new().with_wxids(&["wxid_vip", "wxid_shop_b"]),
+        Content::image("https://example.com/a.png"),
+    )
+    .with_interval(Duration::from_millis(10))
+    .send(&client, &directory)
+    .await
+    .unwrap();
+    assert_eq!(report.failed.len(), 2);
+
+    let unknown = Broadcast::new(Audience::new().with_label("missing"), Content::text("hi"));
+    assert!(unknown.preview(&client, &directory).await.is_err());
+}
+
+#[test]
+fn test_broadcast_render() {
+    let contact = Contact {
+        wxid: "wxid_tricky".to_string(),
+        nick_name: "{remark}".to_string(),
+        remark: "{wxid} {nickname}".to_string(),
+        ..Contact::default()
+    };
+    assert_eq!(
+        Content::text("Hi {remark} ({nickname}, {wxid}) {unknown}").render(&contact),
+        Content::text("Hi {wxid} {nickname} ({remark}, wxid_tricky) {unknown}")
+    );
+}
+
+#[tokio::test]
+async fn test_member_cache_events() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+    let sim = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID);
+    let cache = MemberCache::new(fixtures::MOCK_APP_ID);
+    let room = fixtures::MOCK_CHATROOM;
+    let event = |payload: Value| CallbackEvent::try_from(&payload).unwrap();
+
+    let report = cache.warm_up(&client).await.unwrap();
+    assert_eq!(report.cached, [room]);
+    assert_eq!(cache.cached(room).unwrap().members.len(), 2);
+
+    cache.apply_event(&event(
+        sim.member_invited(
+            room,
+            (fixtures::MOCK_WXID, "Mock Bot"),
+            &[("wxid_new", "李四"), ("wxid_other", "王五")],
+        )
+        .build(),
+    ));
+    let members = cache.cached(room).unwrap();
+    assert_eq!(members.members.len(), 4);
+    let joined = members.get("wxid_new").unwrap();
+    assert_eq!(joined.nick_name, "李四");
+    assert_eq!(joined.inviter.as_deref(), Some(fixtures::MOCK_WXID));
+
+    // Removed members are matched by chatroom nickname or nickname.
+    cache.apply_event(&event(sim.member_removed(room, &["三哥", "王五"]).build()));
+    let members = cache.cached(room).unwrap();
+    let wxids: Vec<&str> = members.members.iter().map(|m| m.wxid.as_str()).collect();
+    assert_eq!(wxids, [fixtures::MOCK_WXID, "wxid_new"]);
+
+    // Renames keep the entry, plain join notices without wxids drop it.
+    cache.apply_event(&event(
+        sim.chatroom_renamed(room, "张三", "Rust 学习群").build(),
+    ));
+    assert!(cache.cached(room).is_some());
+    cache.apply_event(&event(sim.member_joined(room, "张三", &["赵六"]).build()));
+    assert!(cache.cached(room).is_none());
+    assert_eq!(server.requests_to("/group/getChatroomMemberList").len(), 1);
+}
+
+#[tokio::test]
+async fn test_member_cache_ttl() {
+    let server = MockServer::start().await.unwrap();
+    let client = server.client();
+    let room = fixtures::MOCK_CHATROOM;
+
+    let cache = MemberCache::new(fixtures::MOCK_APP_ID);
+    cache.members(&client, room).await.unwrap();
+    cache.members(&client, room).await.unwrap();
+    assert_eq!(server.requests_to("/group/getChatroomMemberList").len(), 1);
+
+    let expired = MemberCache::new(fixtures::MOCK_APP_ID).with_ttl(Duration::ZERO);
+    expired.members(&client, room).await.unwrap();
+    expired.members(&client, room).await.unwrap();
+    assert_eq!(server.requests_to("/group/getChatroomMemberList").len(), 3);
+
+    server.fail_route("/group/getChatroomMemberList", 500, "failed");
+    let report = expired.warm_up(&client).await.unwrap();
+    assert!(report.cached.is_empty());
+    assert_eq!(report.failed[0].0, room);
+}
@@ -0,0 +1,113 @@
+use rgewe_api::api::{ApiClientBuilder, Wxid};
+use rgewe_api::callback::CallbackEvent;
+use rgewe_api::store::sqlite::SqliteStore;
+use rgewe_api::store::{Direction, MessageQuery, MessageStore, RecordingInterceptor};
+use rgewe_api::testing::callback::CallbackSimulator;
+use rgewe_api::testing::{fixtures, MockServer};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_sqlite_store() {
+    let store = Arc::new(SqliteStore::open_in_memory().unwrap());
+    let sim = CallbackSimulator::new(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID);
+
+    let incoming = [
+        sim.text("wxid_alice", "请把发票寄给我")
+            .with_create_time(1_700_000_000)
+            .build(),
+        sim.group_text(fixtures::MOCK_CHATROOM, "wxid_bob", "meeting moved to 3pm")
+            .with_create_time(1_700_000_100)
+            .build(),
+        sim.link(
+            "wxid_alice",
+            "Quarterly invoice",
+            "Q3 numbers",
+            "https://example.com/q3",
+        )
+        .with_create_time(1_700_000_200)
+        .build(),
+    ];
+    for payload in &incoming {
+        let event = CallbackEvent::try_from(payload).unwrap();
+        assert!(store.record_event(&event).await.unwrap());
+    }
+    // The same message is recorded once.
+    let event = CallbackEvent::try_from(&incoming[0]).unwrap();
+    assert!(!store.record_event(&event).await.unwrap());
+
+    let server = MockServer::start().await.unwrap();
+    let (recording, mut writer) = RecordingInterceptor::new(store.clone());
+    let client = ApiClientBuilder::new()
+        .with_token(fixtures::MOCK_TOKEN)
+        .with_base_url(&server.base_url())
+        .with_interceptor(recording.with_account(fixtures::MOCK_APP_ID, fixtures::MOCK_WXID))
+        .build();
+    let alice = Wxid::try_from("wxid_alice").unwrap();
+    client
+        .post_text(fixtures::MOCK_APP_ID, &alice, "发票已寄出", "")
+        .await
+        .unwrap();
+    server.fail_route("/message/postImage", 500, "failed");
+    client
+        .post_image(fixtures::MOCK_APP_ID, &alice, "https://example.com/a.png")
+        .await
+        .unwrap();
+    assert_eq!(writer.write_pending().await.unwrap(), 1);
+
+    let all = store.query(&MessageQuery::new()).await.unwrap();
+    assert_eq!(all.len(), 4);
+    // The failed image send is not recorded.
+    assert!(all.iter().all(|m| m.content != "https://example.com/a.png"));
+
+    let with_alice = store
+        .query(&MessageQuery::new().with_chat("wxid_alice"))
+        .await
+        .unwrap();
+    assert_eq!(with_alice.len(), 3);
+    let sent = &with_alice[0];
+    assert_eq!(sent.direction, Direction::Outgoing);
+    assert_eq!(sent.sender, fixtures::MOCK_WXID);
+    assert_eq!(sent.content, "发票已寄出");
+    assert!(sent.new_msg_id > 0);
+
+    let by_bob = store
+        .query(&MessageQuery::new().with_sender("wxid_bob"))
+        .await
+        .unwrap();
+    assert_eq!(by_bob.len(), 1);
+    assert_eq!(by_bob[0].chat_id, fixtures::MOCK_CHATROOM);
+    assert_eq!(by_bob[0].content, "meeting moved to 3pm");
+
+    let in_range = store
+        .query(&MessageQuery::new().with_time_range(1_700_000_000, 1_700_000_200))
+        .await
+        .unwrap();
+    assert_eq!(in_range.len(), 2);
+
+    let link = store
+        .query(&MessageQuery::new().with_text("invoice"))
+        .await
+        .unwrap();
+    assert_eq!(link.len(), 1);
+    let app_msg = link[0].app_msg.as_ref().unwrap();
+    assert_eq!(app_msg.kind, 5);
+    assert_eq!(app_msg.url, "https://example.com/q3");
+
+    // Substrings of Chinese texts, and terms shorter than three characters.
+    let invoices = store
+        .query(&MessageQuery::new().with_text("发票"))
+        .await
+        .unwrap();
+    assert_eq!(invoices.len(), 2);
+    let mailed = store
+        .query(&MessageQuery::new().with_text("票已寄"))
+        .await
+        .unwrap();
+    assert_eq!(mailed.len(), 1);
+
+    let latest = store
+        .query(&MessageQuery::new().with_limit(1))
+        .await
+        .unwrap();
+    assert_eq!(latest[0].content, "发票已寄出");
+}